@@ -12,6 +12,12 @@ pub enum Error {
 	/// Expected a field but found the end of the multipart data.
 	#[error("unexpected end of fields")]
 	UnexpectedEnd,
+	/// A field was larger than the limit set by its extractor, so it was not received in full.
+	#[error("field is larger than the limit of {limit} bytes")]
+	TooLarge {
+		/// The limit, in bytes.
+		limit: u64,
+	},
 	/// A custom error from an extractor, typically specific to that type.
 	#[error("could not parse to {target}: {error}")]
 	Custom {
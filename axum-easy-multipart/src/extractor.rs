@@ -32,7 +32,11 @@ pub enum Rejection {
 
 impl IntoResponse for Rejection {
 	fn into_response(self) -> Response {
-		(http::StatusCode::BAD_REQUEST, self.to_string()).into_response()
+		let status_code = match self {
+			Self::Error(Error::TooLarge { .. }) => http::StatusCode::PAYLOAD_TOO_LARGE,
+			_ => http::StatusCode::BAD_REQUEST,
+		};
+		(status_code, self.to_string()).into_response()
 	}
 }
 
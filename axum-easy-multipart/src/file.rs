@@ -56,6 +56,12 @@ pub trait MakeTempfile: Send + Sync {
 	///
 	/// Generally just propagates the error returned by `NamedTempFile::new`.
 	fn tempfile(&self) -> std::io::Result<NamedTempFile>;
+	/// The maximum size of the field, in bytes. Extraction stops with [`Error::TooLarge`] as soon as more than this has been received, so oversized fields are never written to disk in full.
+	///
+	/// Defaults to no limit.
+	fn max_size(&self) -> Option<u64> {
+		None
+	}
 }
 
 /// Provides the default behavior for creating [File]'s temporary file
@@ -89,10 +95,16 @@ impl<MakeTempfileImpl: MakeTempfile> FromSingleMultipartField for File<MakeTempf
 			})?
 			.into_parts();
 		let mut temp_file = tokio::fs::File::from_std(temp_file);
+		let max_size = make_tempfile.max_size();
 		let mut size = 0usize;
 
 		while let Some(chunk) = field.chunk().await.map_err(Error::Multipart)? {
 			size += chunk.len();
+			if let Some(limit) = max_size {
+				if u64::try_from(size).unwrap_or(u64::MAX) > limit {
+					return Err(Error::TooLarge { limit });
+				}
+			}
 			temp_file
 				.write_all(&chunk)
 				.await
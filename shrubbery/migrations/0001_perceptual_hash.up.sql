@@ -0,0 +1,2 @@
+-- difference hash of the image, see `helpers::perceptual_hash`; null = not an image or could not be decoded
+ALTER TABLE files ADD COLUMN perceptual_hash BIGINT;
@@ -0,0 +1,8 @@
+ALTER TABLE files
+	DROP COLUMN size,
+	DROP COLUMN width,
+	DROP COLUMN height,
+	DROP COLUMN duration,
+	DROP COLUMN capture_time,
+	DROP COLUMN camera_model,
+	DROP COLUMN orientation;
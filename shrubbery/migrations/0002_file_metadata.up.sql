@@ -0,0 +1,9 @@
+-- extracted from the file on upload and replacement, see `helpers::metadata`; null = unknown or not applicable to the media type
+ALTER TABLE files
+	ADD COLUMN size BIGINT, -- in bytes
+	ADD COLUMN width INTEGER,
+	ADD COLUMN height INTEGER,
+	ADD COLUMN duration DOUBLE PRECISION, -- in seconds
+	ADD COLUMN capture_time TIMESTAMP WITHOUT TIME ZONE, -- EXIF does not record the time zone
+	ADD COLUMN camera_model VARCHAR,
+	ADD COLUMN orientation SMALLINT CHECK (orientation BETWEEN 1 AND 8); -- EXIF orientation
@@ -0,0 +1 @@
+ALTER TABLE files ADD COLUMN mime_type VARCHAR; -- detected from the file's contents; null = uploaded before detection
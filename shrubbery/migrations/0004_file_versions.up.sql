@@ -0,0 +1,11 @@
+CREATE TABLE file_versions (
+	id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY, -- stored in the `versions` directory, named by this ID
+	file BIGINT NOT NULL REFERENCES files ON DELETE CASCADE,
+	number INTEGER NOT NULL, -- counts up from 1 for each file
+	media_type file_media_type NOT NULL,
+	mime_type VARCHAR,
+	size BIGINT,
+	replaced_by INTEGER REFERENCES users ON DELETE SET NULL, -- null = user was deleted
+	replaced_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(file, number)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE files DROP COLUMN deleted_by;
+ALTER TABLE files DROP COLUMN deleted_time;
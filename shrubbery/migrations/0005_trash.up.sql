@@ -0,0 +1,2 @@
+ALTER TABLE files ADD COLUMN deleted_time TIMESTAMP WITH TIME ZONE; -- null = not in the trash
+ALTER TABLE files ADD COLUMN deleted_by INTEGER REFERENCES users ON DELETE SET NULL; -- null = not in the trash or user was deleted
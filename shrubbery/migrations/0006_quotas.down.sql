@@ -0,0 +1,4 @@
+ALTER TABLE users DROP COLUMN max_files;
+ALTER TABLE users DROP COLUMN max_bytes;
+DROP INDEX files_uploaded_by;
+ALTER TABLE files DROP COLUMN uploaded_by;
@@ -0,0 +1,4 @@
+ALTER TABLE files ADD COLUMN uploaded_by INTEGER REFERENCES users ON DELETE SET NULL; -- null = uploaded before this was recorded or user was deleted
+CREATE INDEX files_uploaded_by ON files (uploaded_by);
+ALTER TABLE users ADD COLUMN max_bytes BIGINT CHECK (max_bytes >= 0); -- null = the configured default
+ALTER TABLE users ADD COLUMN max_files BIGINT CHECK (max_files >= 0); -- null = the configured default
@@ -0,0 +1,6 @@
+-- values cannot be removed from an enum, so it is recreated; this fails if any files still have the new media types
+ALTER TYPE file_media_type RENAME TO file_media_type_old;
+CREATE TYPE file_media_type AS ENUM ('image', 'video');
+ALTER TABLE files ALTER COLUMN media_type TYPE file_media_type USING media_type::text::file_media_type;
+ALTER TABLE file_versions ALTER COLUMN media_type TYPE file_media_type USING media_type::text::file_media_type;
+DROP TYPE file_media_type_old;
@@ -0,0 +1,3 @@
+ALTER TYPE file_media_type ADD VALUE 'audio';
+ALTER TYPE file_media_type ADD VALUE 'pdf';
+ALTER TYPE file_media_type ADD VALUE 'text';
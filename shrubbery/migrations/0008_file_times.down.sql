@@ -0,0 +1,2 @@
+ALTER TABLE files DROP COLUMN modified_time;
+ALTER TABLE files DROP COLUMN uploaded_time;
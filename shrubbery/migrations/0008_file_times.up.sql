@@ -0,0 +1,7 @@
+-- added without defaults so that existing files are null until they are filled in from the modification times of their stored content
+ALTER TABLE files ADD COLUMN uploaded_time TIMESTAMP WITH TIME ZONE;
+ALTER TABLE files ADD COLUMN modified_time TIMESTAMP WITH TIME ZONE; -- content or details last changed
+ALTER TABLE files ALTER COLUMN uploaded_time SET DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE files ALTER COLUMN modified_time SET DEFAULT CURRENT_TIMESTAMP;
+CREATE INDEX files_uploaded_time ON files (uploaded_time);
+CREATE INDEX files_modified_time ON files (modified_time);
@@ -0,0 +1,11 @@
+CREATE TABLE file_comments (
+	id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	file BIGINT NOT NULL REFERENCES files ON DELETE CASCADE,
+	body VARCHAR NOT NULL CHECK (body <> ''),
+	created_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	created_by INTEGER REFERENCES users ON DELETE SET NULL, -- null = user was deleted
+	edited_time TIMESTAMP WITH TIME ZONE -- null = never edited
+);
+CREATE TRIGGER file_comments_require_created_by_on_insertion BEFORE INSERT ON file_comments FOR EACH ROW EXECUTE PROCEDURE require_created_by('comment');
+CREATE INDEX file_comments_file ON file_comments (file);
+CREATE INDEX file_comments_created_time ON file_comments (created_time);
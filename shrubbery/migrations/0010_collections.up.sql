@@ -0,0 +1,17 @@
+CREATE TABLE collections (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	name VARCHAR NOT NULL UNIQUE,
+	description VARCHAR,
+	created_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	created_by INTEGER REFERENCES users ON DELETE SET NULL -- null = user was deleted
+);
+CREATE TRIGGER collections_require_created_by_on_insertion BEFORE INSERT ON collections FOR EACH ROW EXECUTE PROCEDURE require_created_by('collection');
+
+CREATE TABLE collection_files (
+	collection INTEGER NOT NULL REFERENCES collections ON DELETE CASCADE,
+	file BIGINT NOT NULL REFERENCES files ON DELETE CASCADE,
+	position INTEGER NOT NULL, -- files are ordered by this; there may be gaps where files were removed
+	PRIMARY KEY (collection, file),
+	UNIQUE (collection, position) DEFERRABLE INITIALLY DEFERRED -- deferred so that files can swap positions
+);
+CREATE INDEX collection_files_file ON collection_files (file);
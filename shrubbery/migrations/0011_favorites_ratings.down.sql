@@ -0,0 +1,2 @@
+DROP TABLE file_ratings;
+DROP TABLE file_favorites;
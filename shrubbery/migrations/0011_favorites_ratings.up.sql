@@ -0,0 +1,16 @@
+CREATE TABLE file_favorites (
+	file BIGINT NOT NULL REFERENCES files ON DELETE CASCADE,
+	favorited_by INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+	favorited_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (file, favorited_by)
+);
+CREATE INDEX file_favorites_favorited_by ON file_favorites (favorited_by);
+
+CREATE TABLE file_ratings (
+	file BIGINT NOT NULL REFERENCES files ON DELETE CASCADE,
+	rated_by INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+	rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+	rated_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (file, rated_by)
+);
+CREATE INDEX file_ratings_rated_by ON file_ratings (rated_by);
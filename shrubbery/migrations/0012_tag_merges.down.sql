@@ -0,0 +1,2 @@
+DROP TABLE tag_merges;
+DROP TABLE tag_aliases;
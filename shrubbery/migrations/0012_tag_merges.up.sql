@@ -0,0 +1,26 @@
+CREATE TABLE tag_aliases (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	name VARCHAR NOT NULL,
+	category INTEGER REFERENCES tag_categories ON DELETE CASCADE, -- null = no category
+	tag INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE, -- the tag that the alias stands for
+	created_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	created_by INTEGER REFERENCES users ON DELETE SET NULL, -- null = user was deleted
+	UNIQUE(name, category)
+);
+CREATE TRIGGER tag_aliases_require_created_by_on_insertion BEFORE INSERT ON tag_aliases FOR EACH ROW EXECUTE PROCEDURE require_created_by('tag alias');
+CREATE INDEX tag_aliases_tag ON tag_aliases (tag);
+-- nulls are distinct in the unique constraint, so aliases without a category need their own
+CREATE UNIQUE INDEX tag_aliases_uncategorized_name ON tag_aliases (name) WHERE category IS NULL;
+
+CREATE TABLE tag_merges (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	-- the merged tag is deleted, so its name and category's name are copied
+	source_name VARCHAR NOT NULL,
+	source_category VARCHAR, -- null = no category
+	target INTEGER REFERENCES tags ON DELETE SET NULL, -- null = the tag that was merged into has since been deleted
+	num_files INTEGER NOT NULL, -- how many files had the merged tag
+	kept_alias BOOLEAN NOT NULL,
+	merged_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	merged_by INTEGER REFERENCES users ON DELETE SET NULL -- null = user was deleted
+);
+CREATE INDEX tag_merges_target ON tag_merges (target);
@@ -0,0 +1,12 @@
+CREATE TABLE tag_implications (
+	id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	tag INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE,
+	implied INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE, -- added to every file that has `tag`
+	created_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	created_by INTEGER REFERENCES users ON DELETE SET NULL, -- null = user was deleted
+	UNIQUE(tag, implied),
+	-- longer cycles are rejected when implications are added, since a constraint can't see other rows
+	CHECK (tag <> implied)
+);
+CREATE TRIGGER tag_implications_require_created_by_on_insertion BEFORE INSERT ON tag_implications FOR EACH ROW EXECUTE PROCEDURE require_created_by('tag implication');
+CREATE INDEX tag_implications_implied ON tag_implications (implied);
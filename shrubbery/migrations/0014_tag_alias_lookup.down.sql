@@ -0,0 +1,17 @@
+CREATE OR REPLACE FUNCTION tags_by_name(desired_name tags.name%TYPE) RETURNS table(id tags.id%TYPE) STABLE LANGUAGE plpgsql AS $func$ BEGIN
+	IF count(*) = 0 FROM tags WHERE tags.name = desired_name THEN -- https://www.postgresql.org/docs/14/plpgsql-expressions.html
+		RAISE EXCEPTION using message = 'no tags by name', detail = desired_name;
+	END IF;
+	RETURN QUERY SELECT tags.id FROM tags WHERE tags.name = desired_name;
+END $func$;
+CREATE OR REPLACE FUNCTION tag_by_category_and_name(desired_category tag_categories.name%TYPE, desired_name tags.name%TYPE) RETURNS tags.id%TYPE STABLE LANGUAGE plpgsql AS $func$
+	DECLARE id tags.id%TYPE;
+	BEGIN
+		ASSERT desired_name IS NOT NULL, 'tag name is null';
+		SELECT tags.id INTO id FROM tags WHERE tags.name = desired_name AND tags.category IS NOT DISTINCT FROM tag_category_by_name(desired_category);
+		IF id IS NULL THEN
+			RAISE EXCEPTION using message = 'unknown tag', detail = desired_category, hint = desired_name; -- abusing exception fields
+		END IF;
+		RETURN id;
+	END
+$func$;
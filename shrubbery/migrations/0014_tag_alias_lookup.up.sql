@@ -0,0 +1,21 @@
+-- aliases are looked up after tags, so a tag is never hidden by an alias with the same name
+CREATE OR REPLACE FUNCTION tags_by_name(desired_name tags.name%TYPE) RETURNS table(id tags.id%TYPE) STABLE LANGUAGE plpgsql AS $func$ BEGIN
+	RETURN QUERY SELECT tags.id FROM tags WHERE tags.name = desired_name UNION SELECT tag_aliases.tag FROM tag_aliases WHERE tag_aliases.name = desired_name;
+	IF NOT FOUND THEN
+		RAISE EXCEPTION using message = 'no tags by name', detail = desired_name;
+	END IF;
+END $func$;
+CREATE OR REPLACE FUNCTION tag_by_category_and_name(desired_category tag_categories.name%TYPE, desired_name tags.name%TYPE) RETURNS tags.id%TYPE STABLE LANGUAGE plpgsql AS $func$
+	DECLARE id tags.id%TYPE;
+	BEGIN
+		ASSERT desired_name IS NOT NULL, 'tag name is null';
+		SELECT tags.id INTO id FROM tags WHERE tags.name = desired_name AND tags.category IS NOT DISTINCT FROM tag_category_by_name(desired_category);
+		IF id IS NULL THEN
+			SELECT tag_aliases.tag INTO id FROM tag_aliases WHERE tag_aliases.name = desired_name AND tag_aliases.category IS NOT DISTINCT FROM tag_category_by_name(desired_category);
+		END IF;
+		IF id IS NULL THEN
+			RAISE EXCEPTION using message = 'unknown tag', detail = desired_category, hint = desired_name; -- abusing exception fields
+		END IF;
+		RETURN id;
+	END
+$func$;
@@ -0,0 +1,3 @@
+DROP VIEW tag_category_tree;
+DROP FUNCTION tags_by_category_tree;
+ALTER TABLE tag_categories DROP COLUMN parent;
@@ -0,0 +1,17 @@
+ALTER TABLE tag_categories ADD COLUMN parent INTEGER REFERENCES tag_categories ON DELETE SET NULL; -- null = top level
+-- longer cycles are rejected when the parent is changed, since a constraint can't see other rows
+ALTER TABLE tag_categories ADD CONSTRAINT tag_categories_parent_not_self CHECK (parent <> id);
+CREATE INDEX tag_categories_parent ON tag_categories (parent);
+
+-- like tags_by_category, but also the tags in its subcategories, at any depth
+CREATE FUNCTION tags_by_category_tree(desired_category tag_categories.name%TYPE) RETURNS table(id tags.id%TYPE) STABLE LANGUAGE SQL AS $func$
+	WITH RECURSIVE tree (id) AS (SELECT tag_category_by_name(desired_category) UNION SELECT tag_categories.id FROM tree INNER JOIN tag_categories ON tag_categories.parent = tree.id)
+	SELECT tags.id FROM tags WHERE tags.category IN (SELECT id FROM tree)
+$func$;
+
+-- `path` is the names of the category's ancestors and then its own name, which orders categories so that each comes right before its subcategories
+CREATE VIEW tag_category_tree AS WITH RECURSIVE tree (id, depth, path) AS (
+	SELECT id, 0, ARRAY[name] FROM tag_categories WHERE parent IS NULL
+	UNION ALL
+	SELECT tag_categories.id, tree.depth + 1, tree.path || tag_categories.name FROM tree INNER JOIN tag_categories ON tag_categories.parent = tree.id
+) SELECT id, depth, path FROM tree;
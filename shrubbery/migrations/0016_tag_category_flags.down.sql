@@ -0,0 +1,2 @@
+ALTER TABLE tag_categories DROP COLUMN required;
+ALTER TABLE tag_categories DROP COLUMN exclusive;
@@ -0,0 +1,2 @@
+ALTER TABLE tag_categories ADD COLUMN exclusive BOOLEAN NOT NULL DEFAULT FALSE; -- files can have at most one tag directly in the category
+ALTER TABLE tag_categories ADD COLUMN required BOOLEAN NOT NULL DEFAULT FALSE; -- files aren't fully tagged until they have a tag in the category or its subcategories
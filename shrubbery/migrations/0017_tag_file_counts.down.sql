@@ -0,0 +1,11 @@
+DROP TRIGGER files_count_tags_on_deletion ON files;
+DROP TRIGGER files_count_tags_on_update ON files;
+DROP TRIGGER file_tags_count_on_update_removed ON file_tags;
+DROP TRIGGER file_tags_count_on_update_added ON file_tags;
+DROP TRIGGER file_tags_count_on_deletion ON file_tags;
+DROP TRIGGER file_tags_count_on_insertion ON file_tags;
+DROP FUNCTION count_deleted_file_tags;
+DROP FUNCTION count_trashed_file_tags;
+DROP FUNCTION count_removed_file_tags;
+DROP FUNCTION count_added_file_tags;
+ALTER TABLE tags DROP COLUMN num_files;
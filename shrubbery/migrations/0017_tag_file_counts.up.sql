@@ -0,0 +1,40 @@
+-- kept up to date by triggers so that the tags index can sort by it without counting every tag's files
+-- files in the trash don't count, as they aren't found by searching for the tag
+ALTER TABLE tags ADD COLUMN num_files BIGINT NOT NULL DEFAULT 0;
+UPDATE tags SET num_files = (SELECT count(*) FROM file_tags INNER JOIN files ON file_tags.file = files.id WHERE file_tags.tag = tags.id AND files.deleted_time IS NULL);
+
+-- the tags are locked in order first, so that two statements changing the same tags can't deadlock
+CREATE FUNCTION count_added_file_tags() RETURNS TRIGGER LANGUAGE PLPGSQL AS $func$ BEGIN
+	PERFORM 1 FROM tags WHERE id IN (SELECT tag FROM added) ORDER BY id FOR NO KEY UPDATE;
+	UPDATE tags SET num_files = num_files + changes.count FROM (SELECT added.tag, count(*) FROM added INNER JOIN files ON added.file = files.id WHERE files.deleted_time IS NULL GROUP BY added.tag) AS changes WHERE tags.id = changes.tag;
+	RETURN NULL;
+END $func$;
+-- rows removed along with their file were already uncounted by count_deleted_file_tags, as the file is gone by now
+CREATE FUNCTION count_removed_file_tags() RETURNS TRIGGER LANGUAGE PLPGSQL AS $func$ BEGIN
+	PERFORM 1 FROM tags WHERE id IN (SELECT tag FROM removed) ORDER BY id FOR NO KEY UPDATE;
+	UPDATE tags SET num_files = num_files - changes.count FROM (SELECT removed.tag, count(*) FROM removed INNER JOIN files ON removed.file = files.id WHERE files.deleted_time IS NULL GROUP BY removed.tag) AS changes WHERE tags.id = changes.tag;
+	RETURN NULL;
+END $func$;
+CREATE TRIGGER file_tags_count_on_insertion AFTER INSERT ON file_tags REFERENCING NEW TABLE AS added FOR EACH STATEMENT EXECUTE PROCEDURE count_added_file_tags();
+CREATE TRIGGER file_tags_count_on_deletion AFTER DELETE ON file_tags REFERENCING OLD TABLE AS removed FOR EACH STATEMENT EXECUTE PROCEDURE count_removed_file_tags();
+-- rows aren't moved between tags by updating them, but this keeps the counts right if they ever are
+CREATE TRIGGER file_tags_count_on_update_added AFTER UPDATE ON file_tags REFERENCING NEW TABLE AS added FOR EACH STATEMENT EXECUTE PROCEDURE count_added_file_tags();
+CREATE TRIGGER file_tags_count_on_update_removed AFTER UPDATE ON file_tags REFERENCING OLD TABLE AS removed FOR EACH STATEMENT EXECUTE PROCEDURE count_removed_file_tags();
+
+-- moving files to and from the trash
+CREATE FUNCTION count_trashed_file_tags() RETURNS TRIGGER LANGUAGE PLPGSQL AS $func$ BEGIN
+	PERFORM 1 FROM tags WHERE id IN (SELECT file_tags.tag FROM old_files INNER JOIN new_files ON old_files.id = new_files.id INNER JOIN file_tags ON file_tags.file = new_files.id WHERE (old_files.deleted_time IS NULL) <> (new_files.deleted_time IS NULL)) ORDER BY id FOR NO KEY UPDATE;
+	UPDATE tags SET num_files = num_files + changes.count FROM (SELECT file_tags.tag, sum(CASE WHEN new_files.deleted_time IS NULL THEN 1 ELSE -1 END) AS count FROM old_files INNER JOIN new_files ON old_files.id = new_files.id INNER JOIN file_tags ON file_tags.file = new_files.id WHERE (old_files.deleted_time IS NULL) <> (new_files.deleted_time IS NULL) GROUP BY file_tags.tag) AS changes WHERE tags.id = changes.tag;
+	RETURN NULL;
+END $func$;
+-- transition tables can't be used with a column list, so this runs on every update of files
+CREATE TRIGGER files_count_tags_on_update AFTER UPDATE ON files REFERENCING OLD TABLE AS old_files NEW TABLE AS new_files FOR EACH STATEMENT EXECUTE PROCEDURE count_trashed_file_tags();
+-- files are normally deleted from the trash, but this keeps the counts right if one is deleted without going through it
+CREATE FUNCTION count_deleted_file_tags() RETURNS TRIGGER LANGUAGE PLPGSQL AS $func$ BEGIN
+	IF OLD.deleted_time IS NULL THEN
+		PERFORM 1 FROM tags WHERE id IN (SELECT tag FROM file_tags WHERE file = OLD.id) ORDER BY id FOR NO KEY UPDATE;
+		UPDATE tags SET num_files = num_files - 1 WHERE id IN (SELECT tag FROM file_tags WHERE file = OLD.id);
+	END IF;
+	RETURN OLD;
+END $func$;
+CREATE TRIGGER files_count_tags_on_deletion BEFORE DELETE ON files FOR EACH ROW EXECUTE PROCEDURE count_deleted_file_tags();
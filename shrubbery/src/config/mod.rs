@@ -14,9 +14,84 @@ pub struct Config {
 	pub num_workers: Option<usize>,
 	pub database_url: String,
 	/// Should be a persistent location to maintain database integrity.
+	///
+	/// Temporary files are always kept here. With local storage, this is also where the content of files is stored.
 	pub file_storage: PathBuf,
+	#[serde(default)]
+	pub storage: StorageBackend,
 	#[serde(default = "default_cookie_signing_key")]
 	pub cookie_signing_key: TokenKey,
+	/// The maximum Hamming distance between the perceptual hashes of two images for them to be considered similar.
+	#[serde(default = "default_similarity_threshold")]
+	pub similarity_threshold: u32,
+	#[serde(default)]
+	pub version_retention: VersionRetention,
+	/// Files in the trash are purged after this many days. If zero, they are kept until they are purged manually.
+	#[serde(default = "default_trash_retention_days")]
+	pub trash_retention_days: u32,
+	#[serde(default)]
+	pub upload_limits: UploadLimits,
+}
+
+/// Limits on what editors can upload. Each limit is unlimited if unset.
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct UploadLimits {
+	/// The maximum size of a single file, in bytes. Enforced while the file is being received.
+	pub file_size: Option<u64>,
+	/// The maximum total size of the files that each user has uploaded, in bytes. Can be overridden for each user.
+	pub user_bytes: Option<u64>,
+	/// The maximum number of files that each user has uploaded. Can be overridden for each user.
+	pub user_files: Option<u64>,
+}
+
+/// How many old versions of replaced files are kept, and for how long.
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct VersionRetention {
+	/// The number of old versions kept for each file, in addition to the current content. Unlimited if unset.
+	pub max_versions: Option<u32>,
+	/// Old versions are removed once they have been replaced for this many days. Kept forever if unset.
+	pub max_age_days: Option<u32>,
+}
+
+/// Where the content of files is stored.
+#[derive(Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageBackend {
+	/// In `file_storage`.
+	Local {
+		/// The number of levels of subdirectories that files are spread across, to keep directories small.
+		#[serde(default)]
+		shard_levels: u8,
+	},
+	/// In a bucket of Amazon S3 or a compatible server.
+	S3(S3Config),
+}
+
+impl Default for StorageBackend {
+	fn default() -> Self {
+		Self::Local { shard_levels: 0 }
+	}
+}
+
+#[derive(Deserialize)]
+pub struct S3Config {
+	/// For example, `https://s3.us-east-1.amazonaws.com` or `http://localhost:9000`.
+	pub endpoint: String,
+	pub bucket: String,
+	#[serde(default = "default_s3_region")]
+	pub region: String,
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	/// Puts the bucket in the path rather than the host name, which most self-hosted servers require.
+	#[serde(default)]
+	pub path_style: bool,
+	/// Prepended to the name of every object, to share a bucket with other data.
+	#[serde(default)]
+	pub prefix: String,
+}
+
+fn default_s3_region() -> String {
+	"us-east-1".into()
 }
 
 fn deserialize_level_filter<'de, D: serde::de::Deserializer<'de>>(
@@ -83,6 +158,14 @@ const fn default_log_level() -> LogLevel {
 	}
 }
 
+const fn default_similarity_threshold() -> u32 {
+	10
+}
+
+const fn default_trash_retention_days() -> u32 {
+	30
+}
+
 fn default_cookie_signing_key() -> TokenKey {
 	let generated = TokenKey::generate();
 	let encoded = base64::encode(generated.as_raw_data());
@@ -0,0 +1,20 @@
+use ormx::Table;
+
+use super::UserId;
+use crate::timestamp::Timestamp;
+
+pub type Id = super::Id;
+
+#[derive(Table)]
+#[ormx(table = "collections", insertable = Create, deletable)]
+pub struct Collection {
+	#[ormx(get_optional = by_id(Id))]
+	pub id: Id,
+	#[ormx(set)]
+	pub name: String,
+	#[ormx(set)]
+	pub description: Option<String>,
+	#[ormx(custom_type, default)]
+	pub created_time: Timestamp,
+	pub created_by: Option<UserId>,
+}
@@ -1,6 +1,7 @@
 use ormx::Table;
 
 use super::media_type::MediaType;
+use crate::timestamp::Timestamp;
 
 pub type Id = super::BigId;
 
@@ -13,4 +14,25 @@ pub struct File {
 	pub description: Option<String>,
 	#[ormx(custom_type)]
 	pub media_type: MediaType,
+	pub mime_type: Option<String>,
+	pub perceptual_hash: Option<i64>,
+	pub size: Option<i64>,
+	pub width: Option<i32>,
+	pub height: Option<i32>,
+	pub duration: Option<f64>,
+	pub capture_time: Option<time::PrimitiveDateTime>,
+	pub camera_model: Option<String>,
+	pub orientation: Option<i16>,
+	/// `None` if the file is not in the trash.
+	#[ormx(custom_type, default)]
+	pub deleted_time: Option<Timestamp>,
+	/// `None` if the uploader is unknown or was deleted.
+	#[ormx(default)]
+	pub uploaded_by: Option<super::UserId>,
+	/// `None` for files uploaded before this was recorded, until it is filled in at startup.
+	#[ormx(custom_type, default)]
+	pub uploaded_time: Option<Timestamp>,
+	/// When the content or details of the file last changed. `None` in the same cases as `uploaded_time`.
+	#[ormx(custom_type, default)]
+	pub modified_time: Option<Timestamp>,
 }
@@ -0,0 +1,3 @@
+//! Comments are only accessed through queries that join them with their authors, so there is no model struct.
+
+pub type Id = super::BigId;
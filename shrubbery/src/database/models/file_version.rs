@@ -0,0 +1,3 @@
+//! Old versions of files are only accessed through queries that join them with other tables, so there is no model struct.
+
+pub type Id = super::BigId;
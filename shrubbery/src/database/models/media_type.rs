@@ -7,10 +7,13 @@ use std::fmt::{self, Display, Formatter};
 pub enum MediaType {
 	Image,
 	Video,
+	Audio,
+	Pdf,
+	Text,
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("invalid media type; expected `image` or `video`")]
+#[error("invalid media type; expected `image`, `video`, `audio`, `pdf` or `text`")]
 pub struct FromStrError;
 
 impl std::str::FromStr for MediaType {
@@ -20,6 +23,9 @@ impl std::str::FromStr for MediaType {
 		match raw {
 			"image" | "Image" => Ok(Self::Image),
 			"video" | "Video" => Ok(Self::Video),
+			"audio" | "Audio" => Ok(Self::Audio),
+			"pdf" | "PDF" => Ok(Self::Pdf),
+			"text" | "Text" => Ok(Self::Text),
 			_ => Err(FromStrError),
 		}
 	}
@@ -34,39 +40,12 @@ impl Display for MediaType {
 			(Self::Image, false) => "image",
 			(Self::Video, true) => "Video",
 			(Self::Video, false) => "video",
+			(Self::Audio, true) => "Audio",
+			(Self::Audio, false) => "audio",
+			(Self::Pdf, true) => "PDF",
+			(Self::Pdf, false) => "pdf",
+			(Self::Text, true) => "Text",
+			(Self::Text, false) => "text",
 		})
 	}
 }
-
-impl MediaType {
-	pub fn display_options(value: Option<Self>) -> impl Display + 'static {
-		struct Helper(Option<MediaType>);
-		impl Display for Helper {
-			fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-				let value = self.0;
-				for possibility in [MediaType::Image, MediaType::Video] {
-					let selected = if value == Some(possibility) {
-						" selected"
-					} else {
-						""
-					};
-					write!(
-						formatter,
-						"<option value=\"{possibility}\"{selected}>{possibility:#}</option>"
-					)?;
-				}
-				Ok(())
-			}
-		}
-
-		Helper(value)
-	}
-
-	pub fn from_mime(mime: &mime::Mime) -> Option<Self> {
-		match mime.type_() {
-			mime::IMAGE => Some(Self::Image),
-			mime::VIDEO => Some(Self::Video),
-			_ => None,
-		}
-	}
-}
@@ -8,15 +8,23 @@ pub use media_type::MediaType;
 pub use user_crypt::PasswordHash as UserPassword;
 pub use user_role::UserRole;
 
+pub mod collection;
 pub mod file;
+pub mod file_comment;
 pub mod file_tag;
+pub mod file_version;
 pub mod tag;
+pub mod tag_alias;
 pub mod tag_category;
 pub mod user;
 
+pub use collection::{Collection, Id as CollectionId};
 pub use file::{File, Id as FileId};
+pub use file_comment::Id as FileCommentId;
 pub use file_tag::{FileTag, Id as FileTagId};
+pub use file_version::Id as FileVersionId;
 pub use tag::{Id as TagId, Tag};
+pub use tag_alias::Id as TagAliasId;
 pub use tag_category::{Id as TagCategoryId, TagCategory};
 pub use user::{Id as UserId, User};
 
@@ -17,17 +17,3 @@ pub struct Tag {
 	pub created_time: Timestamp,
 	pub created_by: Option<UserId>,
 }
-
-impl Tag {
-	pub async fn count(
-		database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
-	) -> sqlx::Result<i64> {
-		struct Helper {
-			count: i64,
-		}
-		sqlx::query_as!(Helper, r#"SELECT count(*) as "count!" FROM tags"#)
-			.fetch_one(database)
-			.await
-			.map(|helper| helper.count)
-	}
-}
@@ -0,0 +1,3 @@
+//! Aliases are only accessed through queries that join them with their categories, so there is no model struct.
+
+pub type Id = super::Id;
@@ -16,6 +16,15 @@ pub struct TagCategory {
 	pub description: Option<String>,
 	#[ormx(custom_type, set)]
 	pub color: Color,
+	/// `None` for top-level categories.
+	#[ormx(set)]
+	pub parent: Option<Id>,
+	/// Files can have at most one tag directly in the category.
+	#[ormx(set)]
+	pub exclusive: bool,
+	/// Files aren't fully tagged until they have a tag in the category or its subcategories.
+	#[ormx(set)]
+	pub required: bool,
 	#[ormx(custom_type, default)]
 	pub created_time: Timestamp,
 	pub created_by: Option<UserId>,
@@ -22,6 +22,12 @@ pub struct User {
 	pub created_time: Timestamp,
 	#[ormx(custom_type, default, set)]
 	pub last_login: Option<Timestamp>,
+	/// The user's storage quota in bytes, or `None` for the configured default.
+	#[ormx(default, set)]
+	pub max_bytes: Option<i64>,
+	/// The maximum number of files that the user can upload, or `None` for the configured default.
+	#[ormx(default, set)]
+	pub max_files: Option<i64>,
 }
 
 impl User {
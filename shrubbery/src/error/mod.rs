@@ -39,8 +39,15 @@ pub struct Io(pub &'static str, #[source] pub std::io::Error);
 pub struct BadRequest(pub Cow<'static, str>);
 
 #[derive(Debug, thiserror::Error)]
-#[error("invalid or missing content type")]
-pub struct BadContentType;
+#[error("unrecognized or unsupported file format")]
+pub struct UnsupportedFormat;
+
+#[derive(Debug, thiserror::Error)]
+#[error("the file was declared as {declared} but it is actually {detected}")]
+pub struct ContentTypeMismatch {
+	pub declared: String,
+	pub detected: &'static str,
+}
 
 #[derive(Debug, thiserror::Error)]
 #[error("wrong field order (expected {0:?} field)")]
@@ -70,7 +77,8 @@ impl_response!(Encrypt, INTERNAL_SERVER_ERROR);
 impl_response!(Decrypt, INTERNAL_SERVER_ERROR);
 impl_response!(Multipart, BAD_REQUEST);
 impl_response!(BadRequest, BAD_REQUEST);
-impl_response!(BadContentType, BAD_REQUEST);
+impl_response!(UnsupportedFormat, BAD_REQUEST);
+impl_response!(ContentTypeMismatch, BAD_REQUEST);
 impl_response!(WrongFieldOrder, BAD_REQUEST);
 impl_response!(ExpectedField, BAD_REQUEST);
 
@@ -0,0 +1,247 @@
+//! Checking that the database and storage agree, for the `fsck` subcommand.
+//!
+//! Rows and stored content are written in separate steps, so an interrupted request or a crash can leave them out of sync.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::TryStreamExt as _;
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::helpers::sniff;
+use crate::storage::{Key, Storage, QUARANTINE};
+
+/// Temporary files older than this are assumed to have been left behind by an interrupted request rather than being in use.
+const STALE_TEMP_AGE: Duration = Duration::from_hours(24);
+/// The prefix of the names that the `tempfile` crate gives to temporary files.
+const TEMP_PREFIX: &str = ".tmp";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("querying database: {0}")]
+	Sqlx(#[from] sqlx::Error),
+	#[error("listing storage: {0}")]
+	ListStorage(#[source] io::Error),
+	#[error("listing temporary files: {0}")]
+	ListTemp(#[source] io::Error),
+}
+
+enum Problem {
+	/// A row refers to content that is not stored.
+	Missing(Key),
+	/// Content is stored without a row referring to it.
+	Orphan(Key),
+	StaleTemp(PathBuf),
+	MediaTypeMismatch {
+		file_id: models::FileId,
+		media_type: models::MediaType,
+		mime_type: Option<String>,
+		/// `None` if the content is not in a supported format.
+		detected: Option<sniff::Format>,
+	},
+	Unreadable(Key, io::Error),
+}
+
+impl Display for Problem {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Missing(key) => write!(formatter, "{key}: in the database but not in storage"),
+			Self::Orphan(key) => write!(formatter, "{key}: in storage but not in the database"),
+			Self::StaleTemp(path) => write!(formatter, "{}: stale temporary file", path.display()),
+			Self::MediaTypeMismatch {
+				file_id,
+				media_type,
+				mime_type,
+				detected,
+			} => {
+				write!(
+					formatter,
+					"{file_id}: recorded as {media_type} ({}) but content is ",
+					mime_type.as_deref().unwrap_or("unknown MIME type"),
+				)?;
+				match detected {
+					Some(format) => write!(formatter, "{} ({})", format.media_type, format.mime_type),
+					None => formatter.write_str("not in a supported format"),
+				}
+			}
+			Self::Unreadable(key, error) => write!(formatter, "{key}: could not be read: {error}"),
+		}
+	}
+}
+
+async fn read_header(storage: &dyn Storage, key: Key) -> io::Result<Vec<u8>> {
+	let chunks: Vec<_> = storage
+		.get(key, Some((0, sniff::HEADER_LEN - 1)))
+		.await?
+		.try_collect()
+		.await?;
+	Ok(chunks.concat())
+}
+
+struct FileRecord {
+	id: models::FileId,
+	media_type: models::MediaType,
+	mime_type: Option<String>,
+}
+
+/// Checks that each file has stored content in the format that the database says it has.
+async fn check_files(
+	records: Vec<FileRecord>,
+	storage: &dyn Storage,
+	stored: &HashSet<Key>,
+	problems: &mut Vec<Problem>,
+) {
+	for record in records {
+		let key = Key::File(record.id);
+		if !stored.contains(&key) {
+			problems.push(Problem::Missing(key));
+			continue;
+		}
+		let header = match read_header(storage, key).await {
+			Ok(header) => header,
+			Err(error) => {
+				problems.push(Problem::Unreadable(key, error));
+				continue;
+			}
+		};
+		let detected = sniff::sniff(&header);
+		let matches = match detected {
+			Some(format) => {
+				format.media_type == record.media_type
+					&& record
+						.mime_type
+						.as_deref()
+						.is_none_or(|mime_type| mime_type == format.mime_type)
+			}
+			None => false,
+		};
+		if !matches {
+			problems.push(Problem::MediaTypeMismatch {
+				file_id: record.id,
+				media_type: record.media_type,
+				mime_type: record.mime_type,
+				detected,
+			});
+		}
+	}
+}
+
+/// Finds temporary files in `directory` that have not been modified for [`STALE_TEMP_AGE`].
+fn stale_temp_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+	let mut stale = Vec::new();
+	for entry in std::fs::read_dir(directory)? {
+		let entry = entry?;
+		if !entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX) {
+			continue;
+		}
+		let metadata = entry.metadata()?;
+		let age = metadata.modified()?.elapsed().unwrap_or(Duration::ZERO);
+		if metadata.is_file() && age > STALE_TEMP_AGE {
+			stale.push(entry.path());
+		}
+	}
+	Ok(stale)
+}
+
+/// Moves a stale temporary file into the quarantine directory of `file_storage`.
+fn quarantine_temp(file_storage: &Path, path: &Path) -> io::Result<()> {
+	let directory = file_storage.join(QUARANTINE).join("temp");
+	std::fs::create_dir_all(&directory)?;
+	let name = path
+		.file_name()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+	std::fs::rename(path, directory.join(name))
+}
+
+async fn repair(
+	problem: &Problem,
+	config: &Config,
+	storage: &dyn Storage,
+) -> Option<io::Result<()>> {
+	Some(match problem {
+		Problem::Orphan(key) => storage.quarantine(*key).await,
+		Problem::StaleTemp(path) => quarantine_temp(&config.file_storage, path),
+		_ => return None,
+	})
+}
+
+/// Prints the problems that are found, quarantining orphaned content and stale temporary files if `repair` is set. Returns the number of problems that remain.
+///
+/// Content that is stored while the check runs can be mistaken for an orphan, so repairs should only be made while the server is stopped.
+pub async fn run(
+	config: &Config,
+	database: &Database,
+	storage: &dyn Storage,
+	repair: bool,
+) -> Result<usize, Error> {
+	// content is stored before its row is committed, so querying before listing means that files created during the check are not reported as missing
+	let files = sqlx::query_as!(
+		FileRecord,
+		r#"SELECT id, media_type as "media_type: models::MediaType", mime_type FROM files ORDER BY id"#
+	)
+	.fetch_all(database)
+	.await?;
+	let version_ids = sqlx::query_scalar!("SELECT id FROM file_versions ORDER BY id")
+		.fetch_all(database)
+		.await?;
+	let stored: HashSet<Key> = storage
+		.list()
+		.await
+		.map_err(Error::ListStorage)?
+		.into_iter()
+		.collect();
+
+	let expected: HashSet<Key> = files
+		.iter()
+		.map(|file| Key::File(file.id))
+		.chain(version_ids.into_iter().map(Key::Version))
+		.collect();
+	let mut problems = Vec::new();
+	check_files(files, storage, &stored, &mut problems).await;
+	let mut missing: Vec<_> = expected
+		.iter()
+		.filter(|key| matches!(key, Key::Version(_)) && !stored.contains(key))
+		.copied()
+		.collect();
+	missing.sort_unstable();
+	problems.extend(missing.into_iter().map(Problem::Missing));
+	let mut orphans: Vec<_> = stored.difference(&expected).copied().collect();
+	orphans.sort_unstable();
+	problems.extend(orphans.into_iter().map(Problem::Orphan));
+	problems.extend(
+		stale_temp_files(&config.file_storage)
+			.map_err(Error::ListTemp)?
+			.into_iter()
+			.map(Problem::StaleTemp),
+	);
+
+	let mut remaining = 0;
+	for problem in &problems {
+		let outcome = if repair {
+			self::repair(problem, config, storage).await
+		} else {
+			None
+		};
+		match outcome {
+			Some(Ok(())) => println!("{problem} (quarantined)"),
+			Some(Err(error)) => {
+				println!("{problem} (could not quarantine: {error})");
+				remaining += 1;
+			}
+			None => {
+				println!("{problem}");
+				remaining += 1;
+			}
+		}
+	}
+	println!(
+		"checked {} stored items and found {} problems, {remaining} remaining",
+		stored.len(),
+		problems.len(),
+	);
+	Ok(remaining)
+}
@@ -0,0 +1,313 @@
+//! Reading ZIP and TAR archives, for importing files in bulk.
+//!
+//! Entries are extracted to temporary files and never to their path within the archive, so malicious paths are harmless.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write as _};
+use std::path::Path;
+
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::helpers::sniff;
+
+/// Text files larger than this are not considered sidecars.
+const MAX_TEXT_LEN: u64 = 64 * 1024;
+
+/// An entry in a supported media format, extracted to a temporary file.
+pub struct MediaEntry {
+	pub path: String,
+	pub temp_path: TempPath,
+}
+
+/// How much may be extracted from an archive, since a small archive can expand into much larger files.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+	/// Larger entries are skipped.
+	pub file_size: Option<u64>,
+	/// The most bytes extracted from the whole archive. Reading stops at the entry that would exceed it.
+	pub total: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct Contents {
+	/// In the order they appear in the archive.
+	pub media: Vec<MediaEntry>,
+	/// The contents of text files, which may be sidecars, by their path.
+	pub texts: HashMap<String, String>,
+	/// Entries that were not extracted, along with the reason.
+	pub skipped: Vec<(String, &'static str)>,
+	/// The total size of the media entries.
+	extracted: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+	#[error("ZIP error: {0}")]
+	Zip(#[from] zip::result::ZipError),
+	#[error("not a ZIP or TAR archive")]
+	UnknownFormat,
+}
+
+/// Metadata that tools add to archives, which is not interesting to anyone.
+fn is_junk(path: &str) -> bool {
+	let file_name = path.rsplit('/').next().unwrap_or(path);
+	path.starts_with("__MACOSX/") || file_name.starts_with("._") || file_name == ".DS_Store"
+}
+
+/// Returns whether to keep reading the archive.
+fn add_entry(
+	contents: &mut Contents,
+	path: String,
+	mut reader: impl Read,
+	limits: Limits,
+	make_tempfile: &impl Fn() -> io::Result<NamedTempFile>,
+) -> io::Result<bool> {
+	if is_junk(&path) {
+		return Ok(true);
+	}
+
+	if path.to_lowercase().ends_with(".txt") {
+		let mut text = Vec::new();
+		reader.take(MAX_TEXT_LEN + 1).read_to_end(&mut text)?;
+		if text.len() as u64 > MAX_TEXT_LEN {
+			contents
+				.skipped
+				.push((path, "text file is too large to be a sidecar"));
+		} else if let Ok(text) = String::from_utf8(text) {
+			contents.texts.insert(path, text);
+		} else {
+			contents
+				.skipped
+				.push((path, "text file is not valid UTF-8"));
+		}
+		return Ok(true);
+	}
+
+	let mut header = Vec::new();
+	(&mut reader)
+		.take(sniff::HEADER_LEN)
+		.read_to_end(&mut header)?;
+	if sniff::sniff(&header).is_none() {
+		contents
+			.skipped
+			.push((path, "unrecognized or unsupported format"));
+		return Ok(true);
+	}
+
+	let total_left = limits
+		.total
+		.map(|total| total.saturating_sub(contents.extracted));
+	let limit = match (limits.file_size, total_left) {
+		(Some(file_size), Some(total_left)) => Some(file_size.min(total_left)),
+		(file_size, total_left) => file_size.or(total_left),
+	};
+	let mut temp_file = make_tempfile()?;
+	temp_file.write_all(&header)?;
+	let header_len = header.len() as u64;
+	// one byte more than the limit is enough to tell that the entry is too large
+	let copied = match limit {
+		Some(limit) => io::copy(
+			&mut reader.take((limit + 1).saturating_sub(header_len)),
+			&mut temp_file,
+		)?,
+		None => io::copy(&mut reader, &mut temp_file)?,
+	};
+	let size = header_len + copied;
+	// the temporary file is deleted when it is dropped
+	if matches!(limits.file_size, Some(file_size) if size > file_size) {
+		contents
+			.skipped
+			.push((path, "file is larger than the size limit"));
+		return Ok(true);
+	}
+	if matches!(total_left, Some(total_left) if size > total_left) {
+		contents.skipped.push((
+			path,
+			"this would exceed your storage quota, so the rest of the archive was not read",
+		));
+		return Ok(false);
+	}
+	contents.extracted += size;
+	contents.media.push(MediaEntry {
+		path,
+		temp_path: temp_file.into_temp_path(),
+	});
+	Ok(true)
+}
+
+fn read_zip(
+	file: File,
+	limits: Limits,
+	make_tempfile: &impl Fn() -> io::Result<NamedTempFile>,
+) -> Result<Contents, Error> {
+	let mut archive = zip::ZipArchive::new(file)?;
+	let mut contents = Contents::default();
+	for index in 0..archive.len() {
+		let entry = archive.by_index(index)?;
+		if entry.is_dir() {
+			continue;
+		}
+		let path = entry.name().to_owned();
+		if !add_entry(&mut contents, path, entry, limits, make_tempfile)? {
+			break;
+		}
+	}
+	Ok(contents)
+}
+
+fn read_tar(
+	file: File,
+	limits: Limits,
+	make_tempfile: &impl Fn() -> io::Result<NamedTempFile>,
+) -> Result<Contents, Error> {
+	let mut archive = tar::Archive::new(file);
+	let mut contents = Contents::default();
+	for entry in archive.entries()? {
+		let entry = entry?;
+		if !entry.header().entry_type().is_file() {
+			continue;
+		}
+		let path = entry.path()?.to_string_lossy().into_owned();
+		if !add_entry(&mut contents, path, entry, limits, make_tempfile)? {
+			break;
+		}
+	}
+	Ok(contents)
+}
+
+/// Read the archive at `path`, using `make_tempfile` to create the files that entries are extracted to, within `limits`.
+///
+/// This does blocking IO.
+pub fn read(
+	path: &Path,
+	limits: Limits,
+	make_tempfile: impl Fn() -> io::Result<NamedTempFile>,
+) -> Result<Contents, Error> {
+	let mut file = File::open(path)?;
+	let mut header = Vec::new();
+	(&mut file).take(512).read_to_end(&mut header)?;
+	file.seek(SeekFrom::Start(0))?;
+
+	if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+		read_zip(file, limits, &make_tempfile)
+	} else if header.get(257..262) == Some(b"ustar") {
+		read_tar(file, limits, &make_tempfile)
+	} else {
+		Err(Error::UnknownFormat)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Write as _;
+
+	use tempfile::NamedTempFile;
+
+	const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+	fn check(archive: &NamedTempFile) {
+		let contents =
+			super::read(archive.path(), super::Limits::default(), NamedTempFile::new).unwrap();
+		assert_eq!(
+			contents
+				.media
+				.iter()
+				.map(|entry| entry.path.as_str())
+				.collect::<Vec<_>>(),
+			["photos/cat.png", "notes.md"]
+		);
+		assert_eq!(std::fs::read(&contents.media[0].temp_path).unwrap(), PNG);
+		assert_eq!(contents.texts["photos/cat.png.txt"], "animal:cat\n");
+		assert_eq!(
+			contents.skipped,
+			[("data.bin".to_owned(), "unrecognized or unsupported format")]
+		);
+	}
+
+	#[test]
+	fn zip() {
+		let mut archive = NamedTempFile::new().unwrap();
+		let mut writer = zip::ZipWriter::new(archive.as_file_mut());
+		let options = zip::write::FileOptions::default();
+		for (name, data) in [
+			("photos/cat.png", PNG),
+			("photos/cat.png.txt", b"animal:cat\n"),
+			("__MACOSX/photos/._cat.png", b"junk"),
+			("notes.md", b"# Notes"),
+			("data.bin", b"\0\x01\x02"),
+		] {
+			writer.start_file(name, options).unwrap();
+			writer.write_all(data).unwrap();
+		}
+		writer.finish().unwrap();
+		drop(writer);
+		check(&archive);
+	}
+
+	#[test]
+	fn limits() {
+		let mut archive = NamedTempFile::new().unwrap();
+		let mut writer = zip::ZipWriter::new(archive.as_file_mut());
+		let options = zip::write::FileOptions::default();
+		let big = [PNG, &[0; 100]].concat();
+		for (name, data) in [
+			("a.png", PNG),
+			("big.png", &big[..]),
+			("b.png", PNG),
+			("c.png", PNG),
+		] {
+			writer.start_file(name, options).unwrap();
+			writer.write_all(data).unwrap();
+		}
+		writer.finish().unwrap();
+		drop(writer);
+
+		let limits = super::Limits {
+			file_size: Some(50),
+			total: Some(PNG.len() as u64 * 2),
+		};
+		let contents = super::read(archive.path(), limits, NamedTempFile::new).unwrap();
+		assert_eq!(
+			contents
+				.media
+				.iter()
+				.map(|entry| entry.path.as_str())
+				.collect::<Vec<_>>(),
+			["a.png", "b.png"]
+		);
+		assert_eq!(
+			contents.skipped,
+			[
+				("big.png".to_owned(), "file is larger than the size limit"),
+				(
+					"c.png".to_owned(),
+					"this would exceed your storage quota, so the rest of the archive was not read"
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn tar() {
+		let mut archive = NamedTempFile::new().unwrap();
+		let mut builder = tar::Builder::new(archive.as_file_mut());
+		for (name, data) in [
+			("photos/cat.png", PNG),
+			("photos/cat.png.txt", b"animal:cat\n"),
+			("notes.md", b"# Notes"),
+			("data.bin", b"\0\x01\x02"),
+		] {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(data.len() as u64);
+			header.set_mode(0o644);
+			header.set_cksum();
+			builder.append_data(&mut header, name, data).unwrap();
+		}
+		builder.finish().unwrap();
+		drop(builder);
+		check(&archive);
+	}
+}
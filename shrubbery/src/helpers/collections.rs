@@ -0,0 +1,136 @@
+//! Ordered collections of files, like the pages of a comic or the slides of a presentation.
+//!
+//! Positions only order the files of a collection, and removing a file leaves a gap, so users are shown each file's rank rather than its position.
+
+use crate::database::{models, Database};
+
+/// A collection that a file is in, along with the file's neighbors in it.
+pub struct Membership {
+	pub collection: models::CollectionId,
+	pub name: String,
+	/// Counts up from 1.
+	pub rank: i64,
+	pub count: i64,
+	pub previous: Option<models::FileId>,
+	pub next: Option<models::FileId>,
+}
+
+/// With `show_trashed` false, files in the trash are skipped over as if they were not in any collection.
+pub async fn memberships(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	file_id: models::FileId,
+	show_trashed: bool,
+) -> sqlx::Result<Vec<Membership>> {
+	sqlx::query_as!(
+		Membership,
+		r#"SELECT collections.id as collection, collections.name, ranked.rank as "rank!", ranked.count as "count!", ranked.previous, ranked.next FROM (SELECT collection, file, row_number() OVER ordered AS rank, count(*) OVER (PARTITION BY collection) AS count, lag(file) OVER ordered AS previous, lead(file) OVER ordered AS next FROM collection_files INNER JOIN files ON collection_files.file = files.id WHERE collection IN (SELECT collection FROM collection_files WHERE file = $1) AND (files.deleted_time IS NULL OR $2) WINDOW ordered AS (PARTITION BY collection ORDER BY position)) AS ranked INNER JOIN collections ON ranked.collection = collections.id WHERE ranked.file = $1 ORDER BY collections.name"#,
+		file_id,
+		show_trashed,
+	)
+	.fetch_all(database)
+	.await
+}
+
+/// Locks the collection until the transaction ends, so that concurrent changes to it don't give two files the same position.
+///
+/// Returns `false` if the collection doesn't exist.
+async fn lock(
+	collection_id: models::CollectionId,
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<bool> {
+	Ok(
+		sqlx::query!(
+			"SELECT id FROM collections WHERE id = $1 FOR UPDATE",
+			collection_id,
+		)
+		.fetch_optional(&mut *transaction)
+		.await?
+		.is_some(),
+	)
+}
+
+/// Adds the file to the end of the collection. Adding a file that is already in the collection does nothing.
+///
+/// Returns `false` if the collection doesn't exist.
+pub async fn add(
+	collection_id: models::CollectionId,
+	file_id: models::FileId,
+	database: &Database,
+) -> sqlx::Result<bool> {
+	let mut transaction = database.begin().await?;
+	if !lock(collection_id, &mut transaction).await? {
+		return Ok(false);
+	}
+	sqlx::query!(
+		"INSERT INTO collection_files (collection, file, position) SELECT $1, $2, COALESCE(MAX(position), -1) + 1 FROM collection_files WHERE collection = $1 ON CONFLICT (collection, file) DO NOTHING",
+		collection_id,
+		file_id,
+	)
+	.execute(&mut transaction)
+	.await?;
+	transaction.commit().await?;
+	Ok(true)
+}
+
+/// Moves `file_id` so that it is at `index` in `files`, or at the end if `index` is past it.
+///
+/// Returns `false` if `file_id` is not in `files`.
+fn reorder(files: &mut Vec<models::FileId>, file_id: models::FileId, index: usize) -> bool {
+	match files.iter().position(|&file| file == file_id) {
+		Some(current) => {
+			files.remove(current);
+			files.insert(index.min(files.len()), file_id);
+			true
+		}
+		None => false,
+	}
+}
+
+/// Moves the file to `index`, counting from 0, among the files of the collection. This also closes any gaps in the positions.
+///
+/// Returns `false` if the collection doesn't exist or the file isn't in it.
+pub async fn move_file(
+	collection_id: models::CollectionId,
+	file_id: models::FileId,
+	index: usize,
+	database: &Database,
+) -> sqlx::Result<bool> {
+	let mut transaction = database.begin().await?;
+	if !lock(collection_id, &mut transaction).await? {
+		return Ok(false);
+	}
+	let mut files = sqlx::query_scalar!(
+		"SELECT file FROM collection_files WHERE collection = $1 ORDER BY position",
+		collection_id,
+	)
+	.fetch_all(&mut transaction)
+	.await?;
+	if !reorder(&mut files, file_id, index) {
+		return Ok(false);
+	}
+	sqlx::query!(
+		"UPDATE collection_files SET position = array_position($2::bigint[], file) - 1 WHERE collection = $1",
+		collection_id,
+		&files,
+	)
+	.execute(&mut transaction)
+	.await?;
+	transaction.commit().await?;
+	Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+	#[test]
+	fn reorder() {
+		let mut files = vec![1, 2, 3, 4];
+		assert!(super::reorder(&mut files, 4, 0));
+		assert_eq!(files, [4, 1, 2, 3]);
+		assert!(super::reorder(&mut files, 1, 2));
+		assert_eq!(files, [4, 2, 1, 3]);
+		assert!(super::reorder(&mut files, 4, 100));
+		assert_eq!(files, [2, 1, 3, 4]);
+		assert!(!super::reorder(&mut files, 5, 0));
+		assert_eq!(files, [2, 1, 3, 4]);
+	}
+}
@@ -0,0 +1,114 @@
+//! Tag implications, like `character:pikachu` implying `series:pokemon`, so that the implied tag doesn't have to be added by hand.
+//!
+//! Implied tags are stored on files like any other tag rather than being expanded when searching, so searching, exporting and the file page see them without knowing about implications. They are added whenever a file's tags change, and removing an implication leaves behind the tags that it already added.
+
+use crate::database::{models, Database};
+use crate::helpers::tag_categories::{self, ExclusiveViolation};
+
+/// Why an implication couldn't be added.
+#[derive(Debug, thiserror::Error)]
+pub enum Rejected {
+	/// Every tag in a cycle would have to be on the same files.
+	#[error("that tag already implies this tag, directly or through other tags")]
+	Cycle,
+	/// Applying the implication would give a file two tags in an exclusive category.
+	#[error(transparent)]
+	Exclusive(#[from] ExclusiveViolation),
+}
+
+crate::error::impl_response!(Rejected, BAD_REQUEST);
+
+/// Adds the tags implied by the tags of `files`, or of every file if `files` is `None`. Chains of implications are followed, so a tag implied by an implied tag is added too.
+///
+/// Returns the number of files that gained tags.
+pub async fn apply(
+	files: Option<&[models::FileId]>,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<u64> {
+	Ok(sqlx::query!(
+		"WITH RECURSIVE implied (file, tag) AS (SELECT file, tag FROM file_tags WHERE $1::bigint[] IS NULL OR file = ANY($1) UNION SELECT implied.file, tag_implications.implied FROM implied INNER JOIN tag_implications ON implied.tag = tag_implications.tag), added AS (INSERT INTO file_tags (file, tag) SELECT file, tag FROM implied ON CONFLICT (file, tag) DO NOTHING RETURNING file) UPDATE files SET modified_time = CURRENT_TIMESTAMP WHERE id IN (SELECT file FROM added)",
+		files,
+	)
+	.execute(database)
+	.await?
+	.rows_affected())
+}
+
+/// Adds the implication and applies it to the files that have `tag`.
+///
+/// Returns the number of files that gained tags.
+pub async fn add(
+	tag: models::TagId,
+	implied: models::TagId,
+	created_by: models::UserId,
+	database: &Database,
+) -> sqlx::Result<Result<u64, Rejected>> {
+	let mut transaction = database.begin().await?;
+	// only one implication can be added at a time, since two that are each fine on their own can make a cycle together
+	sqlx::query!("LOCK TABLE tag_implications IN SHARE ROW EXCLUSIVE MODE")
+		.execute(&mut transaction)
+		.await?;
+	let cycle = sqlx::query_scalar!(
+		r#"WITH RECURSIVE reachable (tag) AS (SELECT $1::int UNION SELECT tag_implications.implied FROM reachable INNER JOIN tag_implications ON reachable.tag = tag_implications.tag) SELECT EXISTS (SELECT 1 FROM reachable WHERE tag = $2) as "cycle!""#,
+		implied,
+		tag,
+	)
+	.fetch_one(&mut transaction)
+	.await?;
+	if cycle {
+		return Ok(Err(Rejected::Cycle));
+	}
+	sqlx::query!(
+		"INSERT INTO tag_implications (tag, implied, created_by) VALUES ($1, $2, $3)",
+		tag,
+		implied,
+		created_by,
+	)
+	.execute(&mut transaction)
+	.await?;
+	let files = sqlx::query_scalar!("SELECT file FROM file_tags WHERE tag = $1", tag)
+		.fetch_all(&mut transaction)
+		.await?;
+	let changed = apply(Some(files.as_slice()), &mut transaction).await?;
+	if let Err(violation) = tag_categories::check_exclusive(&files, &mut transaction).await? {
+		return Ok(Err(violation.into()));
+	}
+	transaction.commit().await?;
+	Ok(Ok(changed))
+}
+
+/// Moves the implications of `tag` onto `target`, for when `tag` is merged into it. Ones that `target` already has, or that would make it imply itself, are left on `tag` to be deleted with it.
+///
+/// Returns `false` if the combined implications would make a cycle, in which case the transaction should be rolled back.
+pub async fn move_to(
+	tag: models::TagId,
+	target: models::TagId,
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<bool> {
+	// like in `add`, since moving implications can make a cycle too
+	sqlx::query!("LOCK TABLE tag_implications IN SHARE ROW EXCLUSIVE MODE")
+		.execute(&mut *transaction)
+		.await?;
+	sqlx::query!(
+		"UPDATE tag_implications SET tag = $2 WHERE tag = $1 AND implied <> $2 AND NOT EXISTS (SELECT 1 FROM tag_implications AS existing WHERE existing.tag = $2 AND existing.implied = tag_implications.implied)",
+		tag,
+		target,
+	)
+	.execute(&mut *transaction)
+	.await?;
+	sqlx::query!(
+		"UPDATE tag_implications SET implied = $2 WHERE implied = $1 AND tag <> $2 AND NOT EXISTS (SELECT 1 FROM tag_implications AS existing WHERE existing.implied = $2 AND existing.tag = tag_implications.tag)",
+		tag,
+		target,
+	)
+	.execute(&mut *transaction)
+	.await?;
+	// only implications involving `target` changed, so any new cycle goes through it
+	let cycle = sqlx::query_scalar!(
+		r#"WITH RECURSIVE reachable (tag) AS (SELECT implied FROM tag_implications WHERE tag = $1 UNION SELECT tag_implications.implied FROM reachable INNER JOIN tag_implications ON reachable.tag = tag_implications.tag) SELECT EXISTS (SELECT 1 FROM reachable WHERE tag = $1) as "cycle!""#,
+		target,
+	)
+	.fetch_one(&mut *transaction)
+	.await?;
+	Ok(!cycle)
+}
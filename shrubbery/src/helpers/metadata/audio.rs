@@ -0,0 +1,106 @@
+//! Reading the duration from the headers of WAV and FLAC files.
+//!
+//! Compressed formats like MP3 only reveal their duration when every frame is read, so their duration is not extracted.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use super::Error;
+
+pub(super) const FLAC_MAGIC: [u8; 4] = *b"fLaC";
+
+pub(super) fn is_wav(magic: &[u8; 12]) -> bool {
+	magic[..4] == *b"RIFF" && magic[8..] == *b"WAVE"
+}
+
+fn read_chunk_header(reader: &mut impl Read) -> Result<([u8; 4], u32), Error> {
+	let mut header = [0u8; 8];
+	reader.read_exact(&mut header)?;
+	let (id, size) = header.split_at(4);
+	Ok((
+		id.try_into().unwrap(),
+		u32::from_le_bytes(size.try_into().unwrap()),
+	))
+}
+
+/// A WAV file is a RIFF file with a `fmt ` chunk, which has the number of bytes per second, and a `data` chunk.
+pub(super) fn read_wav(reader: &mut (impl Read + Seek)) -> Result<Option<f64>, Error> {
+	const MALFORMED: Error = Error::Malformed("WAV");
+
+	reader.seek(SeekFrom::Start(12))?;
+	let mut byte_rate = None;
+	loop {
+		let (id, size) = read_chunk_header(reader)?;
+		match &id {
+			b"fmt " => {
+				let mut format = [0u8; 12];
+				if size < 12 {
+					return Err(MALFORMED);
+				}
+				reader.read_exact(&mut format)?;
+				byte_rate = Some(u32::from_le_bytes(format[8..12].try_into().unwrap()));
+				reader.seek(SeekFrom::Current(i64::from(size) - 12))?;
+			}
+			b"data" => {
+				let byte_rate = byte_rate.filter(|&rate| rate > 0).ok_or(MALFORMED)?;
+				return Ok(Some(f64::from(size) / f64::from(byte_rate)));
+			}
+			_ => {
+				reader.seek(SeekFrom::Current(i64::from(size)))?;
+			}
+		}
+		// chunks are padded to an even size
+		if size % 2 == 1 {
+			reader.seek(SeekFrom::Current(1))?;
+		}
+	}
+}
+
+/// The first metadata block of a FLAC file is always `STREAMINFO`, which has the sample rate and the total number of samples.
+pub(super) fn read_flac(reader: &mut impl Read) -> Result<Option<f64>, Error> {
+	// the magic, the block header, then the minimum and maximum block and frame sizes
+	let mut header = [0u8; 18];
+	reader.read_exact(&mut header)?;
+	if header[4] & 0x7f != 0 {
+		return Err(Error::Malformed("FLAC"));
+	}
+	let mut packed = [0u8; 8];
+	reader.read_exact(&mut packed)?;
+	// 20 bits of sample rate, 3 bits of channels, 5 bits of bits per sample and 36 bits of samples
+	let packed = u64::from_be_bytes(packed);
+	let sample_rate = packed >> 44;
+	let samples = packed & ((1 << 36) - 1);
+	// zero samples means the total is unknown
+	if sample_rate == 0 || samples == 0 {
+		return Ok(None);
+	}
+	#[allow(clippy::cast_precision_loss)] // both are far below 2^52
+	Ok(Some(samples as f64 / sample_rate as f64))
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Cursor;
+
+	#[test]
+	fn wav() {
+		let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+		// a chunk of odd size to check the padding
+		file.extend(b"LIST\x03\0\0\0abc\0");
+		file.extend(b"fmt \x10\0\0\0");
+		// PCM, 2 channels, 44100 Hz, 176400 bytes per second, 4 bytes per frame, 16 bits
+		file.extend(b"\x01\0\x02\0\x44\xac\0\0\x10\xb1\x02\0\x04\0\x10\0");
+		file.extend(b"data\x20\x62\x05\0");
+		assert!(super::is_wav(file[..12].try_into().unwrap()));
+		assert_eq!(super::read_wav(&mut Cursor::new(file)).unwrap(), Some(2.0));
+	}
+
+	#[test]
+	fn flac() {
+		let mut file = b"fLaC\x80\0\0\x22".to_vec();
+		file.extend([0x10, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+		// 44100 Hz, 2 channels, 16 bits and 88200 samples
+		let packed: u64 = (0xac44 << 44) | (1 << 41) | (15 << 36) | 0x1_5888;
+		file.extend(packed.to_be_bytes());
+		assert_eq!(super::read_flac(&mut Cursor::new(file)).unwrap(), Some(2.0));
+	}
+}
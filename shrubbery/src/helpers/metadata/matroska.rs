@@ -0,0 +1,252 @@
+//! Reading the duration and resolution from the headers of Matroska files (`.mkv`, `.webm`).
+//!
+//! Matroska is built on EBML, where every element is an ID and a size, both encoded as variable-length integers, followed by the element's data.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use super::{Error, VideoInfo};
+
+/// The ID of the EBML header element, which starts every Matroska file.
+pub(super) const MAGIC: [u8; 4] = [0x1a, 0x45, 0xdf, 0xa3];
+
+const MALFORMED: Error = Error::Malformed("Matroska");
+
+mod id {
+	pub const SEGMENT: u32 = 0x1853_8067;
+	pub const INFO: u32 = 0x1549_a966;
+	pub const TIMESTAMP_SCALE: u32 = 0x2a_d7b1;
+	pub const DURATION: u32 = 0x4489;
+	pub const TRACKS: u32 = 0x1654_ae6b;
+	pub const TRACK_ENTRY: u32 = 0xae;
+	pub const VIDEO: u32 = 0xe0;
+	pub const PIXEL_WIDTH: u32 = 0xb0;
+	pub const PIXEL_HEIGHT: u32 = 0xba;
+	pub const CLUSTER: u32 = 0x1f43_b675;
+}
+
+/// In nanoseconds
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Returns the value and whether all of its value bits were set, which for sizes means the size is unknown.
+fn read_vint(reader: &mut impl Read, keep_marker: bool) -> Result<(u64, bool), Error> {
+	let mut first = [0u8; 1];
+	reader.read_exact(&mut first)?;
+	let first = first[0];
+	let len = first.leading_zeros() + 1;
+	if len > 8 {
+		return Err(MALFORMED);
+	}
+	let marker = 0x80u8 >> (len - 1);
+	let mut value = u64::from(first & !marker);
+	let mut all_ones = value == u64::from(marker - 1);
+	for _ in 1..len {
+		let mut byte = [0u8; 1];
+		reader.read_exact(&mut byte)?;
+		value = (value << 8) | u64::from(byte[0]);
+		all_ones &= byte[0] == 0xff;
+	}
+	if keep_marker {
+		value |= u64::from(marker) << (8 * (len - 1));
+	}
+	Ok((value, all_ones))
+}
+
+struct ElementHeader {
+	id: u32,
+	/// `None` if the size is unknown, in which case the element extends to the end of its parent.
+	end: Option<u64>,
+}
+
+/// Returns `None` if there are no more elements before `parent_end`.
+fn read_header(
+	reader: &mut (impl Read + Seek),
+	parent_end: u64,
+) -> Result<Option<ElementHeader>, Error> {
+	if reader.stream_position()? >= parent_end {
+		return Ok(None);
+	}
+	let (id, _) = read_vint(reader, true)?;
+	let id = u32::try_from(id).map_err(|_| MALFORMED)?;
+	let (size, unknown) = read_vint(reader, false)?;
+	let end = if unknown {
+		None
+	} else {
+		let end = reader
+			.stream_position()?
+			.checked_add(size)
+			.ok_or(MALFORMED)?;
+		if end > parent_end {
+			return Err(MALFORMED);
+		}
+		Some(end)
+	};
+	Ok(Some(ElementHeader { id, end }))
+}
+
+fn read_data<const N: usize>(reader: &mut impl Read, size: u64) -> Result<[u8; N], Error> {
+	let size = usize::try_from(size).map_err(|_| MALFORMED)?;
+	if size > N {
+		return Err(MALFORMED);
+	}
+	// right-align the data so it can be interpreted as a big-endian number
+	let mut buf = [0u8; N];
+	reader.read_exact(&mut buf[N - size..])?;
+	Ok(buf)
+}
+
+fn read_uint(reader: &mut impl Read, size: u64) -> Result<u64, Error> {
+	read_data::<8>(reader, size).map(u64::from_be_bytes)
+}
+
+fn read_float(reader: &mut impl Read, size: u64) -> Result<f64, Error> {
+	match size {
+		4 => read_data::<4>(reader, size).map(|data| f32::from_be_bytes(data).into()),
+		8 => read_data::<8>(reader, size).map(f64::from_be_bytes),
+		_ => Err(MALFORMED),
+	}
+}
+
+/// Calls `handle` with the ID, size, and end of each child element, then skips to the end of the child.
+///
+/// Children of unknown size are not allowed, since there would be no way to skip them.
+fn for_each_child<R: Read + Seek>(
+	reader: &mut R,
+	end: u64,
+	mut handle: impl FnMut(&mut R, u32, u64, u64) -> Result<(), Error>,
+) -> Result<(), Error> {
+	while let Some(header) = read_header(reader, end)? {
+		let child_end = header.end.ok_or(MALFORMED)?;
+		let size = child_end - reader.stream_position()?;
+		handle(reader, header.id, size, child_end)?;
+		reader.seek(SeekFrom::Start(child_end))?;
+	}
+	Ok(())
+}
+
+pub(super) fn read(reader: &mut (impl Read + Seek)) -> Result<VideoInfo, Error> {
+	let file_end = reader.seek(SeekFrom::End(0))?;
+	reader.seek(SeekFrom::Start(0))?;
+
+	let mut info = VideoInfo::default();
+	let mut timestamp_scale = DEFAULT_TIMESTAMP_SCALE;
+	let mut duration = None;
+
+	let segment_end = loop {
+		let header = read_header(reader, file_end)?.ok_or(MALFORMED)?;
+		// live streams have segments of unknown size
+		let end = header.end.unwrap_or(file_end);
+		if header.id == id::SEGMENT {
+			break end;
+		}
+		reader.seek(SeekFrom::Start(end))?;
+	};
+
+	while let Some(header) = read_header(reader, segment_end)? {
+		// the metadata we need comes before the clusters, which may have unknown sizes anyway
+		if header.id == id::CLUSTER {
+			break;
+		}
+		let end = header.end.ok_or(MALFORMED)?;
+		match header.id {
+			id::INFO => for_each_child(reader, end, |reader, id, size, _| {
+				match id {
+					id::TIMESTAMP_SCALE => timestamp_scale = read_uint(reader, size)?,
+					id::DURATION => duration = Some(read_float(reader, size)?),
+					_ => {}
+				}
+				Ok(())
+			})?,
+			id::TRACKS => for_each_child(reader, end, |reader, id, _, end| {
+				if id != id::TRACK_ENTRY || info.width.is_some() {
+					return Ok(());
+				}
+				for_each_child(reader, end, |reader, id, _, end| {
+					if id != id::VIDEO {
+						return Ok(());
+					}
+					for_each_child(reader, end, |reader, id, size, _| {
+						match id {
+							id::PIXEL_WIDTH => info.width = u32::try_from(read_uint(reader, size)?).ok(),
+							id::PIXEL_HEIGHT => info.height = u32::try_from(read_uint(reader, size)?).ok(),
+							_ => {}
+						}
+						Ok(())
+					})
+				})
+			})?,
+			_ => {}
+		}
+		reader.seek(SeekFrom::Start(end))?;
+	}
+
+	#[allow(clippy::cast_precision_loss)] // seconds do not need to be that precise
+	let nanoseconds_per_tick = timestamp_scale as f64;
+	info.duration = duration.map(|ticks| ticks * nanoseconds_per_tick / 1e9);
+
+	Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Cursor;
+
+	use super::super::VideoInfo;
+
+	/// Only supports IDs and sizes that fit in a byte, or the unknown size if `data` is `None`.
+	fn element(id: &[u8], data: Option<&[u8]>) -> Vec<u8> {
+		let size = match data {
+			Some(data) => 0x80 | u8::try_from(data.len()).unwrap(),
+			None => 0xff,
+		};
+		[id, &[size], data.unwrap_or_default()].concat()
+	}
+
+	#[test]
+	fn read() {
+		let ebml_header = element(&super::MAGIC, Some(&element(&[0x42, 0x82], Some(b"webm"))));
+		let info = element(
+			&[0x15, 0x49, 0xa9, 0x66],
+			Some(
+				&[
+					element(&[0x2a, 0xd7, 0xb1], Some(&[0x0f, 0x42, 0x40])),
+					element(&[0x44, 0x89], Some(&2500.0f32.to_be_bytes())),
+				]
+				.concat(),
+			),
+		);
+		let video = element(
+			&[0xe0],
+			Some(
+				&[
+					element(&[0xb0], Some(&[0x05, 0x00])),
+					element(&[0xba], Some(&[0x02, 0xd0])),
+				]
+				.concat(),
+			),
+		);
+		let tracks = element(
+			&[0x16, 0x54, 0xae, 0x6b],
+			Some(&element(
+				&[0xae],
+				Some(&[element(&[0xd7], Some(&[1])), video].concat()),
+			)),
+		);
+		let segment = [
+			&[0x18, 0x53, 0x80, 0x67, 0xff][..],
+			&info,
+			&tracks,
+			&element(&[0x1f, 0x43, 0xb6, 0x75], None),
+		]
+		.concat();
+		let file = [ebml_header, segment].concat();
+
+		assert_eq!(
+			super::read(&mut Cursor::new(file)).unwrap(),
+			VideoInfo {
+				duration: Some(2.5),
+				width: Some(1280),
+				height: Some(720),
+			}
+		);
+	}
+}
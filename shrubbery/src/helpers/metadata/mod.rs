@@ -0,0 +1,264 @@
+//! Extraction of metadata such as dimensions, duration, and EXIF data from files.
+//!
+//! Failing to extract metadata is never fatal; it is logged and the affected fields are left empty.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use time::PrimitiveDateTime;
+
+use crate::database::models::MediaType;
+
+mod audio;
+mod matroska;
+mod mp4;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metadata {
+	pub size: Option<i64>,
+	pub width: Option<i32>,
+	pub height: Option<i32>,
+	pub duration: Option<f64>,
+	pub capture_time: Option<PrimitiveDateTime>,
+	pub camera_model: Option<String>,
+	pub orientation: Option<i16>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+	#[error("image error: {0}")]
+	Image(#[from] image::ImageError),
+	#[error("EXIF error: {0}")]
+	Exif(#[from] exif::Error),
+	#[error("unrecognized video container")]
+	UnknownContainer,
+	#[error("malformed {0} file")]
+	Malformed(&'static str),
+}
+
+/// What we can get from the header of a video container.
+#[derive(Debug, Default, PartialEq)]
+struct VideoInfo {
+	/// In seconds
+	duration: Option<f64>,
+	width: Option<u32>,
+	height: Option<u32>,
+}
+
+pub async fn extract(path: PathBuf, media_type: MediaType) -> Metadata {
+	tokio::task::spawn_blocking(move || extract_blocking(&path, media_type))
+		.await
+		.unwrap_or_else(|error| {
+			tracing::error!("metadata extraction task failed: {error}");
+			Metadata::default()
+		})
+}
+
+fn extract_blocking(path: &Path, media_type: MediaType) -> Metadata {
+	let mut metadata = Metadata::default();
+
+	match std::fs::metadata(path) {
+		Ok(fs_metadata) => metadata.size = i64::try_from(fs_metadata.len()).ok(),
+		Err(error) => tracing::warn!("could not get size of {path:?}: {error}"),
+	}
+
+	let results = match media_type {
+		MediaType::Image => [
+			image_dimensions(path, &mut metadata),
+			image_exif(path, &mut metadata),
+		],
+		MediaType::Video => [video_info(path, &mut metadata), Ok(())],
+		MediaType::Audio => [audio_info(path, &mut metadata), Ok(())],
+		MediaType::Pdf | MediaType::Text => [Ok(()), Ok(())],
+	};
+	for error in results.into_iter().filter_map(Result::err) {
+		tracing::warn!("could not extract metadata from {path:?}: {error}");
+	}
+
+	metadata
+}
+
+fn image_dimensions(path: &Path, metadata: &mut Metadata) -> Result<(), Error> {
+	let (width, height) = image::io::Reader::open(path)?
+		.with_guessed_format()?
+		.into_dimensions()?;
+	metadata.width = i32::try_from(width).ok();
+	metadata.height = i32::try_from(height).ok();
+	Ok(())
+}
+
+fn image_exif(path: &Path, metadata: &mut Metadata) -> Result<(), Error> {
+	let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(File::open(path)?)) {
+		Ok(exif) => exif,
+		// most images have no EXIF data, which is fine
+		Err(exif::Error::NotFound(..)) => return Ok(()),
+		Err(error) => return Err(error.into()),
+	};
+	let field = |tag| {
+		exif
+			.get_field(tag, exif::In::PRIMARY)
+			.map(|field| &field.value)
+	};
+
+	if let Some(exif::Value::Ascii(values)) = field(exif::Tag::DateTimeOriginal) {
+		metadata.capture_time = values
+			.first()
+			.and_then(|raw| exif::DateTime::from_ascii(raw).ok())
+			.and_then(|date_time| {
+				let date = time::Date::from_calendar_date(
+					date_time.year.into(),
+					date_time.month.try_into().ok()?,
+					date_time.day,
+				)
+				.ok()?;
+				let time = time::Time::from_hms(date_time.hour, date_time.minute, date_time.second).ok()?;
+				Some(PrimitiveDateTime::new(date, time))
+			});
+	}
+
+	if let Some(exif::Value::Ascii(values)) = field(exif::Tag::Model) {
+		metadata.camera_model = values
+			.first()
+			.map(|raw| {
+				String::from_utf8_lossy(raw)
+					.trim_matches(|ch: char| ch == '\0' || ch.is_whitespace())
+					.to_owned()
+			})
+			.filter(|model| !model.is_empty());
+	}
+
+	if let Some(value) = field(exif::Tag::Orientation) {
+		metadata.orientation = value
+			.get_uint(0)
+			.and_then(|orientation| i16::try_from(orientation).ok())
+			.filter(|orientation| (1..=8).contains(orientation));
+	}
+
+	Ok(())
+}
+
+fn video_info(path: &Path, metadata: &mut Metadata) -> Result<(), Error> {
+	let mut reader = BufReader::new(File::open(path)?);
+	let mut magic = [0u8; 8];
+	reader.read_exact(&mut magic)?;
+	reader.seek(SeekFrom::Start(0))?;
+
+	let info = if magic[..4] == matroska::MAGIC {
+		matroska::read(&mut reader)?
+	} else if mp4::is_top_level_box(magic[4..].try_into().unwrap()) {
+		mp4::read(&mut reader)?
+	} else {
+		return Err(Error::UnknownContainer);
+	};
+
+	metadata.duration = info.duration;
+	metadata.width = info.width.and_then(|width| i32::try_from(width).ok());
+	metadata.height = info.height.and_then(|height| i32::try_from(height).ok());
+	Ok(())
+}
+
+fn audio_info(path: &Path, metadata: &mut Metadata) -> Result<(), Error> {
+	let mut reader = BufReader::new(File::open(path)?);
+	let mut magic = [0u8; 12];
+	reader.read_exact(&mut magic)?;
+	reader.seek(SeekFrom::Start(0))?;
+
+	metadata.duration = if magic[..4] == audio::FLAC_MAGIC {
+		audio::read_flac(&mut reader)?
+	} else if audio::is_wav(&magic) {
+		audio::read_wav(&mut reader)?
+	} else if magic[..4] == matroska::MAGIC || mp4::is_top_level_box(magic[4..8].try_into().unwrap())
+	{
+		// audio in a video container, like `.m4a`
+		return video_info(path, metadata);
+	} else {
+		None
+	};
+	Ok(())
+}
+
+/// Formats a number of bytes with a binary prefix, like `1.5 MiB`.
+pub fn display_size(bytes: i64) -> impl Display {
+	struct Helper(i64);
+
+	impl Display for Helper {
+		fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+			const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+			if self.0 < 1024 {
+				return write!(formatter, "{} B", self.0);
+			}
+			#[allow(clippy::cast_precision_loss)] // the result is rounded anyway
+			let mut value = self.0 as f64;
+			let mut unit = "B";
+			for next_unit in UNITS {
+				if value < 1024.0 {
+					break;
+				}
+				value /= 1024.0;
+				unit = next_unit;
+			}
+			write!(formatter, "{value:.1} {unit}")
+		}
+	}
+
+	Helper(bytes)
+}
+
+/// Formats a number of seconds like `1:02:03` or `2:03`.
+pub fn display_duration(seconds: f64) -> impl Display {
+	struct Helper(f64);
+
+	impl Display for Helper {
+		fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+			// durations are positive and not absurdly long
+			#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+			let total = self.0.round() as u64;
+			let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+			if hours > 0 {
+				write!(formatter, "{hours}:{minutes:02}:{seconds:02}")
+			} else {
+				write!(formatter, "{minutes}:{seconds:02}")
+			}
+		}
+	}
+
+	Helper(seconds)
+}
+
+pub fn display_capture_time(capture_time: PrimitiveDateTime) -> impl Display {
+	struct Helper(PrimitiveDateTime);
+
+	impl Display for Helper {
+		fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+			let text = self
+				.0
+				.format(time::macros::format_description!(
+					"[year]-[month]-[day] [hour]:[minute]:[second]"
+				))
+				.map_err(|_| fmt::Error)?;
+			formatter.write_str(&text)
+		}
+	}
+
+	Helper(capture_time)
+}
+
+/// Describes an EXIF orientation value.
+pub fn orientation_name(orientation: i16) -> &'static str {
+	match orientation {
+		1 => "Normal",
+		2 => "Mirrored horizontally",
+		3 => "Rotated 180°",
+		4 => "Mirrored vertically",
+		5 => "Mirrored horizontally and rotated 90° counterclockwise",
+		6 => "Rotated 90° clockwise",
+		7 => "Mirrored horizontally and rotated 90° clockwise",
+		8 => "Rotated 90° counterclockwise",
+		_ => "Unknown",
+	}
+}
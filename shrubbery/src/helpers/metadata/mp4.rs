@@ -0,0 +1,201 @@
+//! Reading the duration and resolution from the `moov` box of ISO base media files (MP4, MOV, M4V, etc.).
+
+use std::io::{Read, Seek, SeekFrom};
+
+use super::{Error, VideoInfo};
+
+const MALFORMED: Error = Error::Malformed("MP4");
+
+/// Box types that can plausibly start a file, used to detect the container.
+pub(super) fn is_top_level_box(kind: [u8; 4]) -> bool {
+	matches!(
+		&kind,
+		b"ftyp" | b"moov" | b"mdat" | b"free" | b"skip" | b"wide"
+	)
+}
+
+struct BoxHeader {
+	kind: [u8; 4],
+	end: u64,
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, Error> {
+	let mut buf = [0u8; 4];
+	reader.read_exact(&mut buf)?;
+	Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, Error> {
+	let mut buf = [0u8; 8];
+	reader.read_exact(&mut buf)?;
+	Ok(u64::from_be_bytes(buf))
+}
+
+/// Returns `None` if there are no more boxes before `parent_end`.
+fn read_header(
+	reader: &mut (impl Read + Seek),
+	parent_end: u64,
+) -> Result<Option<BoxHeader>, Error> {
+	let start = reader.stream_position()?;
+	if start.saturating_add(8) > parent_end {
+		return Ok(None);
+	}
+	let size = read_u32(reader)?;
+	let mut kind = [0u8; 4];
+	reader.read_exact(&mut kind)?;
+	let end = match size {
+		// extends to the end of the parent
+		0 => parent_end,
+		1 => start.checked_add(read_u64(reader)?).ok_or(MALFORMED)?,
+		size => start + u64::from(size),
+	};
+	if end < reader.stream_position()? || end > parent_end {
+		return Err(MALFORMED);
+	}
+	Ok(Some(BoxHeader { kind, end }))
+}
+
+/// Reads the version from the full box header and skips the flags.
+fn read_version(reader: &mut impl Read) -> Result<u8, Error> {
+	Ok(read_u32(reader)?.to_be_bytes()[0])
+}
+
+fn read_mvhd(reader: &mut impl Read, info: &mut VideoInfo) -> Result<(), Error> {
+	let (timescale, duration) = if read_version(reader)? == 1 {
+		let _creation_and_modification_time = (read_u64(reader)?, read_u64(reader)?);
+		(read_u32(reader)?, read_u64(reader)?)
+	} else {
+		let _creation_and_modification_time = (read_u32(reader)?, read_u32(reader)?);
+		let timescale = read_u32(reader)?;
+		let duration = read_u32(reader)?;
+		// all ones means the duration is unknown
+		let duration = if duration == u32::MAX {
+			u64::MAX
+		} else {
+			duration.into()
+		};
+		(timescale, duration)
+	};
+	if timescale > 0 && duration != u64::MAX {
+		#[allow(clippy::cast_precision_loss)] // seconds do not need to be that precise
+		let seconds = duration as f64 / f64::from(timescale);
+		info.duration = Some(seconds);
+	}
+	Ok(())
+}
+
+/// Returns the width and height of the track, which are zero for tracks that are not visual.
+fn read_tkhd(reader: &mut (impl Read + Seek)) -> Result<(u32, u32), Error> {
+	// creation time, modification time, track ID, reserved, duration
+	let skip = if read_version(reader)? == 1 {
+		8 + 8 + 4 + 4 + 8
+	} else {
+		4 + 4 + 4 + 4 + 4
+	};
+	// reserved, layer, alternate group, volume, reserved, matrix
+	let skip = skip + 8 + 2 + 2 + 2 + 2 + 36;
+	reader.seek(SeekFrom::Current(skip))?;
+	// 16.16 fixed point
+	let width = read_u32(reader)? >> 16;
+	let height = read_u32(reader)? >> 16;
+	Ok((width, height))
+}
+
+pub(super) fn read(reader: &mut (impl Read + Seek)) -> Result<VideoInfo, Error> {
+	let file_end = reader.seek(SeekFrom::End(0))?;
+	reader.seek(SeekFrom::Start(0))?;
+
+	let mut info = VideoInfo::default();
+
+	let moov_end = loop {
+		let header = read_header(reader, file_end)?.ok_or(MALFORMED)?;
+		if &header.kind == b"moov" {
+			break header.end;
+		}
+		reader.seek(SeekFrom::Start(header.end))?;
+	};
+
+	while let Some(header) = read_header(reader, moov_end)? {
+		match &header.kind {
+			b"mvhd" => read_mvhd(reader, &mut info)?,
+			b"trak" if info.width.is_none() => {
+				while let Some(trak_child) = read_header(reader, header.end)? {
+					if &trak_child.kind == b"tkhd" {
+						let (width, height) = read_tkhd(reader)?;
+						if width > 0 && height > 0 {
+							info.width = Some(width);
+							info.height = Some(height);
+						}
+						break;
+					}
+					reader.seek(SeekFrom::Start(trak_child.end))?;
+				}
+			}
+			_ => {}
+		}
+		reader.seek(SeekFrom::Start(header.end))?;
+	}
+
+	Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Cursor;
+
+	use super::super::VideoInfo;
+
+	fn make_box(kind: [u8; 4], body: &[u8]) -> Vec<u8> {
+		let size = u32::try_from(8 + body.len()).unwrap();
+		[&size.to_be_bytes()[..], &kind, body].concat()
+	}
+
+	#[test]
+	fn read() {
+		let mvhd = make_box(
+			*b"mvhd",
+			&[
+				&[0u8; 4][..],            // version and flags
+				&[0; 8],                  // creation and modification time
+				&1000u32.to_be_bytes(),   // timescale
+				&12_500u32.to_be_bytes(), // duration
+				&[0; 80],                 // rate, volume, matrix, etc.
+			]
+			.concat(),
+		);
+		let audio_tkhd = make_box(*b"tkhd", &[0; 84]);
+		let video_tkhd = make_box(
+			*b"tkhd",
+			&[
+				&[0u8; 76][..],
+				&(1920u32 << 16).to_be_bytes(),
+				&(1080u32 << 16).to_be_bytes(),
+			]
+			.concat(),
+		);
+		let moov = make_box(
+			*b"moov",
+			&[
+				mvhd,
+				make_box(*b"trak", &audio_tkhd),
+				make_box(*b"trak", &[make_box(*b"edts", &[]), video_tkhd].concat()),
+			]
+			.concat(),
+		);
+		let file = [
+			make_box(*b"ftyp", b"isom\0\0\0\0"),
+			moov,
+			make_box(*b"mdat", &[1, 2, 3]),
+		]
+		.concat();
+
+		assert_eq!(
+			super::read(&mut Cursor::new(file)).unwrap(),
+			VideoInfo {
+				duration: Some(12.5),
+				width: Some(1920),
+				height: Some(1080),
+			}
+		);
+	}
+}
@@ -1,9 +1,22 @@
+pub mod archive;
 pub mod auth;
+pub mod collections;
 pub mod cookie;
+pub mod implications;
+pub mod metadata;
 pub mod or_null;
 pub mod pagination;
 pub mod percent;
+pub mod perceptual_hash;
+pub mod quota;
+pub mod serve_file;
+pub mod sniff;
+pub mod tag_categories;
+pub mod tag_text;
+pub mod trash;
+pub mod versions;
 pub mod viewspec;
+pub mod zip_stream;
 
 pub use or_null::OrNull;
 
@@ -13,6 +26,18 @@ pub fn set_none_if_empty(opt: &mut Option<String>) {
 	}
 }
 
+/// `text` with the characters that are special in `LIKE` patterns escaped.
+pub fn escape_like(text: &str) -> String {
+	let mut ret = String::with_capacity(text.len());
+	for ch in text.chars() {
+		if matches!(ch, '\\' | '%' | '_') {
+			ret.push('\\');
+		}
+		ret.push(ch);
+	}
+	ret
+}
+
 /// This is temporary while askama fixes their issues with deriving `axum::IntoResponse`
 macro_rules! impl_into_response {
 	($name:ident) => {
@@ -36,11 +36,13 @@ impl Query {
 	}
 }
 
-#[derive(askama::Template, Clone, Copy)]
+#[derive(askama::Template, Clone)]
 #[template(path = "_partials/pagination.html")]
 pub struct Template {
 	pub inner: Query,
 	pub num_pages: PageNum,
+	/// Other query parameters to keep when changing pages, already encoded, like `sort=name`.
+	pub extra: String,
 }
 
 impl Template {
@@ -48,34 +50,47 @@ impl Template {
 		Self {
 			inner: query,
 			num_pages,
+			extra: String::new(),
 		}
 	}
 
+	pub fn with_extra(mut self, extra: String) -> Self {
+		self.extra = extra;
+		self
+	}
+
 	pub fn href(&self) -> std::borrow::Cow<'static, str> {
-		match (self.inner.page, self.inner.page_size) {
+		let href = match (self.inner.page, self.inner.page_size) {
 			(Some(page), Some(page_size)) => format!("?page={page}&page_size={page_size}"),
 			(Some(page), None) => format!("?page={page}"),
 			(None, Some(page_size)) => format!("?page_size={page_size}"),
-			(None, None) => return "".into(),
+			(None, None) if self.extra.is_empty() => return "".into(),
+			(None, None) => return format!("?{}", self.extra).into(),
+		};
+		if self.extra.is_empty() {
+			href.into()
+		} else {
+			format!("{href}&{}", self.extra).into()
 		}
-		.into()
 	}
 
-	pub fn next_page(mut self) -> Option<Self> {
+	pub fn next_page(&self) -> Option<Self> {
 		let new_page = self.inner.page().checked_add(1)?;
-		self.inner.page = Some(new_page);
 		if new_page < self.num_pages {
-			Some(self)
+			let mut next = self.clone();
+			next.inner.page = Some(new_page);
+			Some(next)
 		} else {
 			None
 		}
 	}
 
-	pub fn prev_page(mut self) -> Option<Self> {
+	pub fn prev_page(&self) -> Option<Self> {
 		let new_page = self.inner.page().checked_sub(1)?;
-		self.inner.page = Some(new_page);
 		if new_page >= 0 {
-			Some(self)
+			let mut prev = self.clone();
+			prev.inner.page = Some(new_page);
+			Some(prev)
 		} else {
 			None
 		}
@@ -0,0 +1,90 @@
+//! Perceptual hashing of images, used to find visually similar files.
+//!
+//! We use a difference hash ("dHash"): the image is shrunk to 9x8 grayscale pixels and each bit records whether a pixel is brighter than its right neighbor.
+//! Resizing or recompressing an image barely changes its hash, so the Hamming distance between two hashes is a good measure of how similar the images look.
+
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::database::models::MediaType;
+
+const WIDTH: u32 = 9;
+const HEIGHT: u32 = 8;
+
+/// The hash is stored in a `BIGINT` column, so it is reinterpreted as signed. Only the bits matter.
+pub fn hash_image(image: &DynamicImage) -> i64 {
+	let small = image
+		.resize_exact(WIDTH, HEIGHT, FilterType::Triangle)
+		.into_luma8();
+	let mut hash = 0u64;
+	for y in 0..HEIGHT {
+		for x in 0..WIDTH - 1 {
+			hash <<= 1;
+			if small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0] {
+				hash |= 1;
+			}
+		}
+	}
+	i64::from_ne_bytes(hash.to_ne_bytes())
+}
+
+fn hash_file_blocking(path: &Path) -> image::ImageResult<i64> {
+	let image = image::io::Reader::open(path)?
+		.with_guessed_format()?
+		.decode()?;
+	Ok(hash_image(&image))
+}
+
+/// Returns `None` if the file is not an image or could not be decoded, which is logged but not considered an error since the hash is only used for finding similar files.
+pub async fn hash_file(path: PathBuf, media_type: MediaType) -> Option<i64> {
+	if media_type != MediaType::Image {
+		return None;
+	}
+	let result = tokio::task::spawn_blocking({
+		let path = path.clone();
+		move || hash_file_blocking(&path)
+	})
+	.await;
+	match result {
+		Ok(Ok(hash)) => Some(hash),
+		Ok(Err(error)) => {
+			tracing::warn!("could not compute perceptual hash of {path:?}: {error}");
+			None
+		}
+		Err(error) => {
+			tracing::error!("perceptual hashing task failed: {error}");
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use image::{DynamicImage, GrayImage, Luma};
+
+	use super::hash_image;
+
+	fn gradient(width: u32, height: u32, ascending: bool) -> DynamicImage {
+		DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+			#[allow(clippy::cast_possible_truncation)]
+			let value = (x * 255 / (width - 1)) as u8;
+			Luma([if ascending { value } else { 255 - value }])
+		}))
+	}
+
+	#[test]
+	fn scale_invariant() {
+		assert_eq!(
+			hash_image(&gradient(64, 64, false)),
+			hash_image(&gradient(300, 200, false))
+		);
+	}
+
+	#[test]
+	fn gradients() {
+		assert_eq!(hash_image(&gradient(64, 64, true)), 0);
+		assert_eq!(hash_image(&gradient(64, 64, false)), -1);
+	}
+}
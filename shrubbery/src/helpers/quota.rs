@@ -0,0 +1,184 @@
+//! Limits on how much each editor can upload.
+//!
+//! Files count towards the quota of the user who uploaded them until they are purged, so files in the trash still count. The previous versions of a file count towards the same quota until they are pruned, since they are stored too.
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::{FromRequest, RequestParts};
+use axum::response::{IntoResponse, Response};
+use headers::HeaderMapExt as _;
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::auth;
+use crate::helpers::metadata::display_size;
+
+/// Allowance for the parts of an upload's body that are not file content, like the other fields and the headers of each part.
+const BODY_OVERHEAD: u64 = 64 * 1024;
+
+fn to_i64(limit: u64) -> i64 {
+	i64::try_from(limit).unwrap_or(i64::MAX)
+}
+
+/// The maximum size of a single file, in bytes, if there is one.
+pub fn max_file_size(config: &Config) -> Option<i64> {
+	config.upload_limits.file_size.map(to_i64)
+}
+
+pub struct Limits {
+	pub bytes: Option<i64>,
+	pub files: Option<i64>,
+}
+
+impl Limits {
+	/// Uses the configured defaults where the user has no limits of their own.
+	pub fn new(max_bytes: Option<i64>, max_files: Option<i64>, config: &Config) -> Self {
+		Self {
+			bytes: max_bytes.or(config.upload_limits.user_bytes.map(to_i64)),
+			files: max_files.or(config.upload_limits.user_files.map(to_i64)),
+		}
+	}
+
+	pub fn for_user(user: &models::User, config: &Config) -> Self {
+		Self::new(user.max_bytes, user.max_files, config)
+	}
+}
+
+pub struct Usage {
+	pub bytes: i64,
+	pub files: i64,
+}
+
+pub async fn usage(
+	user_id: models::UserId,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Usage> {
+	sqlx::query_as!(
+		Usage,
+		r#"SELECT (COALESCE((SELECT SUM(size) FROM files WHERE uploaded_by = $1), 0) + COALESCE((SELECT SUM(file_versions.size) FROM file_versions INNER JOIN files ON file_versions.file = files.id WHERE files.uploaded_by = $1), 0))::BIGINT as "bytes!", (SELECT COUNT(*) FROM files WHERE uploaded_by = $1) as "files!""#,
+		user_id,
+	)
+	.fetch_one(database)
+	.await
+}
+
+/// Locks the user's row until the transaction ends, so that concurrent uploads by the same user cannot together exceed their quota, and gets their limits and usage.
+pub async fn lock(
+	user_id: models::UserId,
+	config: &Config,
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<(Limits, Usage)> {
+	let user = sqlx::query!(
+		"SELECT max_bytes, max_files FROM users WHERE id = $1 FOR UPDATE",
+		user_id,
+	)
+	.fetch_one(&mut *transaction)
+	.await?;
+	let usage = usage(user_id, &mut *transaction).await?;
+	Ok((Limits::new(user.max_bytes, user.max_files, config), usage))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Exceeded {
+	#[error("the file is larger than the limit of {}", display_size(*limit))]
+	FileSize { limit: i64 },
+	#[error("this would exceed your storage quota of {}", display_size(*limit))]
+	Bytes { limit: i64 },
+	#[error("this would exceed your limit of {limit} files")]
+	Files { limit: i64 },
+}
+
+crate::error::impl_response!(Exceeded, PAYLOAD_TOO_LARGE);
+
+/// Checks that adding `bytes` in `files` new files to the usage stays within the limits.
+pub fn check(limits: &Limits, usage: &Usage, bytes: i64, files: i64) -> Result<(), Exceeded> {
+	if let Some(limit) = limits.files {
+		if usage.files.saturating_add(files) > limit {
+			return Err(Exceeded::Files { limit });
+		}
+	}
+	if let Some(limit) = limits.bytes {
+		if usage.bytes.saturating_add(bytes) > limit {
+			return Err(Exceeded::Bytes { limit });
+		}
+	}
+	Ok(())
+}
+
+/// Like [`auth::Editor`], but also rejects uploads that would exceed the user's quota before their body is received, judging by its `Content-Length`.
+///
+/// Only uploads that are too large even after allowing for [`BODY_OVERHEAD`] are rejected here, so the quota must still be checked once the files have been received.
+pub struct Uploader(pub models::User);
+
+#[async_trait]
+impl FromRequest<Body> for Uploader {
+	type Rejection = Response;
+
+	async fn from_request(req: &mut RequestParts<Body>) -> Result<Self, Self::Rejection> {
+		let auth::Editor(user) = auth::Editor::from_request(req)
+			.await
+			.map_err(IntoResponse::into_response)?;
+		let config = Arc::clone(
+			req
+				.extensions()
+				.get::<Arc<Config>>()
+				.expect("Could not get config from app data"),
+		);
+		let database = Arc::clone(
+			req
+				.extensions()
+				.get::<Arc<Database>>()
+				.expect("Could not get database from app data"),
+		);
+		let content_length = req
+			.headers()
+			.typed_get::<headers::ContentLength>()
+			.map_or(0, |content_length| content_length.0);
+
+		let usage = usage(user.id, &*database)
+			.await
+			.map_err(|error| error::Sqlx(error).into_response())?;
+		check(
+			&Limits::for_user(&user, &config),
+			&usage,
+			to_i64(content_length.saturating_sub(BODY_OVERHEAD)),
+			1,
+		)
+		.map_err(IntoResponse::into_response)?;
+		Ok(Self(user))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{check, Exceeded, Limits, Usage};
+
+	#[test]
+	fn limits() {
+		let usage = Usage {
+			bytes: 900,
+			files: 9,
+		};
+		let limits = Limits {
+			bytes: Some(1000),
+			files: Some(10),
+		};
+		assert!(check(&limits, &usage, 100, 1).is_ok());
+		assert!(matches!(
+			check(&limits, &usage, 101, 1),
+			Err(Exceeded::Bytes { limit: 1000 })
+		));
+		assert!(matches!(
+			check(&limits, &usage, 0, 2),
+			Err(Exceeded::Files { limit: 10 })
+		));
+		let unlimited = Limits {
+			bytes: None,
+			files: None,
+		};
+		assert!(check(&unlimited, &usage, i64::MAX, i64::MAX).is_ok());
+	}
+}
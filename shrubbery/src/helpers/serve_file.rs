@@ -0,0 +1,502 @@
+//! Serving stored files directly, with support for conditional requests and (multiple) byte ranges.
+
+use std::io;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::{Bytes, StreamBody};
+use axum::response::{IntoResponse, Response};
+use futures::stream::BoxStream;
+use futures::{StreamExt as _, TryStreamExt as _};
+use headers::{
+	AcceptRanges, CacheControl, ContentLength, ContentRange, ETag, HeaderMapExt as _, IfMatch,
+	IfModifiedSince, IfNoneMatch, IfRange, IfUnmodifiedSince, LastModified,
+};
+use http::header::{
+	HeaderMap, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS,
+};
+use http::StatusCode;
+
+use crate::helpers::{percent, sniff};
+use crate::storage::{Key, Metadata, Storage};
+
+/// Requests for more ranges than this are answered with the whole file, since they are more likely to be abusive than useful.
+const MAX_RANGES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+	/// Displayed in the browser if possible.
+	Inline,
+	/// Downloaded.
+	Attachment,
+}
+
+pub struct Options<'a> {
+	/// The name of the file as shown to the user, used for the suggested filename.
+	pub name: &'a str,
+	/// `None` if the MIME type is not known, in which case no `Content-Type` is sent. Browsers are told not to sniff it either way.
+	pub mime_type: Option<&'a str>,
+	pub disposition: Disposition,
+}
+
+/// Text is only recognized if it is UTF-8, so browsers are told that rather than guessing the encoding.
+fn content_type(mime_type: &str) -> &str {
+	if mime_type == "text/plain" {
+		"text/plain; charset=utf-8"
+	} else {
+		mime_type
+	}
+}
+
+/// Inclusive at both ends, like in the `Range` header.
+type ByteRange = (u64, u64);
+
+#[derive(Debug, PartialEq, Eq)]
+enum Ranges {
+	Full,
+	Unsatisfiable,
+	Partial(Vec<ByteRange>),
+}
+
+/// Uses the storage's tag if it has one. Otherwise, files are only modified by being replaced, which changes the modification time, so the size and modification time are enough to use the `ETag` as a strong validator.
+fn make_etag(metadata: &Metadata) -> ETag {
+	let size = metadata.size;
+	let etag = if let Some(tag) = &metadata.tag {
+		let tag: String = tag
+			.chars()
+			.filter(|&ch| ch.is_ascii_graphic() && ch != '"')
+			.collect();
+		format!("\"{size:x}-{tag}\"")
+	} else {
+		let modified = metadata
+			.modified
+			.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+			.map_or(0, |since_epoch| since_epoch.as_nanos());
+		format!("\"{size:x}-{modified:x}\"")
+	};
+	etag.parse().expect("ETag is well-formed")
+}
+
+/// Returns the status to respond with if the request's preconditions mean that the file should not be sent.
+fn check_preconditions(
+	request: &HeaderMap,
+	etag: &ETag,
+	modified: Option<SystemTime>,
+) -> Option<StatusCode> {
+	// the order of evaluation is from RFC 9110, section 13.2.2
+	if let Some(if_match) = request.typed_get::<IfMatch>() {
+		if !if_match.precondition_passes(etag) {
+			return Some(StatusCode::PRECONDITION_FAILED);
+		}
+	} else if let (Some(if_unmodified_since), Some(modified)) =
+		(request.typed_get::<IfUnmodifiedSince>(), modified)
+	{
+		if !if_unmodified_since.precondition_passes(modified) {
+			return Some(StatusCode::PRECONDITION_FAILED);
+		}
+	}
+
+	if let Some(if_none_match) = request.typed_get::<IfNoneMatch>() {
+		if !if_none_match.precondition_passes(etag) {
+			return Some(StatusCode::NOT_MODIFIED);
+		}
+	} else if let (Some(if_modified_since), Some(modified)) =
+		(request.typed_get::<IfModifiedSince>(), modified)
+	{
+		if !if_modified_since.is_modified(modified) {
+			return Some(StatusCode::NOT_MODIFIED);
+		}
+	}
+
+	None
+}
+
+/// Resolves the ranges in a `Range` header against the size of the file.
+///
+/// Invalid headers are ignored as allowed by RFC 9110, so the whole file is sent. Overlapping and adjacent ranges are merged and sorted, as RFC 9110 recommends, so that the same bytes can't be requested over and over.
+fn resolve_ranges(range: &headers::Range, size: u64) -> Ranges {
+	if range.iter().next().is_none() {
+		return Ranges::Full;
+	}
+	// nothing in an empty file can be satisfied
+	if size == 0 {
+		return Ranges::Unsatisfiable;
+	}
+	let last = size - 1;
+
+	let mut resolved = Vec::new();
+	for bounds in range.iter() {
+		let range = match bounds {
+			(Bound::Included(start), Bound::Included(end)) if start <= end => (start, end.min(last)),
+			(Bound::Included(start), Bound::Unbounded) => (start, last),
+			// the last `suffix` bytes
+			(Bound::Unbounded, Bound::Included(suffix)) if suffix > 0 => {
+				(size.saturating_sub(suffix), last)
+			}
+			(Bound::Unbounded, Bound::Included(_)) => continue,
+			_ => return Ranges::Full,
+		};
+		if range.0 <= last {
+			resolved.push(range);
+		}
+	}
+	resolved.sort_unstable();
+	resolved.dedup_by(|next, merged| {
+		let overlaps = next.0 <= merged.1.saturating_add(1);
+		if overlaps {
+			merged.1 = merged.1.max(next.1);
+		}
+		overlaps
+	});
+	if resolved.len() > MAX_RANGES {
+		Ranges::Full
+	} else if resolved.is_empty() {
+		Ranges::Unsatisfiable
+	} else {
+		Ranges::Partial(resolved)
+	}
+}
+
+fn requested_ranges(
+	request: &HeaderMap,
+	etag: &ETag,
+	last_modified: Option<&LastModified>,
+	size: u64,
+) -> Ranges {
+	match (
+		request.typed_get::<headers::Range>(),
+		request.typed_get::<IfRange>(),
+	) {
+		(None, _) => Ranges::Full,
+		// the client's copy is outdated, so it needs the whole file
+		(Some(_), Some(if_range)) if if_range.is_modified(Some(etag), last_modified) => Ranges::Full,
+		(Some(range), _) => resolve_ranges(&range, size),
+	}
+}
+
+/// The suggested filename for downloads, with path separators and control characters removed, and the extension added if it is missing.
+pub fn download_name(name: &str, mime_type: Option<&str>) -> String {
+	let mut sanitized: String = name
+		.chars()
+		.filter(|ch| !ch.is_control())
+		.map(|ch| if matches!(ch, '/' | '\\') { '_' } else { ch })
+		.collect::<String>()
+		.trim_matches(|ch: char| ch.is_whitespace() || ch == '.')
+		.to_owned();
+	if sanitized.is_empty() {
+		sanitized.push_str("file");
+	}
+
+	let extensions = mime_type.map_or(&[][..], sniff::extensions);
+	let has_extension = match sanitized.rsplit_once('.') {
+		Some((_, existing)) => extensions
+			.iter()
+			.any(|extension| existing.eq_ignore_ascii_case(extension)),
+		None => false,
+	};
+	if let (false, Some(extension)) = (has_extension, extensions.first()) {
+		sanitized.push('.');
+		sanitized.push_str(extension);
+	}
+	sanitized
+}
+
+fn content_disposition(disposition: Disposition, name: &str) -> HeaderValue {
+	let disposition = match disposition {
+		Disposition::Inline => "inline",
+		Disposition::Attachment => "attachment",
+	};
+	// for old clients that do not support `filename*`
+	let fallback: String = name
+		.chars()
+		.map(|ch| {
+			if (ch.is_ascii_graphic() || ch == ' ') && !matches!(ch, '"' | '\\' | '%') {
+				ch
+			} else {
+				'_'
+			}
+		})
+		.collect();
+	let encoded = percent::encode(name.as_bytes());
+	HeaderValue::from_str(&format!(
+		"{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+	))
+	.expect("header value only contains visible ASCII")
+}
+
+/// Only requests the range from storage once the stream is polled, so the ranges of a multipart body are fetched one at a time.
+fn segment(
+	storage: Arc<dyn Storage>,
+	key: Key,
+	range: ByteRange,
+) -> BoxStream<'static, io::Result<Bytes>> {
+	futures::stream::once(async move { storage.get(key, Some(range)).await })
+		.try_flatten()
+		.boxed()
+}
+
+fn literal(bytes: impl Into<Bytes>) -> BoxStream<'static, io::Result<Bytes>> {
+	futures::stream::once(futures::future::ready(Ok(bytes.into()))).boxed()
+}
+
+/// Builds a `multipart/byteranges` body, returning the body, its length, and the boundary.
+fn multipart(
+	storage: &Arc<dyn Storage>,
+	key: Key,
+	ranges: &[ByteRange],
+	size: u64,
+	mime_type: Option<&str>,
+) -> (BoxStream<'static, io::Result<Bytes>>, u64, String) {
+	let boundary = format!("{:016x}", rand::random::<u64>());
+	let content_type = mime_type.map_or_else(String::new, |mime_type| {
+		format!("Content-Type: {}\r\n", content_type(mime_type))
+	});
+
+	let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+	let mut length = 0;
+	for &(start, end) in ranges {
+		let header = format!(
+			"\r\n--{boundary}\r\n{content_type}Content-Range: bytes {start}-{end}/{size}\r\n\r\n"
+		);
+		length += header.len() as u64 + (end - start + 1);
+		parts.push(literal(header));
+		parts.push(segment(Arc::clone(storage), key, (start, end)));
+	}
+	let trailer = format!("\r\n--{boundary}--\r\n");
+	length += trailer.len() as u64;
+	parts.push(literal(trailer));
+
+	(
+		futures::stream::iter(parts).flatten().boxed(),
+		length,
+		boundary,
+	)
+}
+
+/// Serve the content stored under `key` in response to a request with the headers `request`. Fails with [`io::ErrorKind::NotFound`] if there is none.
+///
+/// `HEAD` requests are handled by the router, which strips the body.
+pub async fn serve(
+	storage: &Arc<dyn Storage>,
+	key: Key,
+	request: &HeaderMap,
+	options: Options<'_>,
+) -> io::Result<Response> {
+	let metadata = storage
+		.metadata(key)
+		.await?
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{key} is not stored")))?;
+	let size = metadata.size;
+	let modified = metadata.modified;
+	let etag = make_etag(&metadata);
+	let last_modified = modified.map(LastModified::from);
+
+	let mut headers = HeaderMap::new();
+	headers.typed_insert(etag.clone());
+	if let Some(last_modified) = last_modified {
+		headers.typed_insert(last_modified);
+	}
+	// files can be replaced, so clients must revalidate, which is cheap thanks to the ETag
+	headers.typed_insert(CacheControl::new().with_private().with_no_cache());
+	headers.typed_insert(AcceptRanges::bytes());
+	// uploads are only checked against their signature, and text has none, so text that is really HTML or a script must not be run as one
+	headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+
+	if let Some(status) = check_preconditions(request, &etag, modified) {
+		return Ok((status, headers).into_response());
+	}
+
+	headers.insert(
+		CONTENT_DISPOSITION,
+		content_disposition(
+			options.disposition,
+			&download_name(options.name, options.mime_type),
+		),
+	);
+
+	let (status, body) = match requested_ranges(request, &etag, last_modified.as_ref(), size) {
+		Ranges::Full => {
+			headers.typed_insert(ContentLength(size));
+			(StatusCode::OK, storage.get(key, None).await?)
+		}
+		Ranges::Unsatisfiable => {
+			headers.typed_insert(ContentRange::unsatisfied_bytes(size));
+			return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+		}
+		Ranges::Partial(ranges) if ranges.len() == 1 => {
+			let (start, end) = ranges[0];
+			headers
+				.typed_insert(ContentRange::bytes(start..=end, size).expect("range is within the file"));
+			headers.typed_insert(ContentLength(end - start + 1));
+			(
+				StatusCode::PARTIAL_CONTENT,
+				storage.get(key, Some((start, end))).await?,
+			)
+		}
+		Ranges::Partial(ranges) => {
+			let (body, length, boundary) = multipart(storage, key, &ranges, size, options.mime_type);
+			headers.insert(
+				CONTENT_TYPE,
+				HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}"))
+					.expect("boundary is hexadecimal"),
+			);
+			headers.typed_insert(ContentLength(length));
+			(StatusCode::PARTIAL_CONTENT, body)
+		}
+	};
+
+	if !headers.contains_key(CONTENT_TYPE) {
+		if let Some(content_type) = options
+			.mime_type
+			.and_then(|mime_type| HeaderValue::from_str(content_type(mime_type)).ok())
+		{
+			headers.insert(CONTENT_TYPE, content_type);
+		}
+	}
+
+	Ok((status, headers, StreamBody::new(body)).into_response())
+}
+
+#[cfg(test)]
+mod test {
+	use std::sync::Arc;
+
+	use super::{download_name, resolve_ranges, serve, Disposition, Options, Ranges};
+	use crate::storage::{Key, Local, Storage};
+
+	#[test]
+	fn resolve() {
+		let resolve = |raw: &str, size| {
+			let mut headers = http::HeaderMap::new();
+			headers.insert(http::header::RANGE, raw.parse().unwrap());
+			let range = headers::HeaderMapExt::typed_get::<headers::Range>(&headers).unwrap();
+			resolve_ranges(&range, size)
+		};
+		assert_eq!(
+			resolve("bytes=0-499", 1000),
+			Ranges::Partial(vec![(0, 499)])
+		);
+		assert_eq!(
+			resolve("bytes=0-0, -100, 900-", 1000),
+			Ranges::Partial(vec![(0, 0), (900, 999)])
+		);
+		assert_eq!(
+			resolve("bytes=500-599, 0-9, 10-19, 550-700", 1000),
+			Ranges::Partial(vec![(0, 19), (500, 700)])
+		);
+		// repeating a range doesn't repeat the content, or count towards the limit
+		let repeated = format!("bytes={}", ["0-"; super::MAX_RANGES * 2].join(","));
+		assert_eq!(resolve(&repeated, 1000), Ranges::Partial(vec![(0, 999)]));
+		assert_eq!(
+			resolve("bytes=500-2000", 1000),
+			Ranges::Partial(vec![(500, 999)])
+		);
+		assert_eq!(
+			resolve("bytes=-2000", 1000),
+			Ranges::Partial(vec![(0, 999)])
+		);
+		assert_eq!(resolve("bytes=1000-", 1000), Ranges::Unsatisfiable);
+		assert_eq!(resolve("bytes=0-", 0), Ranges::Unsatisfiable);
+		assert_eq!(resolve("bytes=5-3", 1000), Ranges::Full);
+		assert_eq!(resolve("bytes=abc", 1000), Ranges::Full);
+	}
+
+	#[test]
+	fn name() {
+		assert_eq!(download_name("cat", Some("image/png")), "cat.png");
+		assert_eq!(download_name("cat.PNG", Some("image/png")), "cat.PNG");
+		assert_eq!(download_name("cat.jpg", Some("image/jpeg")), "cat.jpg");
+		assert_eq!(download_name("../etc/passwd", None), "_etc_passwd");
+		assert_eq!(download_name(" \n", Some("video/webm")), "file.webm");
+	}
+
+	fn storage_with_digits() -> (tempfile::TempDir, Arc<dyn Storage>) {
+		let dir = tempfile::tempdir().unwrap();
+		std::fs::write(dir.path().join("1"), b"0123456789").unwrap();
+		let storage = Local::new(dir.path().to_owned(), 0);
+		(dir, Arc::new(storage))
+	}
+
+	#[tokio::test]
+	async fn multiple_ranges() {
+		let (_dir, storage) = storage_with_digits();
+
+		let mut request = http::HeaderMap::new();
+		request.insert(http::header::RANGE, "bytes=0-1, -3".parse().unwrap());
+		let response = serve(
+			&storage,
+			Key::File(1),
+			&request,
+			Options {
+				name: "digits",
+				mime_type: Some("text/plain"),
+				disposition: Disposition::Inline,
+			},
+		)
+		.await
+		.unwrap();
+		assert_eq!(response.status(), http::StatusCode::PARTIAL_CONTENT);
+		assert_eq!(
+			response.headers()[http::header::X_CONTENT_TYPE_OPTIONS],
+			"nosniff"
+		);
+
+		let content_type = response.headers()[http::header::CONTENT_TYPE]
+			.to_str()
+			.unwrap()
+			.to_owned();
+		let boundary = content_type
+			.strip_prefix("multipart/byteranges; boundary=")
+			.unwrap();
+		let content_length: usize = response.headers()[http::header::CONTENT_LENGTH]
+			.to_str()
+			.unwrap()
+			.parse()
+			.unwrap();
+		let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+		assert_eq!(body.len(), content_length);
+		assert_eq!(
+			std::str::from_utf8(&body).unwrap(),
+			format!("\r\n--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 7-9/10\r\n\r\n789\r\n--{boundary}--\r\n"),
+		);
+
+		let mut request = http::HeaderMap::new();
+		request.insert(
+			http::header::IF_NONE_MATCH,
+			response_etag(&storage).await.parse().unwrap(),
+		);
+		let response = serve(
+			&storage,
+			Key::File(1),
+			&request,
+			Options {
+				name: "digits",
+				mime_type: None,
+				disposition: Disposition::Attachment,
+			},
+		)
+		.await
+		.unwrap();
+		assert_eq!(response.status(), http::StatusCode::NOT_MODIFIED);
+	}
+
+	async fn response_etag(storage: &Arc<dyn Storage>) -> String {
+		let response = serve(
+			storage,
+			Key::File(1),
+			&http::HeaderMap::new(),
+			Options {
+				name: "digits",
+				mime_type: None,
+				disposition: Disposition::Inline,
+			},
+		)
+		.await
+		.unwrap();
+		response.headers()[http::header::ETAG]
+			.to_str()
+			.unwrap()
+			.to_owned()
+	}
+}
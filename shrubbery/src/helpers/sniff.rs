@@ -0,0 +1,316 @@
+//! Detection of file formats from their first bytes ("magic numbers").
+//!
+//! The content type of an uploaded file is supplied by the client, which usually guesses it from the file extension, so it is only used as a sanity check against what we detect.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use axum::response::{IntoResponse, Response};
+
+use crate::database::models::MediaType;
+use crate::error;
+
+/// Enough to cover every signature in [`sniff`].
+pub const HEADER_LEN: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+	pub mime_type: &'static str,
+	pub media_type: MediaType,
+	/// File extensions for this format, without the dot. The first is preferred.
+	extensions: &'static [&'static str],
+	/// Other MIME types that clients commonly declare for this format.
+	aliases: &'static [&'static str],
+}
+
+impl Format {
+	const fn image(
+		mime_type: &'static str,
+		extensions: &'static [&'static str],
+		aliases: &'static [&'static str],
+	) -> Self {
+		Self {
+			mime_type,
+			media_type: MediaType::Image,
+			extensions,
+			aliases,
+		}
+	}
+
+	const fn video(
+		mime_type: &'static str,
+		extensions: &'static [&'static str],
+		aliases: &'static [&'static str],
+	) -> Self {
+		Self {
+			mime_type,
+			media_type: MediaType::Video,
+			extensions,
+			aliases,
+		}
+	}
+
+	const fn audio(
+		mime_type: &'static str,
+		extensions: &'static [&'static str],
+		aliases: &'static [&'static str],
+	) -> Self {
+		Self {
+			mime_type,
+			media_type: MediaType::Audio,
+			extensions,
+			aliases,
+		}
+	}
+
+	fn matches_declared(self, declared: &mime::Mime) -> bool {
+		let essence = declared.essence_str();
+		essence == self.mime_type || self.aliases.contains(&essence)
+	}
+}
+
+const PNG: Format = Format::image("image/png", &["png"], &[]);
+const JPEG: Format = Format::image(
+	"image/jpeg",
+	&["jpg", "jpeg"],
+	&["image/jpg", "image/pjpeg"],
+);
+const GIF: Format = Format::image("image/gif", &["gif"], &[]);
+const WEBP: Format = Format::image("image/webp", &["webp"], &[]);
+const BMP: Format = Format::image("image/bmp", &["bmp"], &["image/x-bmp", "image/x-ms-bmp"]);
+const TIFF: Format = Format::image("image/tiff", &["tiff", "tif"], &[]);
+const MP4: Format = Format::video(
+	"video/mp4",
+	&["mp4", "m4v"],
+	&["video/x-m4v", "video/quicktime"],
+);
+const QUICKTIME: Format = Format::video("video/quicktime", &["mov"], &["video/mp4"]);
+const WEBM: Format = Format::video("video/webm", &["webm"], &["video/x-matroska"]);
+const MATROSKA: Format = Format::video("video/x-matroska", &["mkv"], &["video/webm"]);
+const AVI: Format = Format::video("video/x-msvideo", &["avi"], &["video/avi", "video/msvideo"]);
+const OGG: Format = Format::video("video/ogg", &["ogv", "ogg"], &["application/ogg"]);
+const MP3: Format = Format::audio("audio/mpeg", &["mp3"], &["audio/mp3", "audio/mpeg3"]);
+const AAC: Format = Format::audio("audio/aac", &["aac"], &["audio/x-aac", "audio/aacp"]);
+const M4A: Format = Format::audio("audio/mp4", &["m4a", "m4b"], &["audio/x-m4a", "audio/m4a"]);
+const FLAC: Format = Format::audio("audio/flac", &["flac"], &["audio/x-flac"]);
+const WAV: Format = Format::audio(
+	"audio/wav",
+	&["wav"],
+	&["audio/x-wav", "audio/wave", "audio/vnd.wave"],
+);
+const OGG_AUDIO: Format = Format::audio(
+	"audio/ogg",
+	&["ogg", "oga", "opus"],
+	&["application/ogg", "audio/opus", "audio/vorbis"],
+);
+const PDF: Format = Format {
+	mime_type: "application/pdf",
+	media_type: MediaType::Pdf,
+	extensions: &["pdf"],
+	aliases: &["application/x-pdf"],
+};
+const TEXT: Format = Format {
+	mime_type: "text/plain",
+	media_type: MediaType::Text,
+	extensions: &["txt"],
+	aliases: &["text/markdown", "text/csv"],
+};
+
+const FORMATS: [Format; 20] = [
+	PNG, JPEG, GIF, WEBP, BMP, TIFF, MP4, QUICKTIME, WEBM, MATROSKA, AVI, OGG, MP3, AAC, M4A, FLAC,
+	WAV, OGG_AUDIO, PDF, TEXT,
+];
+
+/// The file extensions, without the dot, for a MIME type that was detected by [`sniff`]. The first is preferred.
+pub fn extensions(mime_type: &str) -> &'static [&'static str] {
+	FORMATS
+		.iter()
+		.find(|format| format.mime_type == mime_type)
+		.map_or(&[], |format| format.extensions)
+}
+
+/// Whether the header looks like the start of UTF-8 text without control characters other than whitespace.
+///
+/// Text has no signature, so this is only tried after every format that does.
+fn is_text(header: &[u8]) -> bool {
+	let text = match std::str::from_utf8(header) {
+		Ok(text) => text,
+		// the header can end in the middle of a character
+		Err(error) if error.error_len().is_none() => {
+			std::str::from_utf8(&header[..error.valid_up_to()]).unwrap_or_default()
+		}
+		Err(_) => return false,
+	};
+	!text.is_empty()
+		&& text
+			.chars()
+			.all(|ch| !ch.is_control() || matches!(ch, '\t' | '\n' | '\r' | '\x0c'))
+}
+
+/// Detect the format of a file from its first bytes. Returns `None` if the format is unknown or not supported.
+pub fn sniff(header: &[u8]) -> Option<Format> {
+	Some(match header {
+		[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => PNG,
+		[0xff, 0xd8, 0xff, ..] => JPEG,
+		[b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => GIF,
+		[b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => WEBP,
+		[b'R', b'I', b'F', b'F', _, _, _, _, b'A', b'V', b'I', b' ', ..] => AVI,
+		[b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => WAV,
+		// the reserved bytes are checked too, since text can start with `BM`
+		[b'B', b'M', _, _, _, _, 0, 0, 0, 0, ..] => BMP,
+		[b'I', b'I', b'*', 0, ..] | [b'M', b'M', 0, b'*', ..] => TIFF,
+		[_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] => match brand.get(..4)? {
+			b"qt  " => QUICKTIME,
+			b"M4A " | b"M4B " => M4A,
+			// still images in an ISO base media container, which we cannot decode
+			b"avif" | b"avis" | b"heic" | b"heix" | b"mif1" | b"msf1" => return None,
+			_ => MP4,
+		},
+		[0x1a, 0x45, 0xdf, 0xa3, rest @ ..] => {
+			// the doctype is in the EBML header, which comes first
+			if rest.windows(4).any(|window| window == b"webm") {
+				WEBM
+			} else {
+				MATROSKA
+			}
+		}
+		[b'O', b'g', b'g', b'S', rest @ ..] => {
+			// the first page has the identification header of the first stream
+			if [&b"OpusHead"[..], b"\x01vorbis", b"\x7fFLAC"]
+				.iter()
+				.any(|codec| rest.windows(codec.len()).any(|window| window == *codec))
+			{
+				OGG_AUDIO
+			} else {
+				OGG
+			}
+		}
+		[b'f', b'L', b'a', b'C', ..] => FLAC,
+		[b'I', b'D', b'3', ..] => MP3,
+		// MPEG audio frames start with 11 set bits, then the version and the layer, which is 1 for layer III and 0 for AAC
+		[0xff, second, ..] if second & 0xe6 == 0xe2 => MP3,
+		[0xff, second, ..] if second & 0xf6 == 0xf0 => AAC,
+		[b'%', b'P', b'D', b'F', b'-', ..] => PDF,
+		_ if is_text(header) => TEXT,
+		_ => return None,
+	})
+}
+
+fn sniff_file_blocking(path: &Path) -> std::io::Result<Option<Format>> {
+	let mut header = Vec::new();
+	std::fs::File::open(path)?
+		.take(HEADER_LEN)
+		.read_to_end(&mut header)?;
+	Ok(sniff(&header))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckError {
+	#[error(transparent)]
+	Io(#[from] error::Io),
+	#[error(transparent)]
+	Unsupported(#[from] error::UnsupportedFormat),
+	#[error(transparent)]
+	Mismatch(#[from] error::ContentTypeMismatch),
+}
+
+impl IntoResponse for CheckError {
+	fn into_response(self) -> Response {
+		match self {
+			Self::Io(error) => error.into_response(),
+			Self::Unsupported(error) => error.into_response(),
+			Self::Mismatch(error) => error.into_response(),
+		}
+	}
+}
+
+/// Detect the format of an uploaded file, rejecting it if it is unsupported or if the client declared a different format.
+///
+/// A missing or generic declared content type is not considered a disagreement.
+pub async fn check_upload(
+	path: PathBuf,
+	declared: Option<&mime::Mime>,
+) -> Result<Format, CheckError> {
+	let format = tokio::task::spawn_blocking(move || sniff_file_blocking(&path))
+		.await
+		.map_err(|error| error::Io("detecting file format", error.into()))?
+		.map_err(|error| error::Io("detecting file format", error))?
+		.ok_or(error::UnsupportedFormat)?;
+
+	match declared {
+		Some(declared)
+			if *declared != mime::APPLICATION_OCTET_STREAM && !format.matches_declared(declared) =>
+		{
+			Err(
+				error::ContentTypeMismatch {
+					declared: declared.essence_str().to_owned(),
+					detected: format.mime_type,
+				}
+				.into(),
+			)
+		}
+		_ => Ok(format),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn sniff() {
+		let cases: &[(&[u8], Option<Format>)] = &[
+			(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", Some(PNG)),
+			(b"\xff\xd8\xff\xe0\0\x10JFIF", Some(JPEG)),
+			(b"GIF89a\x01\0\x01\0", Some(GIF)),
+			(b"RIFF\0\0\0\0WEBPVP8 ", Some(WEBP)),
+			(b"RIFF\0\0\0\0AVI LIST", Some(AVI)),
+			(b"\0\0\0\x18ftypisom\0\0\0\0", Some(MP4)),
+			(b"\0\0\0\x14ftypqt  \0\0\0\0", Some(QUICKTIME)),
+			(b"\0\0\0\x1cftypavif\0\0\0\0", None),
+			(
+				b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01\x42\x82\x84webm",
+				Some(WEBM),
+			),
+			(
+				b"\x1a\x45\xdf\xa3\xa3\x42\x86\x81\x01\x42\x82\x88matroska",
+				Some(MATROSKA),
+			),
+			(b"%PDF-1.7", Some(PDF)),
+			(b"ID3\x04\0\0\0\0\0\0", Some(MP3)),
+			(b"\xff\xfb\x90\x64", Some(MP3)),
+			(b"\xff\xf1\x50\x80", Some(AAC)),
+			(b"fLaC\0\0\0\x22", Some(FLAC)),
+			(b"RIFF\0\0\0\0WAVEfmt ", Some(WAV)),
+			(b"\0\0\0\x20ftypM4A \0\0\0\0", Some(M4A)),
+			(
+				b"OggS\0\x02\0\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\0\0\0\0\x01\x13OpusHead",
+				Some(OGG_AUDIO),
+			),
+			(
+				b"OggS\0\x02\0\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\0\0\0\0\x01\x2a\x80theora",
+				Some(OGG),
+			),
+			(b"BMW service notes\n", Some(TEXT)),
+			("caf\u{e9}\tna\u{ef}ve\r\n".as_bytes(), Some(TEXT)),
+			// cut off in the middle of a character
+			(&"na\u{ef}ve".as_bytes()[..3], Some(TEXT)),
+			(b"text\0with a null", None),
+			(b"\xfe\xff\xfe", None),
+			(b"", None),
+		];
+		for &(header, expected) in cases {
+			assert_eq!(super::sniff(header), expected, "{header:?}");
+		}
+	}
+
+	#[test]
+	fn matches_declared() {
+		let mime = |raw: &str| raw.parse::<mime::Mime>().unwrap();
+		assert!(JPEG.matches_declared(&mime("image/jpeg")));
+		assert!(JPEG.matches_declared(&mime("image/jpg")));
+		assert!(!JPEG.matches_declared(&mime("image/png")));
+		assert!(!JPEG.matches_declared(&mime("video/mp4")));
+		assert!(PNG.matches_declared(&mime("image/png; charset=binary")));
+	}
+}
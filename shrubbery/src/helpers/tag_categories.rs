@@ -0,0 +1,61 @@
+//! Checking the tags of files against the flags of tag categories.
+//!
+//! Exclusive categories are enforced whenever tags are saved, but required categories are only reported, since files are often uploaded before they are fully tagged.
+
+use crate::database::models;
+
+/// A file with more than one tag in an exclusive category.
+#[derive(Debug, thiserror::Error)]
+#[error("files can only have one tag in the category {category:?}, but file {file} would have {}", .tags.join(", "))]
+pub struct ExclusiveViolation {
+	pub file: models::FileId,
+	pub category: String,
+	pub tags: Vec<String>,
+}
+
+crate::error::impl_response!(ExclusiveViolation, BAD_REQUEST);
+
+/// Checks that none of `files` have more than one tag directly in an exclusive category. Tags in subcategories don't count, since they are more specific rather than alternatives.
+///
+/// This is meant to be called in the transaction that changes the tags, after implied tags are added, so that the changes can be rolled back.
+pub async fn check_exclusive(
+	files: &[models::FileId],
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Result<(), ExclusiveViolation>> {
+	let violation = exclusive_violations(Some(files), Some(1), database)
+		.await?
+		.pop();
+	Ok(match violation {
+		Some(violation) => Err(violation),
+		None => Ok(()),
+	})
+}
+
+/// Like [`check_exclusive`], but finds up to `limit` violations among `files`, or among every file if `files` is `None`.
+pub async fn exclusive_violations(
+	files: Option<&[models::FileId]>,
+	limit: Option<i64>,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Vec<ExclusiveViolation>> {
+	sqlx::query_as!(
+		ExclusiveViolation,
+		r#"SELECT file_tags.file, tag_categories.name as category, array_agg(tags.name ORDER BY tags.name) as "tags!" FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id INNER JOIN tag_categories ON tags.category = tag_categories.id WHERE ($1::bigint[] IS NULL OR file_tags.file = ANY($1)) AND tag_categories.exclusive GROUP BY file_tags.file, tag_categories.name HAVING count(*) > 1 ORDER BY file_tags.file, tag_categories.name LIMIT $2"#,
+		files,
+		limit,
+	)
+	.fetch_all(database)
+	.await
+}
+
+/// The names of the required categories that the file has no tags in.
+pub async fn missing_required(
+	file_id: models::FileId,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Vec<String>> {
+	sqlx::query_scalar!(
+		"SELECT tag_categories.name FROM tag_categories WHERE tag_categories.required AND NOT EXISTS (SELECT 1 FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id INNER JOIN tag_category_tree ON tags.category = tag_category_tree.id WHERE file_tags.file = $1 AND tag_categories.name = ANY(tag_category_tree.path)) ORDER BY tag_categories.name",
+		file_id,
+	)
+	.fetch_all(database)
+	.await
+}
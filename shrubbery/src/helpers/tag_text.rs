@@ -0,0 +1,246 @@
+//! Entering tags as text, one per line, in the same syntax as tags in viewspecs: `name`, `category:name`, or with quotes like `"name: with a colon"`.
+
+use axum::response::ErrorResponse;
+use viewspec::parse::tag::Ref as TagRef;
+use viewspec::parse::Node;
+
+use crate::database::models;
+use crate::error;
+
+/// A line that isn't a single tag with a name.
+#[derive(Debug, thiserror::Error)]
+#[error("line {number} ({line:?}) is not a tag like name or category:name")]
+pub struct InvalidLine {
+	/// Starting from 1.
+	pub number: usize,
+	pub line: String,
+}
+
+crate::error::impl_response!(InvalidLine, BAD_REQUEST);
+
+/// A tag as written, which may not exist.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+	pub category: Option<String>,
+	pub name: String,
+}
+
+/// Blank lines are skipped.
+pub fn parse(text: &str) -> Result<Vec<Entry>, InvalidLine> {
+	let mut ret = Vec::new();
+	for (index, line) in text.lines().enumerate() {
+		if line.trim().is_empty() {
+			continue;
+		}
+		let ast = viewspec::lex_and_parse(line.bytes()).ok();
+		let entry = match ast.as_ref().map(viewspec::parse::Ast::root) {
+			Some(Node::Tag(tag)) => match tag.as_ref() {
+				TagRef::Name(name, _) => Some(Entry {
+					category: None,
+					name: name.to_owned(),
+				}),
+				TagRef::Both { category, name, .. } => Some(Entry {
+					category: Some(category.to_owned()),
+					name: name.to_owned(),
+				}),
+				TagRef::Uncategorized(..) | TagRef::Category(..) | TagRef::CategoryTree(..) => None,
+			},
+			_ => None,
+		};
+		ret.push(entry.ok_or_else(|| InvalidLine {
+			number: index + 1,
+			line: line.to_owned(),
+		})?);
+	}
+	Ok(ret)
+}
+
+/// The line for a tag, which [`parse`] reads back as the same tag.
+pub fn format(category: Option<&str>, name: &str) -> String {
+	let name = viewspec::lex::quote(name);
+	match category {
+		Some(category) => format!("{}:{name}", viewspec::lex::quote(category)),
+		None => name.into_owned(),
+	}
+}
+
+/// More than one tag could be meant.
+#[derive(Debug, PartialEq, Eq)]
+struct Ambiguous;
+
+/// A tag, or the tag of an alias, with the name of an entry without a category.
+struct Match {
+	tag: models::TagId,
+	uncategorized: bool,
+	alias: bool,
+}
+
+/// The tag that an entry without a category is of: the tag without a category that has its name, or else the one an alias without a category stands for, or else the only tag in any category that has the name or an alias with it.
+///
+/// `None` if no tag could be meant.
+fn choose(matching: &[Match]) -> Result<Option<models::TagId>, Ambiguous> {
+	for alias in [false, true] {
+		if let Some(exact) = matching
+			.iter()
+			.find(|m| m.uncategorized && m.alias == alias)
+		{
+			return Ok(Some(exact.tag));
+		}
+	}
+	let first = matching.first().map(|m| m.tag);
+	matching
+		.iter()
+		.all(|m| Some(m.tag) == first)
+		.then_some(first)
+		.ok_or(Ambiguous)
+}
+
+/// Finds the tag for each entry, adding it to `tags`.
+///
+/// As when searching, aliases are resolved to their tags. An entry without a category is of the tag without a category that has its name if there is one, so each line from [`format`] resolves back to its tag; otherwise it can be of a tag in any category as long as only one tag has that name. With `created_by`, tags that don't exist are created, in the entry's category if it has one; categories themselves are never created.
+pub async fn resolve(
+	entries: &[Entry],
+	created_by: Option<models::UserId>,
+	tags: &mut Vec<models::TagId>,
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), ErrorResponse> {
+	for entry in entries {
+		let label = format(entry.category.as_deref(), &entry.name);
+		let (category, tag) = if let Some(category_name) = &entry.category {
+			let category = sqlx::query_scalar!(
+				"SELECT id FROM tag_categories WHERE name = $1",
+				category_name,
+			)
+			.fetch_optional(&mut *transaction)
+			.await
+			.map_err(error::Sqlx)?
+			.ok_or_else(|| error::BadRequest(format!("unknown tag category {category_name:?}").into()))?;
+			let tag = sqlx::query_scalar!(
+				"SELECT COALESCE((SELECT id FROM tags WHERE name = $2 AND category = $1), (SELECT tag FROM tag_aliases WHERE name = $2 AND category = $1)) as id",
+				category,
+				entry.name,
+			)
+			.fetch_one(&mut *transaction)
+			.await
+			.map_err(error::Sqlx)?;
+			(Some(category), tag)
+		} else {
+			let matching = sqlx::query_as!(
+				Match,
+				r#"SELECT id as "tag!", category IS NULL as "uncategorized!", false as "alias!" FROM tags WHERE name = $1 UNION ALL SELECT tag, category IS NULL, true FROM tag_aliases WHERE name = $1"#,
+				entry.name,
+			)
+			.fetch_all(&mut *transaction)
+			.await
+			.map_err(error::Sqlx)?;
+			let tag = choose(&matching).map_err(|Ambiguous| {
+				error::BadRequest(
+					format!("more than one tag is named {label}; add its category like category:name").into(),
+				)
+			})?;
+			(None, tag)
+		};
+		let tag = match (tag, created_by) {
+			(Some(tag), _) => tag,
+			(None, Some(created_by)) => sqlx::query_scalar!(
+				"INSERT INTO tags (name, category, created_by) VALUES ($1, $2, $3) RETURNING id",
+				entry.name,
+				category,
+				created_by,
+			)
+			.fetch_one(&mut *transaction)
+			.await
+			.map_err(error::Sqlx)?,
+			(None, None) => {
+				return Err(error::BadRequest(format!("unknown tag {label}").into()).into());
+			}
+		};
+		if !tags.contains(&tag) {
+			tags.push(tag);
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::Entry;
+
+	fn entry(category: Option<&str>, name: &str) -> Entry {
+		Entry {
+			category: category.map(str::to_owned),
+			name: name.to_owned(),
+		}
+	}
+
+	#[test]
+	fn parse() {
+		assert_eq!(
+			super::parse("cat\n\n  animal:dog \n\"a: b\":\"c \\\"d\\\"\"\n").unwrap(),
+			[
+				entry(None, "cat"),
+				entry(Some("animal"), "dog"),
+				entry(Some("a: b"), "c \"d\""),
+			]
+		);
+		for (text, number) in [
+			("cat\nanimal:", 2),
+			("a & b", 1),
+			("x\n\nanimal::", 3),
+			("@property", 1),
+		] {
+			assert_eq!(super::parse(text).unwrap_err().number, number, "{text:?}");
+		}
+	}
+
+	#[test]
+	fn format_round_trips() {
+		for (category, name) in [
+			(None, "cat"),
+			(Some("animal"), "big dog"),
+			(Some("a:b"), " spaced "),
+			(None, "!@&|()\"\\\n"),
+		] {
+			let line = super::format(category, name);
+			assert_eq!(
+				super::parse(&line).unwrap(),
+				[entry(category, name)],
+				"{line:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn choose() {
+		use super::Match;
+
+		let tag = |tag, uncategorized| Match {
+			tag,
+			uncategorized,
+			alias: false,
+		};
+		let alias = |tag, uncategorized| Match {
+			tag,
+			uncategorized,
+			alias: true,
+		};
+		// `cat` and `animal:cat`: the line for `cat` is its bare name, which must not be mistaken for `animal:cat`.
+		assert_eq!(
+			super::parse(&super::format(None, "cat")).unwrap(),
+			[entry(None, "cat")]
+		);
+		assert_eq!(super::choose(&[tag(2, false), tag(1, true)]), Ok(Some(1)));
+		assert_eq!(super::choose(&[tag(2, false), alias(1, true)]), Ok(Some(1)));
+		assert_eq!(super::choose(&[alias(2, true), tag(1, true)]), Ok(Some(1)));
+		assert_eq!(super::choose(&[tag(2, false)]), Ok(Some(2)));
+		assert_eq!(
+			super::choose(&[tag(2, false), alias(2, false)]),
+			Ok(Some(2))
+		);
+		assert_eq!(
+			super::choose(&[tag(2, false), tag(3, false)]),
+			Err(super::Ambiguous)
+		);
+		assert_eq!(super::choose(&[]), Ok(None));
+	}
+}
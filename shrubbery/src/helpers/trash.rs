@@ -0,0 +1,62 @@
+//! Deleted files are moved to the trash, where they are hidden from search but keep their tags and versions, until they are purged.
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::helpers::versions;
+use crate::storage::{Key, Storage};
+
+/// Permanently deletes the files, which must be in the trash, along with their versions. Returns the IDs of the files that were purged.
+///
+/// The rows are deleted first so the database never refers to missing files. Stored content that cannot be removed afterwards is only logged.
+pub async fn purge(
+	file_ids: &[models::FileId],
+	database: &Database,
+	storage: &dyn Storage,
+) -> sqlx::Result<Vec<models::FileId>> {
+	let mut transaction = database.begin().await?;
+	// the rows are deleted by the cascade, so get the IDs first
+	let version_ids = sqlx::query_scalar!(
+		"SELECT file_versions.id FROM file_versions INNER JOIN files ON file_versions.file = files.id WHERE files.id = ANY($1) AND files.deleted_time IS NOT NULL",
+		file_ids,
+	)
+	.fetch_all(&mut transaction)
+	.await?;
+	let purged = sqlx::query_scalar!(
+		"DELETE FROM files WHERE id = ANY($1) AND deleted_time IS NOT NULL RETURNING id",
+		file_ids,
+	)
+	.fetch_all(&mut transaction)
+	.await?;
+	transaction.commit().await?;
+
+	for &file_id in &purged {
+		if let Err(error) = storage.delete(Key::File(file_id)).await {
+			tracing::warn!("could not remove purged file {file_id} from storage: {error}");
+		}
+	}
+	versions::remove_stored(storage, &version_ids).await;
+
+	Ok(purged)
+}
+
+/// Purges files that have been in the trash for longer than the configured retention. Returns the number of files that were purged.
+pub async fn purge_expired(
+	config: &Config,
+	database: &Database,
+	storage: &dyn Storage,
+) -> sqlx::Result<usize> {
+	if config.trash_retention_days == 0 {
+		return Ok(0);
+	}
+
+	let expired = sqlx::query_scalar!(
+		"SELECT id FROM files WHERE deleted_time < CURRENT_TIMESTAMP - make_interval(days => $1)",
+		i32::try_from(config.trash_retention_days).unwrap_or(i32::MAX),
+	)
+	.fetch_all(database)
+	.await?;
+	if expired.is_empty() {
+		return Ok(0);
+	}
+	Ok(purge(&expired, database, storage).await?.len())
+}
@@ -0,0 +1,42 @@
+//! Old versions of files, which are kept in storage under [`Key::Version`] when a file is replaced.
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::storage::{Key, Storage};
+
+/// Removes the stored content of versions whose rows have been deleted. Failures are logged since the rows are already gone.
+pub async fn remove_stored(storage: &dyn Storage, version_ids: &[models::FileVersionId]) {
+	for &version_id in version_ids {
+		if let Err(error) = storage.delete(Key::Version(version_id)).await {
+			tracing::warn!("could not remove version {version_id} from storage: {error}");
+		}
+	}
+}
+
+/// Removes versions according to the retention policy in `config`, either of a single file or of all files.
+///
+/// Returns the number of versions that were removed.
+pub async fn prune(
+	file_id: Option<models::FileId>,
+	config: &Config,
+	database: &Database,
+	storage: &dyn Storage,
+) -> sqlx::Result<usize> {
+	let retention = config.version_retention;
+	if retention.max_versions.is_none() && retention.max_age_days.is_none() {
+		return Ok(0);
+	}
+
+	let removed = sqlx::query_scalar!(
+		"DELETE FROM file_versions WHERE id IN (SELECT id FROM (SELECT id, replaced_time, row_number() OVER (PARTITION BY file ORDER BY number DESC) AS newness FROM file_versions WHERE $1::bigint IS NULL OR file = $1) AS ranked WHERE newness > $2 OR replaced_time < CURRENT_TIMESTAMP - make_interval(days => $3)) RETURNING id",
+		file_id,
+		retention.max_versions.map(i64::from),
+		retention
+			.max_age_days
+			.map(|days| i32::try_from(days).unwrap_or(i32::MAX)),
+	)
+	.fetch_all(database)
+	.await?;
+	remove_stored(storage, &removed).await;
+	Ok(removed.len())
+}
@@ -1,5 +1,7 @@
+use std::cell::Cell;
 use std::fmt::{self, Display, Formatter};
 
+use viewspec::parse::property::Property;
 use viewspec::parse::tag::{Ref as TagRef, Tag};
 use viewspec::parse::{Ast, Node};
 
@@ -40,11 +42,189 @@ impl<'a> Bindings<'a> {
 fn make_condition_for_tag<'a>(buf: &mut Formatter<'_>, tag: &'a Tag, bindings: &mut Bindings<'a>) {
 	match tag.as_ref() {
 		TagRef::Category(category, _span) => write!(buf, "files.id IN (SELECT DISTINCT file_tags.file FROM file_tags WHERE file_tags.tag IN (SELECT * FROM tags_by_category({})))", bindings.next(category)),
+		TagRef::CategoryTree(category, _span) => write!(buf, "files.id IN (SELECT DISTINCT file_tags.file FROM file_tags WHERE file_tags.tag IN (SELECT * FROM tags_by_category_tree({})))", bindings.next(category)),
 		TagRef::Name(name, _span) => write!(buf, "files.id IN (SELECT DISTINCT file_tags.file FROM file_tags WHERE file_tags.tag IN (SELECT * FROM tags_by_name({})))", bindings.next(name)),
+		TagRef::Uncategorized(name, _span) => write!(buf, "files.id IN (SELECT DISTINCT file_tags.file FROM file_tags WHERE file_tags.tag = tag_by_category_and_name(NULL, {}))", bindings.next(name)),
 		TagRef::Both { category, name, .. } => write!(buf, "files.id IN (SELECT DISTINCT file_tags.file FROM file_tags WHERE file_tags.tag = tag_by_category_and_name({}, {}))", bindings.next(category), bindings.next(name)),
 	}.unwrap();
 }
 
+#[derive(Debug, Clone, Copy)]
+enum PropertyType {
+	Integer,
+	Real,
+	Text,
+	Timestamp,
+	MediaType,
+	/// The name of something the file is related to, which it can be related to many of, like the collections it is in.
+	///
+	/// The column is in the tables of `from`, and `file` is the column of those tables that has the ID of the file.
+	Related {
+		from: &'static str,
+		file: &'static str,
+		description: &'static str,
+	},
+}
+
+impl PropertyType {
+	fn as_sql(self) -> &'static str {
+		match self {
+			Self::Integer => "bigint",
+			Self::Real => "double precision",
+			Self::Text | Self::Related { .. } => "varchar",
+			Self::Timestamp => "timestamp",
+			Self::MediaType => "file_media_type",
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			Self::Integer => "an integer",
+			Self::Real => "a number",
+			Self::Text => "text",
+			Self::Timestamp => "a date like 2000-01-31 or a date and time like \"2000-01-31 12:00:00\"",
+			Self::MediaType => "image, video, audio, pdf or text",
+			Self::Related { description, .. } => description,
+		}
+	}
+
+	fn is_valid(self, value: &str) -> bool {
+		match self {
+			Self::Integer => value.parse::<i64>().is_ok(),
+			Self::Real => matches!(value.parse::<f64>(), Ok(value) if value.is_finite()),
+			Self::Text | Self::Related { .. } => true,
+			Self::Timestamp => {
+				time::Date::parse(value, crate::timestamp::DATE_FORMAT).is_ok()
+					|| time::PrimitiveDateTime::parse(
+						value,
+						time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+					)
+					.is_ok()
+			}
+			Self::MediaType => value.parse::<models::MediaType>().is_ok(),
+		}
+	}
+}
+
+/// Returns the column and type of a property.
+fn lookup_property(name: &str) -> Option<(&'static str, PropertyType)> {
+	Some(match name {
+		"size" => ("files.size", PropertyType::Integer),
+		"width" => ("files.width", PropertyType::Integer),
+		"height" => ("files.height", PropertyType::Integer),
+		"duration" => ("files.duration", PropertyType::Real),
+		"captured" => ("files.capture_time", PropertyType::Timestamp),
+		"camera" => ("files.camera_model", PropertyType::Text),
+		"orientation" => ("files.orientation", PropertyType::Integer),
+		"uploaded" => ("files.uploaded_time", PropertyType::Timestamp),
+		"modified" => ("files.modified_time", PropertyType::Timestamp),
+		"uploader" => (
+			"(SELECT users.username FROM users WHERE users.id = files.uploaded_by)",
+			PropertyType::Text,
+		),
+		"type" => ("files.media_type", PropertyType::MediaType),
+		"format" => ("files.mime_type", PropertyType::Text),
+		"favorites" => (
+			"(SELECT count(*) FROM file_favorites WHERE file_favorites.file = files.id)",
+			PropertyType::Integer,
+		),
+		// unrated files have no rating, rather than a rating of zero
+		"rating" => (
+			"(SELECT avg(file_ratings.rating) FROM file_ratings WHERE file_ratings.file = files.id)",
+			PropertyType::Real,
+		),
+		"collection" => (
+			"collections.name",
+			PropertyType::Related {
+				from:
+					"collection_files INNER JOIN collections ON collection_files.collection = collections.id",
+				file: "collection_files.file",
+				description: "the name of a collection",
+			},
+		),
+		"favorited_by" => (
+			"users.username",
+			PropertyType::Related {
+				from: "file_favorites INNER JOIN users ON file_favorites.favorited_by = users.id",
+				file: "file_favorites.file",
+				description: "a username",
+			},
+		),
+		_ => return None,
+	})
+}
+
+/// Properties are checked before the query is made, so invalid properties are reported without involving the database.
+fn check_properties(viewspec: &Ast) -> Result<(), UserError> {
+	match viewspec.find_map_property(|property| match lookup_property(&property.name) {
+		None => Some(UserError::UnknownProperty(property.name.to_string())),
+		Some((_column, ty)) => {
+			let predicate = property.predicate.as_ref()?;
+			if ty.is_valid(&predicate.value) {
+				None
+			} else {
+				Some(UserError::InvalidPropertyValue {
+					property: property.name.to_string(),
+					value: predicate.value.to_string(),
+					expected: ty.description(),
+				})
+			}
+		}
+	}) {
+		Some(error) => Err(error),
+		None => Ok(()),
+	}
+}
+
+fn make_condition_for_property<'a>(
+	buf: &mut Formatter<'_>,
+	property: &'a Property,
+	bindings: &mut Bindings<'a>,
+) {
+	let (column, ty) = lookup_property(&property.name).expect("properties are checked beforehand");
+	match &property.predicate {
+		None => match ty {
+			// the file is related to anything at all
+			PropertyType::Related { from, file, .. } => {
+				write!(buf, "files.id IN (SELECT {file} FROM {from})")
+			}
+			_ => write!(buf, "{column} IS NOT NULL"),
+		},
+		// text is compared case-insensitively
+		Some(predicate) => match ty {
+			// names are compared like text
+			PropertyType::Related { from, file, .. } => write!(
+				buf,
+				"files.id IN (SELECT {file} FROM {from} WHERE lower({column}) {} lower({}))",
+				predicate.comparison.as_sql(),
+				bindings.next(&predicate.value),
+			),
+			PropertyType::Text => write!(
+				buf,
+				"lower({column}) {} lower({})",
+				predicate.comparison.as_sql(),
+				bindings.next(&predicate.value),
+			),
+			// media types are accepted capitalized, like they are displayed
+			PropertyType::MediaType => write!(
+				buf,
+				"{column} {} CAST(lower({}) AS {})",
+				predicate.comparison.as_sql(),
+				bindings.next(&predicate.value),
+				ty.as_sql(),
+			),
+			_ => write!(
+				buf,
+				"{column} {} CAST({} AS {})",
+				predicate.comparison.as_sql(),
+				bindings.next(&predicate.value),
+				ty.as_sql(),
+			),
+		},
+	}
+	.unwrap();
+}
+
 fn make_query_condition<'a>(
 	buf: &mut Formatter<'_>,
 	viewspec: &'a Ast,
@@ -83,6 +263,7 @@ fn make_query_condition<'a>(
 
 		match node {
 			Node::Tag(tag) => make_condition_for_tag(buf, tag, bindings),
+			Node::Property(property) => make_condition_for_property(buf, property, bindings),
 			Node::Not(inner) => {
 				buf.write_str("NOT (").unwrap();
 				stack.extend(
@@ -121,30 +302,169 @@ fn make_query_condition<'a>(
 	}
 }
 
-fn make_query(viewspec: &Ast, after: Option<models::FileId>, limit: i64) -> (String, Bindings<'_>) {
-	use std::cell::Cell;
+/// The order of search results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+	/// The order that the files were created in.
+	#[default]
+	Id,
+	NewestUploaded,
+	OldestUploaded,
+	RecentlyModified,
+	LeastRecentlyModified,
+	MostFavorited,
+	HighestRated,
+}
+
+impl Sort {
+	const ALL: [Self; 7] = [
+		Self::Id,
+		Self::NewestUploaded,
+		Self::OldestUploaded,
+		Self::RecentlyModified,
+		Self::LeastRecentlyModified,
+		Self::MostFavorited,
+		Self::HighestRated,
+	];
+
+	fn as_str(self) -> &'static str {
+		match self {
+			Self::Id => "id",
+			Self::NewestUploaded => "newest_uploaded",
+			Self::OldestUploaded => "oldest_uploaded",
+			Self::RecentlyModified => "recently_modified",
+			Self::LeastRecentlyModified => "least_recently_modified",
+			Self::MostFavorited => "most_favorited",
+			Self::HighestRated => "highest_rated",
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			Self::Id => "Oldest first",
+			Self::NewestUploaded => "Newest uploads first",
+			Self::OldestUploaded => "Oldest uploads first",
+			Self::RecentlyModified => "Recently modified first",
+			Self::LeastRecentlyModified => "Least recently modified first",
+			Self::MostFavorited => "Most favorited first",
+			Self::HighestRated => "Highest rated first",
+		}
+	}
+
+	/// The expression to sort the rows of `table` by, before their IDs. Unknown times sort as the oldest, and unrated files as the lowest rated.
+	fn key(self, table: &str) -> Option<String> {
+		Some(match self {
+			Self::Id => return None,
+			Self::NewestUploaded | Self::OldestUploaded => {
+				format!("COALESCE({table}.uploaded_time, '-infinity')")
+			}
+			Self::RecentlyModified | Self::LeastRecentlyModified => {
+				format!("COALESCE({table}.modified_time, '-infinity')")
+			}
+			Self::MostFavorited => format!(
+				"(SELECT count(*) FROM file_favorites WHERE file_favorites.file = {table}.id)"
+			),
+			Self::HighestRated => format!(
+				"COALESCE((SELECT avg(file_ratings.rating) FROM file_ratings WHERE file_ratings.file = {table}.id), 0)"
+			),
+		})
+	}
+
+	fn is_descending(self) -> bool {
+		matches!(
+			self,
+			Self::NewestUploaded | Self::RecentlyModified | Self::MostFavorited | Self::HighestRated
+		)
+	}
 
-	struct ConditionHelper<'a, 'b> {
-		viewspec: &'b Ast,
-		bindings: Cell<Option<&'a mut Bindings<'b>>>,
+	pub fn display_options(self) -> impl Display + 'static {
+		struct Helper(Sort);
+		impl Display for Helper {
+			fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+				for possibility in Sort::ALL {
+					let selected = if self.0 == possibility {
+						" selected"
+					} else {
+						""
+					};
+					write!(
+						formatter,
+						"<option value=\"{}\"{selected}>{}</option>",
+						possibility.as_str(),
+						possibility.description(),
+					)?;
+				}
+				Ok(())
+			}
+		}
+
+		Helper(self)
 	}
+}
 
-	impl Display for ConditionHelper<'_, '_> {
-		fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-			make_query_condition(formatter, self.viewspec, self.bindings.take().unwrap());
-			Ok(())
+impl Display for Sort {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		formatter.write_str(self.as_str())
+	}
+}
+
+/// Writes the condition of a viewspec when displayed, which can only be done once.
+struct ConditionHelper<'a, 'b> {
+	viewspec: &'b Ast,
+	bindings: Cell<Option<&'a mut Bindings<'b>>>,
+}
+
+impl<'a, 'b> ConditionHelper<'a, 'b> {
+	fn new(viewspec: &'b Ast, bindings: &'a mut Bindings<'b>) -> Self {
+		Self {
+			viewspec,
+			bindings: Cell::new(Some(bindings)),
 		}
 	}
+}
 
+impl Display for ConditionHelper<'_, '_> {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		make_query_condition(formatter, self.viewspec, self.bindings.take().unwrap());
+		Ok(())
+	}
+}
+
+/// Results come after the file with the ID `after` in the order of `sort`, which is how pages after the first are found.
+fn make_query(
+	viewspec: &Ast,
+	after: Option<models::FileId>,
+	limit: i64,
+	sort: Sort,
+) -> (String, Bindings<'_>) {
+	let (direction, operator) = if sort.is_descending() {
+		("DESC", "<")
+	} else {
+		("ASC", ">")
+	};
+	let (after, order) = match sort.key("files") {
+		None => (
+			format!("files.id {operator} {}", after.unwrap_or(-1)),
+			format!("files.id {direction}"),
+		),
+		Some(key) => (
+			after.map_or_else(
+				|| "TRUE".to_owned(),
+				|after| {
+					format!(
+						"({key}, files.id) {operator} ((SELECT {} FROM files AS previous WHERE previous.id = {after}), {after})",
+						sort.key("previous").unwrap(),
+					)
+				},
+			),
+			format!("{key} {direction}, files.id {direction}"),
+		),
+	};
 	let mut bindings = Bindings::new();
 	let query = format!(
-		"SELECT files.id, files.name FROM files WHERE {} AND files.id > {} ORDER BY files.id LIMIT {}",
-		ConditionHelper {
-			viewspec,
-			bindings: Cell::new(Some(&mut bindings)),
-		},
-		after.unwrap_or(-1),
-		limit,
+		"SELECT files.id, files.name FROM files WHERE files.deleted_time IS NULL AND {} AND {after} ORDER BY {order} LIMIT {limit}",
+		ConditionHelper::new(viewspec, &mut bindings),
 	);
 
 	(query, bindings)
@@ -160,10 +480,20 @@ pub struct ResultItem {
 pub enum UserError {
 	#[error("unknown tag category {0:?}")]
 	UnknownTagCategory(String),
-	#[error("no tags exist with the name {0:?}")]
+	#[error("no tags or aliases exist with the name {0:?}")]
 	NoTagsByName(String),
 	#[error("unknown tag {category:?}:{name:?}")]
 	UnknownTag { category: String, name: String },
+	#[error("unknown tag {0:?} without a category")]
+	UnknownUncategorizedTag(String),
+	#[error("unknown property {0:?}")]
+	UnknownProperty(String),
+	#[error("invalid value {value:?} for property {property:?}; expected {expected}")]
+	InvalidPropertyValue {
+		property: String,
+		value: String,
+		expected: &'static str,
+	},
 }
 
 #[derive(Debug)]
@@ -177,15 +507,43 @@ pub async fn evaluate(
 	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
 	after: Option<models::FileId>,
 	page_size: i64,
+	sort: Sort,
 ) -> Result<Vec<ResultItem>, Error> {
 	tracing::debug!("evaluating viewspec {viewspec:?}");
 
-	let (query, bindings) = make_query(viewspec, after, page_size);
+	check_properties(viewspec).map_err(Error::User)?;
+	let (query, bindings) = make_query(viewspec, after, page_size, sort);
 	let mut query = sqlx::query_as(&query);
 	for binding in bindings.as_values() {
 		query = query.bind(binding);
 	}
-	query.fetch_all(database).await.map_err(|err| match err {
+	query.fetch_all(database).await.map_err(map_error)
+}
+
+/// Finds the IDs of every matching file, in order, without pagination.
+///
+/// This is for changing the matching files in a transaction, where the matches must not change between pages.
+pub async fn evaluate_all(
+	viewspec: &Ast,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> Result<Vec<models::FileId>, Error> {
+	tracing::debug!("evaluating viewspec {viewspec:?} without pagination");
+
+	check_properties(viewspec).map_err(Error::User)?;
+	let mut bindings = Bindings::new();
+	let query = format!(
+		"SELECT files.id FROM files WHERE files.deleted_time IS NULL AND {} ORDER BY files.id",
+		ConditionHelper::new(viewspec, &mut bindings),
+	);
+	let mut query = sqlx::query_scalar(&query);
+	for binding in bindings.as_values() {
+		query = query.bind(binding);
+	}
+	query.fetch_all(database).await.map_err(map_error)
+}
+
+fn map_error(err: sqlx::Error) -> Error {
+	match err {
 		sqlx::Error::Database(ref db_error) => {
 			let db_error = db_error.downcast_ref::<sqlx::postgres::PgDatabaseError>();
 			// abusing exception fields to pass the name inclusive-or category back
@@ -198,11 +556,14 @@ pub async fn evaluate(
 					category: detail.unwrap(),
 					name: hint.unwrap().to_owned(),
 				}),
+				"unknown uncategorized tag" => {
+					Error::User(UserError::UnknownUncategorizedTag(detail.unwrap()))
+				}
 				_ => Error::Sqlx(err),
 			}
 		}
 		other => Error::Sqlx(other),
-	})
+	}
 }
 
 #[cfg(test)]
@@ -247,6 +608,9 @@ mod test {
 						self.formatter.write_str(")").unwrap();
 					}
 					Node::Tag(tag) => super::make_condition_for_tag(self.formatter, tag, self.bindings),
+					Node::Property(property) => {
+						super::make_condition_for_property(self.formatter, property, self.bindings);
+					}
 				}
 			}
 		}
@@ -267,7 +631,12 @@ mod test {
 			"a & (b | c)",
 			"!a:b & (c:d | e:f)",
 			"a & b: & c: & d:e",
+			"a:: & !b: & (c:: | d:e)",
 			r#""de":"fg" & "bac":"def" & ("a\x20c":de | !f)"#,
+			"@width:>=100 & (@camera:canon | !@duration) & a",
+			"(@type:audio | @type:PDF) & !@format:audio/mpeg",
+			"@collection:\"Comic Pages\" | !@collection",
+			"@favorited_by:alice & @rating:>=4 & !@favorites:0",
 		];
 
 		for case in cases {
@@ -105,14 +105,21 @@ impl Error {
 		Ok(())
 	}
 
-	fn to_diagnostic(&self) -> Diagnostic {
+	fn expected_got(expected: &str, got: Option<&(Span, TokenType)>) -> Diagnostic {
 		use lex::Error as LE;
-		use parse::Error as PE;
-		use UserError as UE;
-		match self {
-			Self::Parse(parse_error) => match parse_error {
-				PE::CategoryTooLong(span) => Diagnostic::new_spanned("category is too long", *span),
-				PE::ExpectedTagGot(Some((_span, TokenType::Error(error)))) => match &**error {
+
+		let got_name = match got.map(|(_span, ty)| ty) {
+			None => "EOF",
+			Some(TokenType::And) => "and operator",
+			Some(TokenType::At) => "at sign",
+			Some(TokenType::Or) => "or operator",
+			Some(TokenType::Not) => "not operator",
+			Some(TokenType::OpenParen) => "opening parenthesis",
+			Some(TokenType::CloseParen) => "closing parenthesis",
+			Some(TokenType::Colon) => "colon",
+			Some(TokenType::String) => "string",
+			Some(TokenType::Error(error)) => {
+				return match &**error {
 					LE::StringEnd => {
 						Diagnostic::new_after_end("unexpected end of input", "more input needed here")
 					}
@@ -122,30 +129,99 @@ impl Error {
 						locus_message: Some(reason.to_string().into()),
 					},
 					LE::StringNotUtf8(..) => unreachable!(),
-				},
+				}
+			}
+		};
+		let locus = got
+			.map(|(span, _ty)| *span)
+			.map_or(Locus::AfterEnd, Locus::Span);
+		Diagnostic {
+			message: format!("expected {expected}, got {got_name}").into(),
+			locus,
+			locus_message: Some(format!("expected {expected} here").into()),
+		}
+	}
+
+	fn user_error_span(parsed: &Ast, user_error: &UserError) -> Span {
+		use UserError as UE;
+		let find_property = |name: &str| {
+			parsed
+				.find_map_property(|property| (&*property.name == name).then_some(property))
+				.unwrap()
+		};
+		match user_error {
+			UE::UnknownProperty(name) => find_property(name).name_span,
+			UE::InvalidPropertyValue {
+				property, value, ..
+			} => parsed
+				.find_map_property(|this_property| {
+					let predicate = this_property.predicate.as_ref()?;
+					(&*this_property.name == property && &*predicate.value == value)
+						.then_some(predicate.value_span)
+				})
+				.unwrap(),
+			UE::NoTagsByName(name) => parsed
+				.find_map_tag(|tag| match tag.as_ref() {
+					TagRef::Name(this_name, span) if name == this_name => Some(span),
+					_ => None,
+				})
+				.unwrap(),
+			UE::UnknownTag { category, name } => parsed
+				.find_map_tag(|tag| match tag.as_ref() {
+					TagRef::Both {
+						category: this_category,
+						category_span,
+						name: this_name,
+						name_span,
+					} if category == this_category && name == this_name => Some(Span {
+						start: std::cmp::min(category_span.start, name_span.start),
+						end: std::cmp::max(category_span.end, name_span.end),
+					}),
+					_ => None,
+				})
+				.unwrap(),
+			UE::UnknownUncategorizedTag(name) => parsed
+				.find_map_tag(|tag| match tag.as_ref() {
+					TagRef::Uncategorized(this_name, span) if name == this_name => Some(span),
+					_ => None,
+				})
+				.unwrap(),
+			UE::UnknownTagCategory(category) => parsed
+				.find_map_tag(|tag| match tag.as_ref() {
+					TagRef::Category(this_category, category_span)
+					| TagRef::CategoryTree(this_category, category_span)
+					| TagRef::Both {
+						category: this_category,
+						category_span,
+						..
+					} if category == this_category => Some(category_span),
+					_ => None,
+				})
+				.unwrap(),
+		}
+	}
+
+	fn to_diagnostic(&self) -> Diagnostic {
+		use parse::Error as PE;
+		use UserError as UE;
+		match self {
+			Self::Parse(parse_error) => match parse_error {
+				PE::CategoryTooLong(span) => Diagnostic::new_spanned("category is too long", *span),
 				PE::ExpectedTagGot(got) => {
-					let got_name = match got.as_ref().map(|(_span, ty)| ty) {
-						None => "EOF",
-						Some(TokenType::And) => "and operator",
-						Some(TokenType::Or) => "or operator",
-						Some(TokenType::Not) => "not operator",
-						Some(TokenType::CloseParen) => "closing parenthesis",
-						Some(TokenType::Colon) => "colon",
-						Some(TokenType::Error(_)) => unreachable!("already checked"),
-						Some(TokenType::String | TokenType::OpenParen) => {
-							// these would have been consumed by expression2 as `tag` or the start of `OPEN_PAREN expression0 CLOSE_PAREN` respectively
-							unreachable!("parser doesn't work like that")
-						}
-					};
-					let locus = got
-						.as_ref()
-						.map(|(span, _ty)| *span)
-						.map_or(Locus::AfterEnd, Locus::Span);
-					Diagnostic {
-						message: format!("expected tag, got {got_name}").into(),
-						locus,
-						locus_message: Some("expected tag here".into()),
+					if let Some((_, TokenType::String | TokenType::OpenParen | TokenType::At)) = got {
+						// these would have been consumed by expression2 as `tag`, the start of `OPEN_PAREN expression0 CLOSE_PAREN`, or `property` respectively
+						unreachable!("parser doesn't work like that");
 					}
+					Self::expected_got("tag", got.as_ref())
+				}
+				PE::ExpectedTagNameGot { colon_span: _, got } => {
+					Self::expected_got("tag name", got.as_ref())
+				}
+				PE::ExpectedPropertyNameGot { at_span: _, got } => {
+					Self::expected_got("property name", got.as_ref())
+				}
+				PE::ExpectedPropertyValueGot { colon_span: _, got } => {
+					Self::expected_got("property value", got.as_ref())
 				}
 				PE::UnclosedParenthesis { open_location } => Diagnostic {
 					message: "unclosed parenthesis".into(),
@@ -157,48 +233,25 @@ impl Error {
 				parsed,
 				error: user_error,
 			} => {
-				let span = match user_error {
-					UE::NoTagsByName(name) => parsed
-						.find_map_tag(|tag| match tag.as_ref() {
-							TagRef::Name(this_name, span) if name == this_name => Some(span),
-							_ => None,
-						})
-						.unwrap(),
-					UE::UnknownTag { category, name } => parsed
-						.find_map_tag(|tag| match tag.as_ref() {
-							TagRef::Both {
-								category: this_category,
-								category_span,
-								name: this_name,
-								name_span,
-							} if category == this_category && name == this_name => Some(Span {
-								start: std::cmp::min(category_span.start, name_span.start),
-								end: std::cmp::max(category_span.end, name_span.end),
-							}),
-							_ => None,
-						})
-						.unwrap(),
-					UE::UnknownTagCategory(category) => parsed
-						.find_map_tag(|tag| match tag.as_ref() {
-							TagRef::Category(this_category, category_span)
-							| TagRef::Both {
-								category: this_category,
-								category_span,
-								..
-							} if category == this_category => Some(category_span),
-							_ => None,
-						})
-						.unwrap(),
-				};
+				let span = Self::user_error_span(parsed, user_error);
 				let entity_name = match user_error {
 					UE::NoTagsByName(..) => "name",
-					UE::UnknownTag { .. } => "tag",
+					UE::UnknownTag { .. } | UE::UnknownUncategorizedTag(..) => "tag",
 					UE::UnknownTagCategory(..) => "category",
+					UE::UnknownProperty(..) => "property",
+					UE::InvalidPropertyValue { expected, .. } => {
+						return Diagnostic {
+							message: user_error.to_string().into(),
+							locus: Locus::Span(span),
+							locus_message: Some(format!("expected {expected}").into()),
+						}
+					}
 				};
+				let locus_message = format!("first occurrence of the nonexistent {entity_name}");
 				Diagnostic {
 					message: user_error.to_string().into(),
 					locus: Locus::Span(span),
-					locus_message: Some(format!("first occurrence of the nonexistent {entity_name}").into()),
+					locus_message: Some(locus_message.into()),
 				}
 			}
 		}
@@ -211,12 +264,17 @@ pub struct ViewSpecOrError {
 	pub raw: String,
 }
 
-impl<'de> Deserialize<'de> for ViewSpecOrError {
-	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-		let raw = String::deserialize(deserializer)?;
-		Ok(Self {
+impl ViewSpecOrError {
+	pub fn new(raw: String) -> Self {
+		Self {
 			parsed: viewspec::lex_and_parse(raw.bytes()),
 			raw,
-		})
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for ViewSpecOrError {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer).map(Self::new)
 	}
 }
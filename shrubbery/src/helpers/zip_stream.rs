@@ -0,0 +1,379 @@
+//! Writing ZIP archives as a stream, without seeking back to fill in sizes and checksums.
+//!
+//! Entries are stored without compression, since media formats are already compressed. The checksum and sizes of each entry follow its data in a data descriptor, and ZIP64 extensions are used where sizes or offsets do not fit in 32 bits.
+
+use std::time::SystemTime;
+
+use axum::body::Bytes;
+use time::OffsetDateTime;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// The upper byte of "version made by", which determines how external attributes are interpreted.
+const HOST_UNIX: u16 = 3 << 8;
+/// Sizes and checksum are in the data descriptor, and the name is UTF-8.
+const FLAGS: u16 = (1 << 3) | (1 << 11);
+/// A regular file with mode `0644`.
+const EXTERNAL_ATTRIBUTES: u32 = 0o100_644 << 16;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// A timestamp in the MS-DOS format used by ZIP, which has a resolution of two seconds and covers 1980 to 2107.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DosTime {
+	time: u16,
+	date: u16,
+}
+
+impl DosTime {
+	/// The earliest representable time, 1980-01-01 00:00:00.
+	pub const MIN: Self = Self {
+		time: 0,
+		date: (1 << 5) | 1,
+	};
+
+	/// Times outside the representable range are clamped.
+	pub fn new(time: OffsetDateTime) -> Self {
+		let year = time.year();
+		if year < 1980 {
+			return Self::MIN;
+		}
+		if year > 2107 {
+			return Self {
+				time: (23 << 11) | (59 << 5) | (58 / 2),
+				date: (127 << 9) | (12 << 5) | 31,
+			};
+		}
+		Self {
+			time: (u16::from(time.hour()) << 11)
+				| (u16::from(time.minute()) << 5)
+				| (u16::from(time.second()) / 2),
+			date: (u16::try_from(year - 1980).expect("year is in range") << 9)
+				| (u16::from(u8::from(time.month())) << 5)
+				| u16::from(time.day()),
+		}
+	}
+}
+
+impl From<SystemTime> for DosTime {
+	fn from(time: SystemTime) -> Self {
+		Self::new(OffsetDateTime::from(time))
+	}
+}
+
+struct CentralEntry {
+	name: String,
+	modified: DosTime,
+	crc32: u32,
+	size: u64,
+	offset: u64,
+}
+
+struct Pending {
+	entry: CentralEntry,
+	hasher: crc32fast::Hasher,
+}
+
+/// Produces the bytes of a ZIP archive piece by piece. The caller is responsible for passing entry data along unchanged, after telling the writer about it with [`Writer::data`].
+#[derive(Default)]
+pub struct Writer {
+	offset: u64,
+	entries: Vec<CentralEntry>,
+	pending: Option<Pending>,
+}
+
+fn fits_u32(value: u64) -> bool {
+	value < u64::from(u32::MAX)
+}
+
+/// The value of a 32-bit size or offset field, which is saturated if the real value is in the ZIP64 extra field.
+fn saturate(value: u64) -> u32 {
+	u32::try_from(value)
+		.ok()
+		.filter(|&value| value != u32::MAX)
+		.unwrap_or(u32::MAX)
+}
+
+struct Buffer(Vec<u8>);
+
+impl Buffer {
+	fn u16(&mut self, value: u16) -> &mut Self {
+		self.0.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn u32(&mut self, value: u32) -> &mut Self {
+		self.0.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn u64(&mut self, value: u64) -> &mut Self {
+		self.0.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn bytes(&mut self, value: &[u8]) -> &mut Self {
+		self.0.extend_from_slice(value);
+		self
+	}
+}
+
+fn len_u16(value: &[u8]) -> u16 {
+	u16::try_from(value.len()).expect("length fits in a u16")
+}
+
+impl Writer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn emit(&mut self, buffer: Buffer) -> Bytes {
+		self.offset += buffer.0.len() as u64;
+		buffer.0.into()
+	}
+
+	/// Starts an entry, returning its local header.
+	///
+	/// # Panics
+	///
+	/// If the previous entry was not finished, or the name is longer than 65535 bytes.
+	pub fn start_entry(&mut self, name: String, modified: DosTime) -> Bytes {
+		assert!(
+			self.pending.is_none(),
+			"the previous entry must be finished first"
+		);
+
+		let mut buffer = Buffer(Vec::with_capacity(30 + name.len()));
+		buffer
+			.u32(LOCAL_HEADER_SIGNATURE)
+			.u16(VERSION)
+			.u16(FLAGS)
+			.u16(0) // stored
+			.u16(modified.time)
+			.u16(modified.date)
+			.u32(0) // CRC-32, in the data descriptor
+			.u32(0) // compressed size, in the data descriptor
+			.u32(0) // uncompressed size, in the data descriptor
+			.u16(len_u16(name.as_bytes()))
+			.u16(0) // extra field length
+			.bytes(name.as_bytes());
+
+		self.pending = Some(Pending {
+			entry: CentralEntry {
+				name,
+				modified,
+				crc32: 0,
+				size: 0,
+				offset: self.offset,
+			},
+			hasher: crc32fast::Hasher::new(),
+		});
+		self.emit(buffer)
+	}
+
+	/// Accounts for data of the current entry, which the caller must output.
+	///
+	/// # Panics
+	///
+	/// If there is no current entry.
+	pub fn data(&mut self, data: &[u8]) {
+		let pending = self
+			.pending
+			.as_mut()
+			.expect("an entry must be started first");
+		pending.hasher.update(data);
+		pending.entry.size += data.len() as u64;
+		self.offset += data.len() as u64;
+	}
+
+	/// Finishes the current entry, returning its data descriptor.
+	///
+	/// # Panics
+	///
+	/// If there is no current entry.
+	pub fn finish_entry(&mut self) -> Bytes {
+		let Pending { mut entry, hasher } =
+			self.pending.take().expect("an entry must be started first");
+		entry.crc32 = hasher.finalize();
+
+		let mut buffer = Buffer(Vec::with_capacity(24));
+		buffer.u32(DATA_DESCRIPTOR_SIGNATURE).u32(entry.crc32);
+		if fits_u32(entry.size) {
+			buffer.u32(saturate(entry.size)).u32(saturate(entry.size));
+		} else {
+			buffer.u64(entry.size).u64(entry.size);
+		}
+
+		self.entries.push(entry);
+		self.emit(buffer)
+	}
+
+	/// Writes a complete entry whose data is already in memory.
+	pub fn entry(&mut self, name: String, modified: DosTime, data: Bytes) -> [Bytes; 3] {
+		let header = self.start_entry(name, modified);
+		self.data(&data);
+		[header, data, self.finish_entry()]
+	}
+
+	/// Returns the central directory, which ends the archive.
+	///
+	/// # Panics
+	///
+	/// If the current entry was not finished.
+	pub fn finish(mut self) -> Bytes {
+		assert!(
+			self.pending.is_none(),
+			"the last entry must be finished first"
+		);
+
+		let start = self.offset;
+		let mut buffer = Buffer(Vec::new());
+		for entry in &self.entries {
+			let mut extra = Buffer(Vec::new());
+			if !fits_u32(entry.size) {
+				// uncompressed and compressed size
+				extra.u64(entry.size).u64(entry.size);
+			}
+			if !fits_u32(entry.offset) {
+				extra.u64(entry.offset);
+			}
+			let (version, extra) = if extra.0.is_empty() {
+				(VERSION, Vec::new())
+			} else {
+				let mut field = Buffer(Vec::with_capacity(4 + extra.0.len()));
+				field
+					.u16(ZIP64_EXTRA_ID)
+					.u16(len_u16(&extra.0))
+					.bytes(&extra.0);
+				(VERSION_ZIP64, field.0)
+			};
+
+			buffer
+				.u32(CENTRAL_HEADER_SIGNATURE)
+				.u16(HOST_UNIX | version)
+				.u16(version)
+				.u16(FLAGS)
+				.u16(0) // stored
+				.u16(entry.modified.time)
+				.u16(entry.modified.date)
+				.u32(entry.crc32)
+				.u32(saturate(entry.size))
+				.u32(saturate(entry.size))
+				.u16(len_u16(entry.name.as_bytes()))
+				.u16(len_u16(&extra))
+				.u16(0) // comment length
+				.u16(0) // disk number
+				.u16(0) // internal attributes
+				.u32(EXTERNAL_ATTRIBUTES)
+				.u32(saturate(entry.offset))
+				.bytes(entry.name.as_bytes())
+				.bytes(&extra);
+		}
+		let end = start + buffer.0.len() as u64;
+		let central_size = end - start;
+		let num_entries = self.entries.len() as u64;
+
+		let num_entries_u16 = u16::try_from(num_entries)
+			.ok()
+			.filter(|&num| num != u16::MAX);
+		if num_entries_u16.is_none() || !fits_u32(start) || !fits_u32(central_size) {
+			buffer
+				.u32(ZIP64_END_SIGNATURE)
+				.u64(44) // size of the remaining record
+				.u16(HOST_UNIX | VERSION_ZIP64)
+				.u16(VERSION_ZIP64)
+				.u32(0) // this disk
+				.u32(0) // disk with the central directory
+				.u64(num_entries)
+				.u64(num_entries)
+				.u64(central_size)
+				.u64(start)
+				.u32(ZIP64_LOCATOR_SIGNATURE)
+				.u32(0) // disk with the ZIP64 end record
+				.u64(end)
+				.u32(1); // number of disks
+		}
+		let num_entries_u16 = num_entries_u16.unwrap_or(u16::MAX);
+		buffer
+			.u32(END_SIGNATURE)
+			.u16(0) // this disk
+			.u16(0) // disk with the central directory
+			.u16(num_entries_u16)
+			.u16(num_entries_u16)
+			.u32(saturate(central_size))
+			.u32(saturate(start))
+			.u16(0); // comment length
+
+		self.emit(buffer)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::{Cursor, Read as _};
+
+	use time::macros::datetime;
+
+	use super::{DosTime, Writer};
+
+	#[test]
+	fn dos_time() {
+		assert_eq!(DosTime::new(datetime!(1970-01-01 0:00 UTC)), DosTime::MIN);
+		assert_eq!(
+			DosTime::new(datetime!(2022-09-17 13:45:31 UTC)),
+			DosTime {
+				time: 0x6daf,
+				date: 0x5531,
+			}
+		);
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut writer = Writer::new();
+		let mut archive = Vec::new();
+		let modified = DosTime::new(datetime!(2022-09-17 13:45:30 UTC));
+
+		archive.extend_from_slice(&writer.start_entry("photos/cat.png".into(), modified));
+		for chunk in [&b"\x89PNG"[..], b"\r\n\x1a\n"] {
+			writer.data(chunk);
+			archive.extend_from_slice(chunk);
+		}
+		archive.extend_from_slice(&writer.finish_entry());
+		for part in writer.entry("ünïcode.txt".into(), DosTime::MIN, "text".into()) {
+			archive.extend_from_slice(&part);
+		}
+		archive.extend_from_slice(&writer.finish());
+
+		let mut archive = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+		assert_eq!(archive.len(), 2);
+		for (index, name, contents) in [
+			(0, "photos/cat.png", &b"\x89PNG\r\n\x1a\n"[..]),
+			(1, "ünïcode.txt", b"text"),
+		] {
+			let mut entry = archive.by_index(index).unwrap();
+			assert_eq!(entry.name(), name);
+			assert_eq!(entry.unix_mode(), Some(0o100_644));
+			let mut data = Vec::new();
+			// reading checks the CRC-32
+			entry.read_to_end(&mut data).unwrap();
+			assert_eq!(data, contents);
+		}
+		let entry = archive.by_index(0).unwrap();
+		let last_modified = entry.last_modified();
+		assert_eq!(
+			(
+				last_modified.year(),
+				last_modified.hour(),
+				last_modified.second()
+			),
+			(2022, 13, 30)
+		);
+	}
+}
@@ -28,9 +28,12 @@ use axum::Extension;
 mod config;
 mod database;
 mod error;
+mod fsck;
 mod helpers;
+mod maintenance;
 mod routes;
 mod server;
+mod storage;
 mod timestamp;
 mod token;
 
@@ -46,6 +49,44 @@ pub enum Error {
 	BindUnix(#[source] std::io::Error, std::path::PathBuf),
 	#[error("creating file storage directory due to it not existing at startup: {0}")]
 	CreateFileStorage(#[source] std::io::Error),
+	#[error("configuring storage: {0}")]
+	Storage(#[from] storage::Error),
+	#[error("usage: shrubbery [fsck [--repair] | apply-implications]")]
+	Usage,
+	#[error("checking storage: {0}")]
+	Fsck(#[from] fsck::Error),
+	#[error("{0} problems were found and not repaired")]
+	Inconsistent(usize),
+	#[error("applying tag implications: {0}")]
+	ApplyImplications(#[source] sqlx::Error),
+	#[error("{0} files have more than one tag in an exclusive tag category")]
+	ExclusiveViolations(usize),
+}
+
+enum Command {
+	Serve,
+	/// Check that the database and storage agree, and optionally repair what can be repaired.
+	Fsck {
+		repair: bool,
+	},
+	/// Add implied tags to existing files, such as after upgrading from a version without tag implications.
+	ApplyImplications,
+}
+
+fn parse_args() -> Result<Command, Error> {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	match args
+		.iter()
+		.map(String::as_str)
+		.collect::<Vec<_>>()
+		.as_slice()
+	{
+		[] => Ok(Command::Serve),
+		["fsck"] => Ok(Command::Fsck { repair: false }),
+		["fsck", "--repair"] => Ok(Command::Fsck { repair: true }),
+		["apply-implications"] => Ok(Command::ApplyImplications),
+		_ => Err(Error::Usage),
+	}
 }
 
 struct ErrorReturn(Result<(), Error>);
@@ -69,11 +110,13 @@ fn main() -> ErrorReturn {
 
 #[tokio::main]
 async fn main_() -> Result<(), Error> {
+	let command = parse_args()?;
 	let config = config::config()?;
 
 	if !config.file_storage.exists() {
 		std::fs::create_dir_all(&config.file_storage).map_err(Error::CreateFileStorage)?;
 	}
+	let storage = storage::from_config(&config)?;
 
 	let config = Arc::new(config);
 
@@ -83,8 +126,45 @@ async fn main_() -> Result<(), Error> {
 		.await
 		.map(Arc::new)?;
 
+	match command {
+		Command::Serve => {}
+		Command::Fsck { repair } => {
+			let remaining = fsck::run(&config, &database, &*storage, repair).await?;
+			return if remaining == 0 {
+				Ok(())
+			} else {
+				Err(Error::Inconsistent(remaining))
+			};
+		}
+		Command::ApplyImplications => {
+			let changed = helpers::implications::apply(None, &*database)
+				.await
+				.map_err(Error::ApplyImplications)?;
+			println!("added implied tags to {changed} files");
+			// the tags are added anyway, since implications always hold, but the conflicts need to be fixed by hand
+			let violations = helpers::tag_categories::exclusive_violations(None, None, &*database)
+				.await
+				.map_err(Error::ApplyImplications)?;
+			for violation in &violations {
+				println!("{violation}");
+			}
+			return if violations.is_empty() {
+				Ok(())
+			} else {
+				Err(Error::ExclusiveViolations(violations.len()))
+			};
+		}
+	}
+
+	tokio::spawn(maintenance::run(
+		Arc::clone(&config),
+		Arc::clone(&database),
+		Arc::clone(&storage),
+	));
+
 	let mut app = routes::configure();
 	app = app.layer(Extension(database));
+	app = app.layer(Extension(storage));
 	app = app.layer(Extension(Arc::clone(&config)));
 	app = app.layer(tower_http::trace::TraceLayer::new_for_http());
 
@@ -0,0 +1,87 @@
+//! Cleanup that happens in the background rather than in response to requests.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::helpers::{trash, versions};
+use crate::storage::{Key, Storage};
+
+const INTERVAL: Duration = Duration::from_hours(1);
+
+async fn modified_time(storage: &dyn Storage, key: Key) -> Option<SystemTime> {
+	match storage.metadata(key).await {
+		Ok(metadata) => metadata?.modified,
+		Err(error) => {
+			tracing::warn!("could not get modification time of {key}: {error}");
+			None
+		}
+	}
+}
+
+/// Fills in the times of files that were uploaded before they were recorded, from the modification times of their stored content. Returns the number of files that were filled in.
+///
+/// Replacing a file moves its previous content to a version, so the content of the first version is from when the file was uploaded.
+async fn backfill_file_times(database: &Database, storage: &dyn Storage) -> sqlx::Result<usize> {
+	let files = sqlx::query!(
+		r#"SELECT id, (SELECT id FROM file_versions WHERE file = files.id ORDER BY number LIMIT 1) as "first_version?" FROM files WHERE uploaded_time IS NULL OR modified_time IS NULL"#
+	)
+	.fetch_all(database)
+	.await?;
+	let mut num_filled = 0;
+	for file in files {
+		let modified = match modified_time(storage, Key::File(file.id)).await {
+			Some(modified) => OffsetDateTime::from(modified),
+			None => continue,
+		};
+		let uploaded = match file.first_version {
+			Some(version_id) => modified_time(storage, Key::Version(version_id))
+				.await
+				.map_or(modified, OffsetDateTime::from),
+			None => modified,
+		};
+		sqlx::query!(
+			"UPDATE files SET uploaded_time = COALESCE(uploaded_time, $2), modified_time = COALESCE(modified_time, $3) WHERE id = $1",
+			file.id,
+			uploaded,
+			modified,
+		)
+		.execute(database)
+		.await?;
+		num_filled += 1;
+	}
+	Ok(num_filled)
+}
+
+/// Runs forever, pruning versions that are too old and purging files that have been in the trash for too long. Pruning versions by number happens when files are replaced, so this only has to deal with age.
+///
+/// Before that, the times of files that were uploaded before they were recorded are filled in.
+pub async fn run(config: Arc<Config>, database: Arc<Database>, storage: Arc<dyn Storage>) {
+	match backfill_file_times(&database, &*storage).await {
+		Ok(0) => {}
+		Ok(num_filled) => tracing::info!("filled in the upload times of {num_filled} files"),
+		Err(error) => tracing::warn!("could not fill in the upload times of files: {error}"),
+	}
+
+	let mut interval = tokio::time::interval(INTERVAL);
+	loop {
+		interval.tick().await;
+
+		if config.version_retention.max_age_days.is_some() {
+			match versions::prune(None, &config, &database, &*storage).await {
+				Ok(0) => {}
+				Ok(num_removed) => tracing::info!("pruned {num_removed} old versions of files"),
+				Err(error) => tracing::warn!("could not prune old versions of files: {error}"),
+			}
+		}
+
+		match trash::purge_expired(&config, &database, &*storage).await {
+			Ok(0) => {}
+			Ok(num_purged) => tracing::info!("purged {num_purged} files from the trash"),
+			Err(error) => tracing::warn!("could not purge expired files from the trash: {error}"),
+		}
+	}
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::auth::Admin;
+use crate::helpers::perceptual_hash;
+use crate::storage::{self, Key, Storage};
+
+#[derive(askama::Template)]
+#[template(path = "admin/duplicates.html")]
+struct Template {
+	self_user: models::User,
+	clusters: Vec<Vec<(models::FileId, String)>>,
+	num_unhashed: i64,
+	/// The `after` of the next page, if there is one.
+	next: Option<models::FileId>,
+}
+crate::helpers::impl_into_response!(Template);
+
+/// How many files are compared against every other file on each page, which bounds the work done per page instead of comparing every pair of files at once.
+const FILES_PER_PAGE: i64 = 500;
+
+/// Groups the files into clusters where each file is similar to at least one other file in the cluster.
+///
+/// Clusters are sorted by their lowest ID, as are the files within them.
+fn cluster(
+	pairs: impl IntoIterator<Item = (models::FileId, models::FileId)>,
+) -> Vec<Vec<models::FileId>> {
+	fn root(
+		parents: &mut BTreeMap<models::FileId, models::FileId>,
+		mut id: models::FileId,
+	) -> models::FileId {
+		while let Some(&parent) = parents.get(&id).filter(|&&parent| parent != id) {
+			// path halving
+			let grandparent = parents[&parent];
+			parents.insert(id, grandparent);
+			id = grandparent;
+		}
+		id
+	}
+
+	let mut parents = BTreeMap::new();
+	for (a, b) in pairs {
+		parents.entry(a).or_insert(a);
+		parents.entry(b).or_insert(b);
+		let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+		// keep the lower ID as the root so the clusters come out in a stable order
+		parents.insert(std::cmp::max(a, b), std::cmp::min(a, b));
+	}
+
+	let mut clusters: BTreeMap<models::FileId, Vec<models::FileId>> = BTreeMap::new();
+	let ids: Vec<_> = parents.keys().copied().collect();
+	for id in ids {
+		let root = root(&mut parents, id);
+		clusters.entry(root).or_default().push(id);
+	}
+	clusters.into_values().collect()
+}
+
+/// Whether all of `ids` are in the same cluster.
+fn is_one_cluster(
+	pairs: impl IntoIterator<Item = (models::FileId, models::FileId)>,
+	ids: &[models::FileId],
+) -> bool {
+	cluster(pairs)
+		.iter()
+		.any(|cluster| ids.iter().all(|id| cluster.contains(id)))
+}
+
+#[derive(serde::Deserialize)]
+struct Query {
+	after: Option<models::FileId>,
+}
+
+async fn get_handler(
+	Admin(self_user): Admin,
+	extract::Query(Query { after }): extract::Query<Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let database = &*database;
+
+	// the files on this page are compared against all files, so a cluster can include files from other pages
+	let page = sqlx::query_scalar!(
+		"SELECT id FROM files WHERE deleted_time IS NULL AND perceptual_hash IS NOT NULL AND id > $1 ORDER BY id LIMIT $2",
+		after.unwrap_or(0),
+		FILES_PER_PAGE,
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let next = if i64::try_from(page.len()).unwrap_or(i64::MAX) >= FILES_PER_PAGE {
+		page.last().copied()
+	} else {
+		None
+	};
+	let pairs = sqlx::query!(
+		"SELECT a.id as a, b.id as b FROM files a JOIN files b ON a.id <> b.id WHERE a.id = ANY($1) AND b.deleted_time IS NULL AND bit_count((a.perceptual_hash # b.perceptual_hash)::bit(64)) <= $2",
+		&page,
+		i64::from(config.similarity_threshold),
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let clusters = cluster(pairs.into_iter().map(|record| (record.a, record.b)));
+
+	let ids: Vec<_> = clusters.iter().flatten().copied().collect();
+	let names: HashMap<_, _> = sqlx::query!("SELECT id, name FROM files WHERE id = ANY($1)", &ids)
+		.fetch_all(database)
+		.await
+		.map_err(error::Sqlx)?
+		.into_iter()
+		.map(|record| (record.id, record.name))
+		.collect();
+	let clusters = clusters
+		.into_iter()
+		.map(|cluster| {
+			cluster
+				.into_iter()
+				// files deleted in the meantime are skipped
+				.filter_map(|id| Some((id, names.get(&id)?.clone())))
+				.collect()
+		})
+		.collect();
+
+	let num_unhashed = sqlx::query!(
+		r#"SELECT count(*) as "count!" FROM files WHERE media_type = 'image' AND perceptual_hash IS NULL"#
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?
+	.count;
+
+	Ok(Template {
+		self_user,
+		clusters,
+		num_unhashed,
+		next,
+	})
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "merge")]
+	Merge {
+		keep: models::FileId,
+		files: Vec<models::FileId>,
+	},
+	#[multipart(rename = "hash-missing")]
+	HashMissing {},
+}
+
+/// Merges the tags of `files` into `keep`, then moves `files` to the trash.
+///
+/// The files must still be a cluster of similar files outside the trash, like the page showed, so that an old page or a crafted request can't trash unrelated files.
+async fn merge(
+	self_user: &models::User,
+	keep: models::FileId,
+	mut files: Vec<models::FileId>,
+	config: &Config,
+	database: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<(), ErrorResponse> {
+	files.retain(|&id| id != keep);
+	files.sort_unstable();
+	files.dedup();
+	if files.is_empty() {
+		return Err(error::BadRequest("choose files to merge into the kept file".into()).into());
+	}
+
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let all: Vec<_> = std::iter::once(keep).chain(files.iter().copied()).collect();
+	// lock the files so that they can't be trashed or replaced before the merge is done
+	sqlx::query!("SELECT id FROM files WHERE id = ANY($1) FOR UPDATE", &all)
+		.fetch_all(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	let pairs = sqlx::query!(
+		"SELECT a.id as a, b.id as b FROM files a JOIN files b ON a.id < b.id WHERE a.id = ANY($1) AND b.id = ANY($1) AND a.deleted_time IS NULL AND b.deleted_time IS NULL AND bit_count((a.perceptual_hash # b.perceptual_hash)::bit(64)) <= $2",
+		&all,
+		i64::from(config.similarity_threshold),
+	)
+	.fetch_all(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	if !is_one_cluster(pairs.into_iter().map(|record| (record.a, record.b)), &all) {
+		return Err(
+			error::BadRequest(
+				"these files are no longer similar to each other, or some are in the trash".into(),
+			)
+			.into(),
+		);
+	}
+
+	sqlx::query!(
+		"INSERT INTO file_tags (file, tag) (SELECT DISTINCT $1::bigint as file, tag FROM file_tags WHERE file = ANY($2)) ON CONFLICT DO NOTHING",
+		keep,
+		&files,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	sqlx::query!(
+		"UPDATE files SET deleted_time = CURRENT_TIMESTAMP, deleted_by = $2 WHERE id = ANY($1) AND deleted_time IS NULL",
+		&files,
+		self_user.id,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	transaction.commit().await.map_err(error::Sqlx)?;
+
+	Ok(())
+}
+
+async fn hash_missing(
+	config: &Config,
+	database: &sqlx::Pool<sqlx::Postgres>,
+	storage: &dyn Storage,
+) -> Result<(), ErrorResponse> {
+	let unhashed =
+		sqlx::query!("SELECT id FROM files WHERE media_type = 'image' AND perceptual_hash IS NULL")
+			.fetch_all(database)
+			.await
+			.map_err(error::Sqlx)?;
+
+	for record in unhashed {
+		let id = record.id;
+		let local = match storage::local_copy(storage, Key::File(id), &config.file_storage).await {
+			Ok(local) => local,
+			Err(error) => {
+				tracing::warn!("could not read file {id} to hash it: {error}");
+				continue;
+			}
+		};
+		if let Some(hash) =
+			perceptual_hash::hash_file(local.to_path_buf(), models::MediaType::Image).await
+		{
+			sqlx::query!(
+				"UPDATE files SET perceptual_hash = $2 WHERE id = $1",
+				id,
+				hash
+			)
+			.execute(database)
+			.await
+			.map_err(error::Sqlx)?;
+		}
+	}
+
+	Ok(())
+}
+
+async fn post_handler(
+	Admin(self_user): Admin,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	match req {
+		PostRequest::Merge { keep, files } => {
+			merge(&self_user, keep, files, &config, database).await?;
+		}
+		PostRequest::HashMissing {} => hash_missing(&config, database, &*storage).await?,
+	}
+
+	Ok(Redirect::to("/admin/duplicates").into_response())
+}
+
+pub fn configure() -> Router {
+	Router::new().route("/", axum::routing::get(get_handler).post(post_handler))
+}
+
+#[cfg(test)]
+mod test {
+	use super::{cluster, is_one_cluster};
+
+	#[test]
+	fn clusters() {
+		assert_eq!(cluster([]), Vec::<Vec<i64>>::new());
+		assert_eq!(
+			cluster([(1, 2), (5, 6), (2, 3), (7, 6), (3, 1)]),
+			[vec![1, 2, 3], vec![5, 6, 7]]
+		);
+		// joining two existing clusters
+		assert_eq!(cluster([(4, 9), (2, 3), (3, 9)]), [vec![2, 3, 4, 9]]);
+	}
+
+	#[test]
+	fn one_cluster() {
+		// 1 and 3 are only similar through 2
+		assert!(is_one_cluster([(1, 2), (2, 3)], &[1, 2, 3]));
+		assert!(!is_one_cluster([(1, 2), (3, 4)], &[1, 2, 3]));
+		assert!(!is_one_cluster([], &[1, 2]));
+	}
+}
@@ -4,6 +4,7 @@ use axum::Router;
 use crate::database::models::User;
 use crate::helpers::auth::Admin;
 
+pub mod duplicates;
 pub mod sql;
 pub mod tag_categories;
 pub mod users;
@@ -25,6 +26,6 @@ use super::sub;
 pub fn configure() -> Router {
 	let mut app = Router::new();
 	app = app.route("/", axum::routing::get(handler));
-	sub!(app; sql, tag_categories, users);
+	sub!(app; duplicates, sql, tag_categories, users);
 	app
 }
@@ -15,9 +15,19 @@ pub async fn post_handler(
 	extract::Path((tag_category_id,)): extract::Path<(models::TagCategoryId,)>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
-	models::TagCategory::delete_row(&*database, tag_category_id)
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	// subcategories move up a level rather than to the top
+	sqlx::query!(
+		"UPDATE tag_categories SET parent = (SELECT parent FROM tag_categories WHERE id = $1) WHERE parent = $1",
+		tag_category_id,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	models::TagCategory::delete_row(&mut transaction, tag_category_id)
 		.await
 		.map_err(error::Sqlx)?;
+	transaction.commit().await.map_err(error::Sqlx)?;
 	Ok(Redirect::to(&format!(
 		"/admin/users?{}",
 		req.uri.query().unwrap_or("")
@@ -5,7 +5,7 @@ use axum::{extract, Router};
 
 use crate::database::{models, Database};
 use crate::error;
-use crate::helpers::{auth, set_none_if_empty};
+use crate::helpers::{auth, set_none_if_empty, OrNull};
 
 #[derive(askama::Template)]
 #[template(path = "admin/tag_categories/edit.html")]
@@ -13,6 +13,8 @@ struct Template {
 	updated: bool,
 	self_user: models::User,
 	requested_tag_category: super::TagCategoryWithUserResolved,
+	/// Every category, to choose the parent from.
+	all_categories: Vec<(models::TagCategoryId, String)>,
 }
 crate::helpers::impl_into_response!(Template);
 
@@ -21,7 +23,7 @@ impl super::TagCategoryWithUserResolved {
 		database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
 		id: models::TagCategoryId,
 	) -> sqlx::Result<Option<Self>> {
-		sqlx::query_as!(super::TagCategoryWithUserResolved, r#"SELECT tag_categories.id, tag_categories.name, tag_categories.description, tag_categories.color as "color: models::Color", tag_categories.created_time as "created_time: crate::timestamp::Timestamp", (SELECT name FROM users WHERE id = tag_categories.created_by) as created_by FROM tag_categories WHERE id = $1"#, id).fetch_optional(database).await
+		sqlx::query_as!(super::TagCategoryWithUserResolved, r#"SELECT tag_categories.id, tag_categories.name, tag_categories.description, tag_categories.color as "color: models::Color", tag_categories.parent, tag_category_tree.depth as "depth!", tag_categories.exclusive, tag_categories.required, tag_categories.created_time as "created_time: crate::timestamp::Timestamp", (SELECT name FROM users WHERE id = tag_categories.created_by) as created_by FROM tag_categories INNER JOIN tag_category_tree ON tag_categories.id = tag_category_tree.id WHERE tag_categories.id = $1"#, id).fetch_optional(database).await
 	}
 }
 
@@ -37,11 +39,13 @@ pub async fn get_handler(
 			.await
 			.map_err(error::Sqlx)?
 			.ok_or(error::EntityNotFound("tag category"))?;
+	let all_categories = super::all_categories(database).await.map_err(error::Sqlx)?;
 
 	Ok(Template {
 		updated: false,
 		self_user,
 		requested_tag_category,
+		all_categories,
 	})
 }
 
@@ -50,6 +54,11 @@ pub struct PostRequest {
 	name: String,
 	description: Option<String>,
 	color: models::Color,
+	parent: OrNull<models::TagCategoryId>,
+	/// Present if checked.
+	exclusive: Option<String>,
+	/// Present if checked.
+	required: Option<String>,
 }
 
 pub async fn post_handler(
@@ -61,31 +70,62 @@ pub async fn post_handler(
 	let database = &*database;
 
 	set_none_if_empty(&mut request.description);
+	let parent = request.parent.into_option();
 
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	// only one category can be moved at a time, since two moves that are each fine on their own can make a cycle together
+	sqlx::query!("LOCK TABLE tag_categories IN SHARE ROW EXCLUSIVE MODE")
+		.execute(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	if let Some(parent) = parent {
+		let cycle = sqlx::query_scalar!(
+			r#"WITH RECURSIVE ancestors (id) AS (SELECT $1::int UNION SELECT tag_categories.parent FROM ancestors INNER JOIN tag_categories ON tag_categories.id = ancestors.id WHERE tag_categories.parent IS NOT NULL) SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $2) as "cycle!""#,
+			parent,
+			tag_category_id,
+		)
+		.fetch_one(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+		if cycle {
+			return Err(
+				error::BadRequest(
+					"a tag category cannot be inside itself or one of its subcategories".into(),
+				)
+				.into(),
+			);
+		}
+	}
 	let query_result = sqlx::query!(
-		"UPDATE tag_categories SET name = $2, description = $3, color = $4 WHERE id = $1",
+		"UPDATE tag_categories SET name = $2, description = $3, color = $4, parent = $5, exclusive = $6, required = $7 WHERE id = $1",
 		tag_category_id,
 		request.name,
 		request.description,
-		request.color as _
+		request.color as _,
+		parent,
+		request.exclusive.is_some(),
+		request.required.is_some(),
 	)
-	.execute(database)
+	.execute(&mut transaction)
 	.await
 	.map_err(error::Sqlx)?;
 	if query_result.rows_affected() == 0 {
 		return Err(error::EntityNotFound("tag category").into());
 	}
+	transaction.commit().await.map_err(error::Sqlx)?;
 
 	let requested_tag_category =
 		super::TagCategoryWithUserResolved::get_by_id(database, tag_category_id)
 			.await
 			.map_err(error::Sqlx)?
 			.ok_or(error::EntityNotFound("tag category"))?;
+	let all_categories = super::all_categories(database).await.map_err(error::Sqlx)?;
 
 	Ok(Template {
 		updated: true,
 		self_user,
 		requested_tag_category,
+		all_categories,
 	})
 }
 
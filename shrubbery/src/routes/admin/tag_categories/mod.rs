@@ -6,7 +6,7 @@ use axum::{extract, Router};
 use crate::database::{models, Database};
 use crate::error;
 use crate::helpers::auth::Admin;
-use crate::helpers::pagination;
+use crate::helpers::{pagination, OrNull};
 
 mod delete;
 mod edit;
@@ -16,15 +16,33 @@ struct TagCategoryWithUserResolved {
 	pub name: String,
 	pub description: Option<String>,
 	pub color: models::Color,
+	pub parent: Option<models::TagCategoryId>,
+	/// How many ancestors the category has.
+	pub depth: i32,
+	pub exclusive: bool,
+	pub required: bool,
 	pub created_time: crate::timestamp::Timestamp,
 	pub created_by: Option<String>,
 }
 
+/// Every category as `(id, name)`, in the order of the tree.
+async fn all_categories(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Vec<(models::TagCategoryId, String)>> {
+	sqlx::query!("SELECT tag_categories.id, tag_categories.name FROM tag_categories INNER JOIN tag_category_tree ON tag_categories.id = tag_category_tree.id ORDER BY tag_category_tree.path")
+		.map(|record| (record.id, record.name))
+		.fetch_all(database)
+		.await
+}
+
 #[derive(askama::Template)]
 #[template(path = "admin/tag_categories/index.html")]
 struct Template {
 	self_user: models::User,
+	/// Each category comes right before its subcategories.
 	tag_categories: Vec<TagCategoryWithUserResolved>,
+	/// Every category, to choose the parent of a new one.
+	all_categories: Vec<(models::TagCategoryId, String)>,
 	pagination: pagination::Template,
 }
 crate::helpers::impl_into_response!(Template);
@@ -47,11 +65,14 @@ pub async fn get_handler(
 		return Err(error::EntityNotFound("page").into());
 	}
 
-	let tag_categories = sqlx::query_as!(TagCategoryWithUserResolved, r#"SELECT tag_categories.id, tag_categories.name, tag_categories.description, tag_categories.color as "color: models::Color", tag_categories.created_time as "created_time: crate::timestamp::Timestamp", users.username as "created_by?" FROM tag_categories LEFT JOIN users ON tag_categories.created_by = users.id ORDER BY tag_categories.id OFFSET $1 LIMIT $2"#, pagination.offset(), pagination.limit()).fetch_all(database).await.map_err(error::Sqlx)?;
+	let tag_categories = sqlx::query_as!(TagCategoryWithUserResolved, r#"SELECT tag_categories.id, tag_categories.name, tag_categories.description, tag_categories.color as "color: models::Color", tag_categories.parent, tag_category_tree.depth as "depth!", tag_categories.exclusive, tag_categories.required, tag_categories.created_time as "created_time: crate::timestamp::Timestamp", users.username as "created_by?" FROM tag_categories INNER JOIN tag_category_tree ON tag_categories.id = tag_category_tree.id LEFT JOIN users ON tag_categories.created_by = users.id ORDER BY tag_category_tree.path OFFSET $1 LIMIT $2"#, pagination.offset(), pagination.limit()).fetch_all(database).await.map_err(error::Sqlx)?;
+
+	let all_categories = all_categories(database).await.map_err(error::Sqlx)?;
 
 	Ok(Template {
 		self_user,
 		tag_categories,
+		all_categories,
 		pagination: pagination::Template::from_query(pagination, num_pages),
 	})
 }
@@ -61,6 +82,11 @@ pub struct CreateRequest {
 	name: String,
 	description: String,
 	color: models::Color,
+	parent: OrNull<models::TagCategoryId>,
+	/// Present if checked.
+	exclusive: Option<String>,
+	/// Present if checked.
+	required: Option<String>,
 }
 
 pub async fn post_handler(
@@ -79,6 +105,9 @@ pub async fn post_handler(
 			Some(req.description)
 		},
 		color: req.color,
+		parent: req.parent.into_option(),
+		exclusive: req.exclusive.is_some(),
+		required: req.required.is_some(),
 		created_by: Some(self_user.id),
 	}
 	.insert(&**database)
@@ -3,9 +3,11 @@ use std::sync::Arc;
 use axum::response::{ErrorResponse, IntoResponse};
 use axum::{extract, Router};
 
+use crate::config::Config;
 use crate::database::{models, Database};
 use crate::error;
-use crate::helpers::{auth, set_none_if_empty};
+use crate::helpers::metadata::display_size;
+use crate::helpers::{auth, quota, set_none_if_empty};
 
 #[derive(askama::Template)]
 #[template(path = "admin/users/edit.html")]
@@ -13,23 +15,73 @@ struct Template {
 	updated: bool,
 	self_user: models::User,
 	requested_user: models::User,
+	usage: quota::Usage,
+	/// The limits that apply when the user has none of their own.
+	default_limits: quota::Limits,
 }
 crate::helpers::impl_into_response!(Template);
 
+impl Template {
+	async fn new(
+		updated: bool,
+		self_user: models::User,
+		requested_user: models::User,
+		config: &Config,
+		database: &Database,
+	) -> Result<Self, error::Sqlx> {
+		let usage = quota::usage(requested_user.id, database)
+			.await
+			.map_err(error::Sqlx)?;
+		Ok(Self {
+			updated,
+			self_user,
+			requested_user,
+			usage,
+			default_limits: quota::Limits::new(None, None, config),
+		})
+	}
+
+	fn display_usage(&self) -> String {
+		let bytes = match self.requested_user.max_bytes.or(self.default_limits.bytes) {
+			Some(limit) => format!(
+				"{} of {}",
+				display_size(self.usage.bytes),
+				display_size(limit)
+			),
+			None => display_size(self.usage.bytes).to_string(),
+		};
+		let files = match self.requested_user.max_files.or(self.default_limits.files) {
+			Some(limit) => format!("{} of {limit} files", self.usage.files),
+			None => format!("{} files", self.usage.files),
+		};
+		format!("{bytes}, {files}")
+	}
+}
+
 pub async fn get_handler(
 	auth::Admin(self_user): auth::Admin,
 	extract::Path((user_id,)): extract::Path<(models::UserId,)>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
 	let requested_user = models::User::by_id(&*database, user_id)
 		.await
 		.map_err(error::Sqlx)?
 		.ok_or(error::EntityNotFound("user"))?;
-	Ok(Template {
-		updated: false,
-		self_user,
-		requested_user,
-	})
+	Ok(Template::new(false, self_user, requested_user, &config, &database).await?)
+}
+
+/// Parses a limit from the form, where an empty field means the configured default.
+fn parse_limit(field: Option<&str>, name: &str) -> Result<Option<i64>, error::BadRequest> {
+	match field.map(str::trim) {
+		None | Some("") => Ok(None),
+		Some(value) => match value.parse() {
+			Ok(limit) if limit >= 0 => Ok(Some(limit)),
+			_ => Err(error::BadRequest(
+				format!("{name} must be a non-negative integer").into(),
+			)),
+		},
+	}
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -38,18 +90,23 @@ pub struct PostRequest {
 	password: Option<String>,
 	email: Option<String>,
 	role: models::UserRole,
+	max_bytes: Option<String>,
+	max_files: Option<String>,
 }
 
 pub async fn post_handler(
 	auth::Admin(self_user): auth::Admin,
 	extract::Path((user_id,)): extract::Path<(models::UserId,)>,
 	extract::Form(mut request): extract::Form<PostRequest>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
 	let database = &*database;
 
 	set_none_if_empty(&mut request.password);
 	set_none_if_empty(&mut request.email);
+	let max_bytes = parse_limit(request.max_bytes.as_deref(), "storage quota")?;
+	let max_files = parse_limit(request.max_files.as_deref(), "file limit")?;
 
 	let mut requested_user = models::User::by_id(database, user_id)
 		.await
@@ -76,11 +133,15 @@ pub async fn post_handler(
 		.set_role(database, request.role)
 		.await
 		.map_err(error::Sqlx)?;
-	Ok(Template {
-		updated: true,
-		self_user,
-		requested_user,
-	})
+	requested_user
+		.set_max_bytes(database, max_bytes)
+		.await
+		.map_err(error::Sqlx)?;
+	requested_user
+		.set_max_files(database, max_files)
+		.await
+		.map_err(error::Sqlx)?;
+	Ok(Template::new(true, self_user, requested_user, &config, database).await?)
 }
 
 pub fn configure() -> Router {
@@ -0,0 +1,99 @@
+//! Suggestions for what is being typed, as JSON, for editors that can't list every choice at once.
+
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse};
+use axum::{extract, Json, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, escape_like, tag_text};
+
+/// The most suggestions returned at once.
+const LIMIT: i64 = 20;
+
+#[derive(serde::Serialize)]
+struct TagSuggestion {
+	id: models::TagId,
+	name: String,
+	category: Option<String>,
+	/// The tag as a line of text. See [`tag_text`].
+	label: String,
+	/// The alias that matched, as a line of text, if the tag itself didn't match as well.
+	alias: Option<String>,
+	/// The number of files with the tag.
+	uses: i64,
+}
+
+/// `LIKE` patterns for text starting with `query`, containing it, and containing its characters in order with anything in between, from best match to worst.
+fn patterns(query: &str) -> [String; 3] {
+	let escaped = escape_like(query);
+	let mut fuzzy = String::with_capacity(escaped.len() * 2 + 1);
+	fuzzy.push('%');
+	for ch in query.chars() {
+		fuzzy.push_str(&escape_like(ch.encode_utf8(&mut [0; 4])));
+		fuzzy.push('%');
+	}
+	[format!("{escaped}%"), format!("%{escaped}%"), fuzzy]
+}
+
+#[derive(serde::Deserialize)]
+struct TagsQuery {
+	#[serde(default)]
+	q: String,
+}
+
+/// Tags and aliases matching `q` by name or by `category:name`, ignoring case. Better matches come first, then more used tags, then shorter names.
+///
+/// With an empty `q`, every tag matches, so the most used tags are returned.
+async fn tags_handler(
+	_self_user: auth::Auth,
+	extract::Query(TagsQuery { q }): extract::Query<TagsQuery>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let [prefix, substring, fuzzy] = patterns(q.trim());
+	let suggestions = sqlx::query!(
+		r#"WITH candidates (tag, alias, alias_category, label) AS (SELECT tags.id, NULL::text, NULL::text, tags.name FROM tags UNION ALL SELECT tags.id, NULL, NULL, tag_categories.name || ':' || tags.name FROM tags INNER JOIN tag_categories ON tags.category = tag_categories.id UNION ALL SELECT tag_aliases.tag, tag_aliases.name, tag_categories.name, tag_aliases.name FROM tag_aliases LEFT JOIN tag_categories ON tag_aliases.category = tag_categories.id UNION ALL SELECT tag_aliases.tag, tag_aliases.name, tag_categories.name, tag_categories.name || ':' || tag_aliases.name FROM tag_aliases INNER JOIN tag_categories ON tag_aliases.category = tag_categories.id), ranked AS (SELECT DISTINCT ON (tag) tag, alias, alias_category, CASE WHEN label ILIKE $1 THEN 0 WHEN label ILIKE $2 THEN 1 ELSE 2 END AS rank FROM candidates WHERE label ILIKE $3 ORDER BY tag, rank, alias NULLS FIRST) SELECT tags.id, tags.name, tag_categories.name as "category?", ranked.alias as "alias?", ranked.alias_category, tags.num_files as uses FROM ranked INNER JOIN tags ON ranked.tag = tags.id LEFT JOIN tag_categories ON tags.category = tag_categories.id ORDER BY ranked.rank, tags.num_files DESC, length(tags.name), tag_categories.name NULLS FIRST, tags.name LIMIT $4"#,
+		prefix,
+		substring,
+		fuzzy,
+		LIMIT,
+	)
+	.fetch_all(&*database)
+	.await
+	.map_err(error::Sqlx)?
+	.into_iter()
+	.map(|record| TagSuggestion {
+		label: tag_text::format(record.category.as_deref(), &record.name),
+		alias: record
+			.alias
+			.map(|alias| tag_text::format(record.alias_category.as_deref(), &alias)),
+		id: record.id,
+		name: record.name,
+		category: record.category,
+		uses: record.uses,
+	})
+	.collect::<Vec<_>>();
+	Ok(Json(suggestions))
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/tags", axum::routing::get(tags_handler));
+
+	router
+}
+
+#[cfg(test)]
+mod test {
+	#[test]
+	fn patterns() {
+		assert_eq!(super::patterns("ab"), ["ab%", "%ab%", "%a%b%"]);
+		assert_eq!(super::patterns(""), ["%", "%%", "%"]);
+		assert_eq!(
+			super::patterns(r"5%_a\"),
+			[r"5\%\_a\\%", r"%5\%\_a\\%", r"%5%\%%\_%a%\\%"]
+		);
+	}
+}
@@ -0,0 +1,316 @@
+//! Adding and removing tags on every file that matches a viewspec at once.
+//!
+//! Changes are previewed before they are applied. The viewspec is evaluated again when the changes are applied, so files that started or stopped matching in between are included or left out.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::viewspec::{evaluate, ViewSpecOrError};
+use crate::helpers::{auth, implications, percent, tag_categories};
+use crate::routes::files::id::{get_tags_by_category, TagsByCategory};
+
+/// How many of the matching files would change, once what their tags imply is added.
+struct Preview {
+	/// Files that gain at least one tag, either one of the tags to add or one it implies.
+	gaining: usize,
+	/// Files that lose at least one of the tags to remove. Tags that are implied by a tag the file keeps are not lost.
+	losing: usize,
+	/// Files in either of the above.
+	changing: usize,
+}
+
+enum Stage {
+	Choose,
+	Preview {
+		add: Vec<models::TagId>,
+		remove: Vec<models::TagId>,
+		preview: Preview,
+	},
+	Applied {
+		changed: usize,
+	},
+}
+
+#[derive(askama::Template)]
+#[template(path = "bulk_tags.html")]
+struct Template {
+	self_user: models::User,
+	query: String,
+	matching: usize,
+	tags_by_category: TagsByCategory,
+	stage: Stage,
+}
+crate::helpers::impl_into_response!(Template);
+
+impl Template {
+	/// The names of `tags` as `category:name`, or just `name` for tags without a category.
+	fn tag_names(&self, tags: &[models::TagId]) -> String {
+		let mut names = Vec::with_capacity(tags.len());
+		for group in &self.tags_by_category {
+			for (id, name, _present) in &group.tags {
+				if tags.contains(id) {
+					names.push(match group.category() {
+						Some(category) => format!("{category}:{name}"),
+						None => name.clone(),
+					});
+				}
+			}
+		}
+		names.join(", ")
+	}
+}
+
+/// The search page explains what is wrong with the viewspec.
+fn search_page(raw: &str) -> Response {
+	Redirect::to(&format!("/?search={}", percent::encode(raw.as_bytes()))).into_response()
+}
+
+/// Finds the matching files, or returns the response that redirects to the search page if the viewspec is invalid.
+async fn matching_files(
+	viewspec: ViewSpecOrError,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> Result<(String, Vec<models::FileId>), ErrorResponse> {
+	let (raw, viewspec) = match viewspec {
+		ViewSpecOrError {
+			raw,
+			parsed: Ok(viewspec),
+		} => (raw, viewspec),
+		ViewSpecOrError {
+			raw,
+			parsed: Err(_),
+		} => return Err(search_page(&raw).into()),
+	};
+	match evaluate::evaluate_all(&viewspec, database).await {
+		Ok(ids) => Ok((raw, ids)),
+		Err(evaluate::Error::Sqlx(sql_error)) => Err(error::Sqlx(sql_error).into()),
+		Err(evaluate::Error::User(_)) => Err(search_page(&raw).into()),
+	}
+}
+
+#[derive(serde::Deserialize)]
+pub struct Query {
+	#[serde(rename = "search")]
+	viewspec: ViewSpecOrError,
+}
+
+async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Query(Query { viewspec }): extract::Query<Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	let (query, ids) = matching_files(viewspec, database).await?;
+	let tags_by_category = get_tags_by_category(database, None)
+		.await
+		.map_err(error::Sqlx)?;
+	Ok(
+		Template {
+			self_user,
+			query,
+			matching: ids.len(),
+			tags_by_category,
+			stage: Stage::Choose,
+		}
+		.into_response(),
+	)
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "preview")]
+	Preview {
+		search: String,
+		add: Vec<models::TagId>,
+		remove: Vec<models::TagId>,
+	},
+	#[multipart(rename = "apply")]
+	Apply {
+		search: String,
+		add: Vec<models::TagId>,
+		remove: Vec<models::TagId>,
+	},
+}
+
+fn check_tags(add: &[models::TagId], remove: &[models::TagId]) -> Result<(), error::BadRequest> {
+	if add.is_empty() && remove.is_empty() {
+		Err(error::BadRequest("choose tags to add or remove".into()))
+	} else if add.iter().any(|tag| remove.contains(tag)) {
+		Err(error::BadRequest(
+			"the same tag cannot be both added and removed".into(),
+		))
+	} else {
+		Ok(())
+	}
+}
+
+/// The tags of the files, as pairs of file and tag.
+async fn file_tags(
+	ids: &[models::FileId],
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<HashSet<(models::FileId, models::TagId)>> {
+	Ok(
+		sqlx::query!("SELECT file, tag FROM file_tags WHERE file = ANY($1)", ids)
+			.fetch_all(&mut *transaction)
+			.await?
+			.into_iter()
+			.map(|record| (record.file, record.tag))
+			.collect(),
+	)
+}
+
+/// Adds and removes the tags, then adds what the files' tags imply, which puts back removed tags that are implied by tags the files still have, since implications always hold.
+///
+/// Returns how many files changed and which, by comparing their tags before and after.
+async fn change_tags(
+	ids: &[models::FileId],
+	add: &[models::TagId],
+	remove: &[models::TagId],
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(Preview, Vec<models::FileId>), ErrorResponse> {
+	let before = file_tags(ids, transaction).await.map_err(error::Sqlx)?;
+	sqlx::query!(
+		"DELETE FROM file_tags WHERE file = ANY($1) AND tag = ANY($2)",
+		ids,
+		remove,
+	)
+	.execute(&mut *transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	sqlx::query!(
+		"INSERT INTO file_tags (file, tag) SELECT file, tag FROM unnest($1::bigint[]) AS file CROSS JOIN unnest($2::int[]) AS tag ON CONFLICT (file, tag) DO NOTHING",
+		ids,
+		add,
+	)
+	.execute(&mut *transaction)
+	.await
+	.map_err(|sql_error| match sql_error {
+		// a foreign key violation, from a tag not existing
+		sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23503") => {
+			ErrorResponse::from(error::EntityNotFound("tag"))
+		}
+		sql_error => error::Sqlx(sql_error).into(),
+	})?;
+	implications::apply(Some(ids), &mut *transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	let after = file_tags(ids, transaction).await.map_err(error::Sqlx)?;
+
+	let files = |changes: HashSet<&(models::FileId, models::TagId)>| {
+		changes
+			.into_iter()
+			.map(|&(file, _tag)| file)
+			.collect::<HashSet<_>>()
+	};
+	let gaining = files(after.difference(&before).collect());
+	let losing = files(before.difference(&after).collect());
+	let changed = gaining.union(&losing).copied().collect::<Vec<_>>();
+	let preview = Preview {
+		gaining: gaining.len(),
+		losing: losing.len(),
+		changing: changed.len(),
+	};
+	Ok((preview, changed))
+}
+
+/// Makes the changes without keeping them.
+async fn preview(
+	ids: &[models::FileId],
+	add: &[models::TagId],
+	remove: &[models::TagId],
+	database: &Database,
+) -> Result<Preview, ErrorResponse> {
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let (preview, _changed) = change_tags(ids, add, remove, &mut transaction).await?;
+	transaction.rollback().await.map_err(error::Sqlx)?;
+	Ok(preview)
+}
+
+/// Returns the viewspec, the number of matching files and the number of files that changed.
+async fn apply(
+	search: String,
+	add: &[models::TagId],
+	remove: &[models::TagId],
+	database: &Database,
+) -> Result<(String, usize, usize), ErrorResponse> {
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let (query, ids) = matching_files(ViewSpecOrError::new(search), &mut transaction).await?;
+	let (_preview, changed) = change_tags(&ids, add, remove, &mut transaction).await?;
+	sqlx::query!(
+		"UPDATE files SET modified_time = CURRENT_TIMESTAMP WHERE id = ANY($1)",
+		&changed,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	tag_categories::check_exclusive(&ids, &mut transaction)
+		.await
+		.map_err(error::Sqlx)??;
+	transaction.commit().await.map_err(error::Sqlx)?;
+	Ok((query, ids.len(), changed.len()))
+}
+
+async fn post_handler(
+	auth::Editor(self_user): auth::Editor,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	let (query, matching, stage) = match req {
+		PostRequest::Preview {
+			search,
+			add,
+			remove,
+		} => {
+			check_tags(&add, &remove)?;
+			let (query, ids) = matching_files(ViewSpecOrError::new(search), database).await?;
+			let preview = preview(&ids, &add, &remove, database).await?;
+			(
+				query,
+				ids.len(),
+				Stage::Preview {
+					add,
+					remove,
+					preview,
+				},
+			)
+		}
+		PostRequest::Apply {
+			search,
+			add,
+			remove,
+		} => {
+			check_tags(&add, &remove)?;
+			let (query, matching, changed) = apply(search, &add, &remove, database).await?;
+			(query, matching, Stage::Applied { changed })
+		}
+	};
+
+	let tags_by_category = get_tags_by_category(database, None)
+		.await
+		.map_err(error::Sqlx)?;
+	Ok(
+		Template {
+			self_user,
+			query,
+			matching,
+			tags_by_category,
+			stage,
+		}
+		.into_response(),
+	)
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler).post(post_handler));
+
+	router
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, collections, set_none_if_empty};
+
+struct File {
+	id: models::FileId,
+	name: String,
+	in_trash: bool,
+}
+
+#[derive(askama::Template)]
+#[template(path = "collections/page.html")]
+struct Template {
+	self_user: models::User,
+	collection: models::Collection,
+	/// The username of the creator.
+	created_by: Option<String>,
+	/// In order.
+	files: Vec<File>,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((collection_id,)): extract::Path<(models::CollectionId,)>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	// only editors can see files in the trash, and they need to in order to reorder the collection
+	let show_trashed = self_user.role >= models::UserRole::Editor;
+
+	let collection = models::Collection::by_id(database, collection_id)
+		.await
+		.map_err(error::Sqlx)?
+		.ok_or(error::EntityNotFound("collection"))?;
+	let created_by = match collection.created_by {
+		Some(user_id) => models::User::by_id(database, user_id)
+			.await
+			.map_err(error::Sqlx)?
+			.map(|user| user.username),
+		None => None,
+	};
+	let files = sqlx::query_as!(
+		File,
+		r#"SELECT files.id, files.name, files.deleted_time IS NOT NULL as "in_trash!" FROM collection_files INNER JOIN files ON collection_files.file = files.id WHERE collection_files.collection = $1 AND (files.deleted_time IS NULL OR $2) ORDER BY collection_files.position"#,
+		collection_id,
+		show_trashed,
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(
+		Template {
+			self_user,
+			collection,
+			created_by,
+			files,
+		}
+		.into_response(),
+	)
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "update")]
+	Update {
+		name: String,
+		description: Option<String>,
+	},
+	/// `position` counts up from 1, like the positions shown on the page.
+	#[multipart(rename = "move")]
+	Move { file: models::FileId, position: i64 },
+	#[multipart(rename = "remove")]
+	Remove { file: models::FileId },
+	#[multipart(rename = "delete")]
+	Delete {},
+}
+
+async fn post_handler(
+	auth::Editor(_self_user): auth::Editor,
+	extract::Path((collection_id,)): extract::Path<(models::CollectionId,)>,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	match req {
+		PostRequest::Update {
+			name,
+			mut description,
+		} => {
+			set_none_if_empty(&mut description);
+			let q_result = sqlx::query!(
+				"UPDATE collections SET name = $2, description = $3 WHERE id = $1",
+				collection_id,
+				name,
+				description,
+			)
+			.execute(database)
+			.await
+			.map_err(error::Sqlx)?;
+			if q_result.rows_affected() == 0 {
+				return Err(error::EntityNotFound("collection").into());
+			}
+		}
+		PostRequest::Move { file, position } => {
+			// positions before the first are treated as the first
+			let index = usize::try_from(position.saturating_sub(1)).unwrap_or(0);
+			if !collections::move_file(collection_id, file, index, database)
+				.await
+				.map_err(error::Sqlx)?
+			{
+				return Err(error::EntityNotFound("file in collection").into());
+			}
+		}
+		PostRequest::Remove { file } => {
+			let q_result = sqlx::query!(
+				"DELETE FROM collection_files WHERE collection = $1 AND file = $2",
+				collection_id,
+				file,
+			)
+			.execute(database)
+			.await
+			.map_err(error::Sqlx)?;
+			if q_result.rows_affected() == 0 {
+				return Err(error::EntityNotFound("file in collection").into());
+			}
+		}
+		PostRequest::Delete {} => {
+			let q_result = sqlx::query!("DELETE FROM collections WHERE id = $1", collection_id)
+				.execute(database)
+				.await
+				.map_err(error::Sqlx)?;
+			if q_result.rows_affected() == 0 {
+				return Err(error::EntityNotFound("collection").into());
+			}
+			return Ok(Redirect::to("/collections").into_response());
+		}
+	}
+
+	Ok(Redirect::to(&format!("/collections/{collection_id}")).into_response())
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler).post(post_handler));
+
+	router
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, collections, pagination, set_none_if_empty};
+use crate::timestamp::Timestamp;
+
+mod id;
+
+struct Collection {
+	id: models::CollectionId,
+	name: String,
+	description: Option<String>,
+	/// Including files in the trash.
+	num_files: i64,
+	created_time: Timestamp,
+	/// `None` if the user was deleted.
+	created_by: Option<String>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "collections/index.html")]
+struct Template {
+	self_user: models::User,
+	collections: Vec<Collection>,
+	pagination: pagination::Template,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Query(pagination): extract::Query<pagination::Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	let count = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM collections"#)
+		.fetch_one(database)
+		.await
+		.map_err(error::Sqlx)?;
+	let page_size = pagination.page_size();
+	let num_pages = std::cmp::max((count + page_size - 1) / page_size, 1);
+
+	if pagination.page() >= num_pages {
+		return Err(error::EntityNotFound("page").into());
+	}
+
+	let collections = sqlx::query_as!(
+		Collection,
+		r#"SELECT collections.id, collections.name, collections.description, (SELECT count(*) FROM collection_files WHERE collection = collections.id) as "num_files!", collections.created_time as "created_time: Timestamp", users.username as "created_by?" FROM collections LEFT JOIN users ON collections.created_by = users.id ORDER BY collections.name OFFSET $1 LIMIT $2"#,
+		pagination.offset(),
+		pagination.limit(),
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(
+		Template {
+			self_user,
+			collections,
+			pagination: pagination::Template::from_query(pagination, num_pages),
+		}
+		.into_response(),
+	)
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "create")]
+	Create {
+		name: String,
+		description: Option<String>,
+	},
+	/// Sent from the page of the file.
+	#[multipart(rename = "add")]
+	Add {
+		collection: models::CollectionId,
+		file: models::FileId,
+	},
+}
+
+async fn post_handler(
+	auth::Editor(self_user): auth::Editor,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	match req {
+		PostRequest::Create {
+			name,
+			mut description,
+		} => {
+			use ormx::Insert as _;
+
+			set_none_if_empty(&mut description);
+			let collection = models::collection::Create {
+				name,
+				description,
+				created_by: Some(self_user.id),
+			}
+			.insert(database)
+			.await
+			.map_err(error::Sqlx)?;
+			Ok(Redirect::to(&format!("/collections/{}", collection.id)).into_response())
+		}
+		PostRequest::Add { collection, file } => {
+			sqlx::query!("SELECT id FROM files WHERE id = $1", file)
+				.fetch_optional(database)
+				.await
+				.map_err(error::Sqlx)?
+				.ok_or(error::EntityNotFound("file"))?;
+			if !collections::add(collection, file, database)
+				.await
+				.map_err(error::Sqlx)?
+			{
+				return Err(error::EntityNotFound("collection").into());
+			}
+			Ok(Redirect::to(&format!("/files/{file}#collections")).into_response())
+		}
+	}
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler).post(post_handler));
+	router = router.nest("/:collection_id", id::configure());
+
+	router
+}
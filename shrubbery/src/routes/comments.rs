@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, pagination};
+use crate::timestamp::Timestamp;
+
+struct RecentComment {
+	id: models::FileCommentId,
+	file: models::FileId,
+	file_name: String,
+	body: String,
+	created_time: Timestamp,
+	/// `None` if the user was deleted.
+	author: Option<String>,
+	edited_time: Option<Timestamp>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "comments.html")]
+struct Template {
+	self_user: models::User,
+	/// Newest first.
+	comments: Vec<RecentComment>,
+	pagination: pagination::Template,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Query(pagination): extract::Query<pagination::Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let database = &*database;
+	// comments on files in the trash are only shown to those who can see the files
+	let show_trashed = self_user.role >= models::UserRole::Editor;
+
+	let count = sqlx::query_scalar!(
+		r#"SELECT count(*) as "count!" FROM file_comments INNER JOIN files ON file_comments.file = files.id WHERE files.deleted_time IS NULL OR $1"#,
+		show_trashed,
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let page_size = pagination.page_size();
+	let num_pages = std::cmp::max((count + page_size - 1) / page_size, 1);
+
+	if pagination.page() >= num_pages {
+		return Err(error::EntityNotFound("page").into());
+	}
+
+	let comments = sqlx::query_as!(
+		RecentComment,
+		r#"SELECT file_comments.id, file_comments.file, files.name as file_name, file_comments.body, file_comments.created_time as "created_time: Timestamp", users.username as "author?", file_comments.edited_time as "edited_time: Timestamp" FROM file_comments INNER JOIN files ON file_comments.file = files.id LEFT JOIN users ON file_comments.created_by = users.id WHERE files.deleted_time IS NULL OR $1 ORDER BY file_comments.created_time DESC, file_comments.id DESC OFFSET $2 LIMIT $3"#,
+		show_trashed,
+		pagination.offset(),
+		pagination.limit(),
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(Template {
+		self_user,
+		comments,
+		pagination: pagination::Template::from_query(pagination, num_pages),
+	})
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler));
+
+	router
+}
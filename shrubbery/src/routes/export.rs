@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+use std::io;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use axum::body::{Bytes, StreamBody};
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+use futures::channel::mpsc;
+use futures::{SinkExt as _, StreamExt as _};
+use http::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use viewspec::parse::Ast;
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::viewspec::{evaluate, ViewSpecOrError};
+use crate::helpers::{auth, percent, serve_file, tag_text, zip_stream};
+use crate::storage::{Key, Storage};
+
+/// How many matching files are looked up at a time.
+const PAGE_SIZE: i64 = 1000;
+/// How many chunks are buffered before the export waits for the client to catch up.
+const BUFFERED_CHUNKS: usize = 16;
+const MANIFEST_JSON: &str = "manifest.json";
+const MANIFEST_CSV: &str = "manifest.csv";
+
+#[derive(serde::Deserialize)]
+pub struct Query {
+	#[serde(rename = "search")]
+	viewspec: ViewSpecOrError,
+}
+
+#[derive(serde::Serialize)]
+struct ManifestEntry {
+	id: models::FileId,
+	/// The path of the file in the archive.
+	path: String,
+	name: String,
+	description: Option<String>,
+	media_type: String,
+	mime_type: Option<String>,
+	/// Formatted as `category:name`, or just `name` for tags without a category, like import sidecars. See [`tag_text`].
+	tags: Vec<String>,
+}
+
+struct StoredFile {
+	/// The path of the file in the archive.
+	name: String,
+	key: Key,
+	modified: zip_stream::DosTime,
+}
+
+/// Makes `name` unique among `used` by adding a number before the extension. Names are compared case-insensitively since some filesystems do so.
+fn unique_name(used: &mut HashSet<String>, name: &str) -> String {
+	let (stem, extension) = match name.rsplit_once('.') {
+		Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+		_ => (name, None),
+	};
+	let mut candidate = name.to_owned();
+	let mut number = 2;
+	while !used.insert(candidate.to_lowercase()) {
+		candidate = match extension {
+			Some(extension) => format!("{stem} ({number}).{extension}"),
+			None => format!("{stem} ({number})"),
+		};
+		number += 1;
+	}
+	candidate
+}
+
+fn push_csv_field(buffer: &mut String, value: &str) {
+	if value.contains([',', '"', '\r', '\n']) {
+		buffer.push('"');
+		buffer.push_str(&value.replace('"', "\"\""));
+		buffer.push('"');
+	} else {
+		buffer.push_str(value);
+	}
+}
+
+/// Tags are separated by newlines, since unlike commas and spaces they cannot appear in tag names.
+fn manifest_csv(entries: &[ManifestEntry]) -> String {
+	let mut buffer = String::from("id,path,name,description,media_type,mime_type,tags\r\n");
+	for entry in entries {
+		let fields = [
+			&entry.id.to_string(),
+			&entry.path,
+			&entry.name,
+			entry.description.as_deref().unwrap_or(""),
+			&entry.media_type,
+			entry.mime_type.as_deref().unwrap_or(""),
+			&entry.tags.join("\n"),
+		];
+		for (index, field) in fields.into_iter().enumerate() {
+			if index > 0 {
+				buffer.push(',');
+			}
+			push_csv_field(&mut buffer, field);
+		}
+		buffer.push_str("\r\n");
+	}
+	buffer
+}
+
+enum WriteError {
+	Io(io::Error),
+	Disconnected,
+}
+
+impl From<io::Error> for WriteError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl From<mpsc::SendError> for WriteError {
+	fn from(_: mpsc::SendError) -> Self {
+		Self::Disconnected
+	}
+}
+
+async fn write_archive(
+	manifest: [(&'static str, Bytes); 2],
+	files: Vec<StoredFile>,
+	storage: &dyn Storage,
+	sender: &mut mpsc::Sender<io::Result<Bytes>>,
+) -> Result<(), WriteError> {
+	let mut writer = zip_stream::Writer::new();
+
+	// the manifest goes first so it is available even if the export is interrupted
+	let now = zip_stream::DosTime::from(SystemTime::now());
+	for (name, data) in manifest {
+		for part in writer.entry(name.to_owned(), now, data) {
+			sender.send(Ok(part)).await?;
+		}
+	}
+
+	for file in files {
+		let mut chunks = storage.get(file.key, None).await?;
+		sender
+			.send(Ok(writer.start_entry(file.name, file.modified)))
+			.await?;
+		while let Some(chunk) = chunks.next().await {
+			let chunk = chunk?;
+			writer.data(&chunk);
+			sender.send(Ok(chunk)).await?;
+		}
+		sender.send(Ok(writer.finish_entry())).await?;
+	}
+
+	sender.send(Ok(writer.finish())).await?;
+	Ok(())
+}
+
+/// Evaluates the viewspec a page at a time to find all of the matching files.
+async fn matching_files(
+	viewspec: &Ast,
+	database: &Database,
+) -> Result<Vec<models::FileId>, evaluate::Error> {
+	let mut ids = Vec::new();
+	loop {
+		let page = evaluate::evaluate(
+			viewspec,
+			database,
+			ids.last().copied(),
+			PAGE_SIZE,
+			evaluate::Sort::Id,
+		)
+		.await?;
+		let done = i64::try_from(page.len()).unwrap_or(i64::MAX) < PAGE_SIZE;
+		ids.extend(page.into_iter().map(|item| item.id));
+		if done {
+			return Ok(ids);
+		}
+	}
+}
+
+async fn get_handler(
+	auth::Auth(_self_user): auth::Auth,
+	extract::Query(Query { viewspec }): extract::Query<Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+) -> Result<Response, ErrorResponse> {
+	// the search page explains what is wrong with the viewspec
+	let search_page = |raw: &str| {
+		Redirect::to(&format!("/?search={}", percent::encode(raw.as_bytes()))).into_response()
+	};
+	let (raw, viewspec) = match viewspec {
+		ViewSpecOrError {
+			raw,
+			parsed: Ok(viewspec),
+		} => (raw, viewspec),
+		ViewSpecOrError {
+			raw,
+			parsed: Err(_),
+		} => return Ok(search_page(&raw)),
+	};
+
+	let ids = match matching_files(&viewspec, &database).await {
+		Ok(ids) => ids,
+		Err(evaluate::Error::Sqlx(sql_error)) => return Err(error::Sqlx(sql_error).into()),
+		Err(evaluate::Error::User(_)) => return Ok(search_page(&raw)),
+	};
+
+	let records = sqlx::query!(
+		r#"SELECT files.id, files.name, files.description, files.media_type as "media_type: models::MediaType", files.mime_type, ARRAY(SELECT tag_categories.name FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE file_tags.file = files.id ORDER BY tag_categories.name NULLS FIRST, tags.name) as "tag_categories!: Vec<Option<String>>", ARRAY(SELECT tags.name FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE file_tags.file = files.id ORDER BY tag_categories.name NULLS FIRST, tags.name) as "tag_names!" FROM files WHERE files.id = ANY($1) ORDER BY files.id"#,
+		&ids,
+	)
+	.fetch_all(&*database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	let mut used_names: HashSet<String> = [MANIFEST_JSON, MANIFEST_CSV]
+		.into_iter()
+		.map(str::to_owned)
+		.collect();
+	let mut manifest = Vec::with_capacity(records.len());
+	let mut files = Vec::with_capacity(records.len());
+	for record in records {
+		let key = Key::File(record.id);
+		let metadata = match storage.metadata(key).await {
+			Ok(Some(metadata)) => metadata,
+			Ok(None) => {
+				tracing::warn!(
+					"not exporting file {} since it is missing from storage",
+					record.id
+				);
+				continue;
+			}
+			Err(error) => return Err(error::Io("reading file metadata", error).into()),
+		};
+		let name = unique_name(
+			&mut used_names,
+			&serve_file::download_name(&record.name, record.mime_type.as_deref()),
+		);
+		files.push(StoredFile {
+			name: name.clone(),
+			key,
+			modified: metadata
+				.modified
+				.map_or(zip_stream::DosTime::MIN, zip_stream::DosTime::from),
+		});
+		manifest.push(ManifestEntry {
+			id: record.id,
+			path: name,
+			name: record.name,
+			description: record.description,
+			media_type: record.media_type.to_string(),
+			mime_type: record.mime_type,
+			tags: record
+				.tag_categories
+				.iter()
+				.zip(&record.tag_names)
+				.map(|(category, name)| tag_text::format(category.as_deref(), name))
+				.collect(),
+		});
+	}
+
+	let manifest_json = serde_json::to_vec_pretty(&manifest).expect("manifest is serializable");
+	let manifest = [
+		(MANIFEST_JSON, manifest_json.into()),
+		(MANIFEST_CSV, manifest_csv(&manifest).into()),
+	];
+	let (mut sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+	tokio::spawn(async move {
+		match write_archive(manifest, files, &*storage, &mut sender).await {
+			Ok(()) | Err(WriteError::Disconnected) => {}
+			Err(WriteError::Io(error)) => {
+				tracing::warn!("export failed: {error}");
+				// aborts the response so the client does not mistake the archive for being complete
+				let _ = sender.send(Err(error)).await;
+			}
+		}
+	});
+
+	Ok(
+		(
+			[
+				(CONTENT_TYPE, HeaderValue::from_static("application/zip")),
+				(
+					CONTENT_DISPOSITION,
+					HeaderValue::from_static("attachment; filename=\"export.zip\""),
+				),
+			],
+			StreamBody::new(receiver),
+		)
+			.into_response(),
+	)
+}
+
+pub fn configure() -> Router {
+	let mut app = Router::new();
+	app = app.route("/", axum::routing::get(get_handler));
+	app
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashSet;
+
+	use super::{manifest_csv, unique_name, ManifestEntry};
+
+	#[test]
+	fn unique_names() {
+		let mut used = HashSet::from(["manifest.json".to_owned()]);
+		let mut unique = |name: &str| unique_name(&mut used, name);
+		assert_eq!(unique("cat.png"), "cat.png");
+		assert_eq!(unique("Cat.PNG"), "Cat (2).PNG");
+		assert_eq!(unique("cat.png"), "cat (3).png");
+		assert_eq!(unique("manifest.json"), "manifest (2).json");
+		assert_eq!(unique(".hidden"), ".hidden");
+		assert_eq!(unique(".hidden"), ".hidden (2)");
+		assert_eq!(unique("notes"), "notes");
+		assert_eq!(unique("notes"), "notes (2)");
+	}
+
+	#[test]
+	fn csv() {
+		let entries = [ManifestEntry {
+			id: 1,
+			path: "cat.png".into(),
+			name: "cat".into(),
+			description: Some("a \"fluffy\", orange cat".into()),
+			media_type: "image".into(),
+			mime_type: Some("image/png".into()),
+			tags: vec!["animal:cat".into(), "orange".into()],
+		}];
+		assert_eq!(
+			manifest_csv(&entries),
+			"id,path,name,description,media_type,mime_type,tags\r\n1,cat.png,cat,\"a \"\"fluffy\"\", orange cat\",image,image/png,\"animal:cat\norange\"\r\n"
+		);
+	}
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, pagination};
+use crate::timestamp::Timestamp;
+
+struct Favorite {
+	id: models::FileId,
+	name: String,
+	favorited_time: Timestamp,
+	/// The user's own rating of the file.
+	rating: Option<i16>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "favorites.html")]
+struct Template {
+	self_user: models::User,
+	/// Most recently favorited first.
+	favorites: Vec<Favorite>,
+	pagination: pagination::Template,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Query(pagination): extract::Query<pagination::Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let database = &*database;
+	// files that were favorited and then moved to the trash are only shown to those who can see them
+	let show_trashed = self_user.role >= models::UserRole::Editor;
+
+	let count = sqlx::query_scalar!(
+		r#"SELECT count(*) as "count!" FROM file_favorites INNER JOIN files ON file_favorites.file = files.id WHERE file_favorites.favorited_by = $1 AND (files.deleted_time IS NULL OR $2)"#,
+		self_user.id,
+		show_trashed,
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let page_size = pagination.page_size();
+	let num_pages = std::cmp::max((count + page_size - 1) / page_size, 1);
+
+	if pagination.page() >= num_pages {
+		return Err(error::EntityNotFound("page").into());
+	}
+
+	let favorites = sqlx::query_as!(
+		Favorite,
+		r#"SELECT files.id, files.name, file_favorites.favorited_time as "favorited_time: Timestamp", file_ratings.rating as "rating?" FROM file_favorites INNER JOIN files ON file_favorites.file = files.id LEFT JOIN file_ratings ON file_ratings.file = files.id AND file_ratings.rated_by = file_favorites.favorited_by WHERE file_favorites.favorited_by = $1 AND (files.deleted_time IS NULL OR $2) ORDER BY file_favorites.favorited_time DESC, files.id DESC OFFSET $3 LIMIT $4"#,
+		self_user.id,
+		show_trashed,
+		pagination.offset(),
+		pagination.limit(),
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(Template {
+		self_user,
+		favorites,
+		pagination: pagination::Template::from_query(pagination, num_pages),
+	})
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler));
+
+	router
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::auth;
+use crate::timestamp::Timestamp;
+
+pub struct Comment {
+	pub id: models::FileCommentId,
+	pub body: String,
+	pub created_time: Timestamp,
+	/// `None` if the user was deleted.
+	pub created_by: Option<models::UserId>,
+	/// `None` if the user was deleted.
+	pub author: Option<String>,
+	pub edited_time: Option<Timestamp>,
+}
+
+/// Only the author can edit a comment. Not even admins can, since the comment would still be attributed to its author.
+fn can_edit(created_by: Option<models::UserId>, user: &models::User) -> bool {
+	created_by == Some(user.id)
+}
+
+/// Admins can delete any comment, to moderate the discussion.
+fn can_delete(created_by: Option<models::UserId>, user: &models::User) -> bool {
+	can_edit(created_by, user) || user.role >= models::UserRole::Admin
+}
+
+impl Comment {
+	pub fn can_edit(&self, user: &models::User) -> bool {
+		can_edit(self.created_by, user)
+	}
+
+	pub fn can_delete(&self, user: &models::User) -> bool {
+		can_delete(self.created_by, user)
+	}
+}
+
+/// Oldest first, so the discussion reads from top to bottom.
+pub async fn for_file(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	file_id: models::FileId,
+) -> sqlx::Result<Vec<Comment>> {
+	sqlx::query_as!(
+		Comment,
+		r#"SELECT file_comments.id, body, file_comments.created_time as "created_time: Timestamp", created_by, users.username as "author?", edited_time as "edited_time: Timestamp" FROM file_comments LEFT JOIN users ON file_comments.created_by = users.id WHERE file = $1 ORDER BY file_comments.created_time, file_comments.id"#,
+		file_id,
+	)
+	.fetch_all(database)
+	.await
+}
+
+/// Rejects bodies that are only whitespace, which would otherwise show up as empty comments.
+fn check_body(body: &str) -> Result<(), error::BadRequest> {
+	if body.trim().is_empty() {
+		Err(error::BadRequest("comments cannot be empty".into()))
+	} else {
+		Ok(())
+	}
+}
+
+fn redirect_to_comment(file_id: models::FileId, comment_id: models::FileCommentId) -> Response {
+	Redirect::to(&format!("/files/{file_id}#comment-{comment_id}")).into_response()
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+struct CreateRequest {
+	body: String,
+}
+
+async fn create_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((file_id,)): extract::Path<(models::FileId,)>,
+	axum_easy_multipart::Extractor(CreateRequest { body }): axum_easy_multipart::Extractor<
+		CreateRequest,
+	>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	check_body(&body)?;
+	// as with viewing, only editors can see files in the trash
+	let show_trashed = self_user.role >= models::UserRole::Editor;
+	let comment_id = sqlx::query_scalar!(
+		"INSERT INTO file_comments (file, body, created_by) SELECT id, $2, $3 FROM files WHERE id = $1 AND (deleted_time IS NULL OR $4) RETURNING id",
+		file_id,
+		body,
+		self_user.id,
+		show_trashed,
+	)
+	.fetch_optional(&*database)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("file"))?;
+	Ok(redirect_to_comment(file_id, comment_id))
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "edit")]
+	Edit { body: String },
+	#[multipart(rename = "delete")]
+	Delete {},
+}
+
+async fn post_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((file_id, comment_id)): extract::Path<(models::FileId, models::FileCommentId)>,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	let created_by = sqlx::query_scalar!(
+		"SELECT created_by FROM file_comments WHERE id = $1 AND file = $2",
+		comment_id,
+		file_id,
+	)
+	.fetch_optional(database)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("comment"))?;
+
+	match req {
+		PostRequest::Edit { body } => {
+			if !can_edit(created_by, &self_user) {
+				return Err(auth::Error::Forbidden.into());
+			}
+			check_body(&body)?;
+			sqlx::query!(
+				"UPDATE file_comments SET body = $2, edited_time = CURRENT_TIMESTAMP WHERE id = $1",
+				comment_id,
+				body,
+			)
+			.execute(database)
+			.await
+			.map_err(error::Sqlx)?;
+			Ok(redirect_to_comment(file_id, comment_id))
+		}
+		PostRequest::Delete {} => {
+			if !can_delete(created_by, &self_user) {
+				return Err(auth::Error::Forbidden.into());
+			}
+			sqlx::query!("DELETE FROM file_comments WHERE id = $1", comment_id)
+				.execute(database)
+				.await
+				.map_err(error::Sqlx)?;
+			Ok(Redirect::to(&format!("/files/{file_id}#comments")).into_response())
+		}
+	}
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/comments", axum::routing::post(create_handler));
+	router = router.route("/comments/:comment_id", axum::routing::post(post_handler));
+
+	router
+}
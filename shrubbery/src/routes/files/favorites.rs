@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::auth;
+
+/// How users feel about a file, both overall and the user viewing it.
+pub struct Summary {
+	pub favorites: i64,
+	pub favorited: bool,
+	pub ratings: i64,
+	/// `None` if there are no ratings.
+	pub average_rating: Option<f64>,
+	pub own_rating: Option<i16>,
+}
+
+impl Summary {
+	pub fn display_average_rating(&self) -> Option<String> {
+		self.average_rating.map(|average| format!("{average:.1}"))
+	}
+}
+
+pub async fn summary(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	file_id: models::FileId,
+	user_id: models::UserId,
+) -> sqlx::Result<Summary> {
+	sqlx::query_as!(
+		Summary,
+		r#"SELECT (SELECT count(*) FROM file_favorites WHERE file = $1) as "favorites!", EXISTS (SELECT 1 FROM file_favorites WHERE file = $1 AND favorited_by = $2) as "favorited!", (SELECT count(*) FROM file_ratings WHERE file = $1) as "ratings!", (SELECT avg(rating)::double precision FROM file_ratings WHERE file = $1) as average_rating, (SELECT rating FROM file_ratings WHERE file = $1 AND rated_by = $2) as own_rating"#,
+		file_id,
+		user_id,
+	)
+	.fetch_one(database)
+	.await
+}
+
+/// Any user can favorite and rate the files they can see, and only editors can see files in the trash.
+async fn check_visible(
+	self_user: &models::User,
+	file_id: models::FileId,
+	database: &Database,
+) -> Result<(), ErrorResponse> {
+	let show_trashed = self_user.role >= models::UserRole::Editor;
+	sqlx::query!(
+		"SELECT id FROM files WHERE id = $1 AND (deleted_time IS NULL OR $2)",
+		file_id,
+		show_trashed,
+	)
+	.fetch_optional(database)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("file"))?;
+	Ok(())
+}
+
+fn redirect_to_file(file_id: models::FileId) -> Response {
+	Redirect::to(&format!("/files/{file_id}#feedback")).into_response()
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum FavoriteRequest {
+	#[multipart(rename = "add")]
+	Add {},
+	#[multipart(rename = "remove")]
+	Remove {},
+}
+
+async fn favorite_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((file_id,)): extract::Path<(models::FileId,)>,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<FavoriteRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	check_visible(&self_user, file_id, database).await?;
+
+	match req {
+		FavoriteRequest::Add {} => sqlx::query!(
+			"INSERT INTO file_favorites (file, favorited_by) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+			file_id,
+			self_user.id,
+		),
+		FavoriteRequest::Remove {} => sqlx::query!(
+			"DELETE FROM file_favorites WHERE file = $1 AND favorited_by = $2",
+			file_id,
+			self_user.id,
+		),
+	}
+	.execute(database)
+	.await
+	.map_err(error::Sqlx)?;
+	Ok(redirect_to_file(file_id))
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum RatingRequest {
+	#[multipart(rename = "set")]
+	Set { rating: i16 },
+	#[multipart(rename = "clear")]
+	Clear {},
+}
+
+async fn rating_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((file_id,)): extract::Path<(models::FileId,)>,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<RatingRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	check_visible(&self_user, file_id, database).await?;
+
+	match req {
+		RatingRequest::Set { rating } => {
+			if !(1..=5).contains(&rating) {
+				return Err(error::BadRequest("ratings must be from 1 to 5".into()).into());
+			}
+			sqlx::query!(
+				"INSERT INTO file_ratings (file, rated_by, rating) VALUES ($1, $2, $3) ON CONFLICT (file, rated_by) DO UPDATE SET rating = EXCLUDED.rating, rated_time = CURRENT_TIMESTAMP",
+				file_id,
+				self_user.id,
+				rating,
+			)
+		}
+		RatingRequest::Clear {} => sqlx::query!(
+			"DELETE FROM file_ratings WHERE file = $1 AND rated_by = $2",
+			file_id,
+			self_user.id,
+		),
+	}
+	.execute(database)
+	.await
+	.map_err(error::Sqlx)?;
+	Ok(redirect_to_file(file_id))
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/favorite", axum::routing::post(favorite_handler));
+	router = router.route("/rating", axum::routing::post(rating_handler));
+
+	router
+}
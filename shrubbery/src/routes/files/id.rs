@@ -1,4 +1,3 @@
-use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
@@ -8,7 +7,12 @@ use crate::config::Config;
 use crate::database::models::media_type::MediaType as FileMediaType;
 use crate::database::{models, Database};
 use crate::error;
-use crate::helpers::auth;
+use crate::helpers::{
+	auth, collections, implications, metadata, perceptual_hash, quota, serve_file, sniff,
+	tag_categories, tag_text, versions,
+};
+use crate::storage::{self, Key, Storage};
+use crate::timestamp::Timestamp;
 
 #[derive(Clone, Copy)]
 enum Action {
@@ -16,6 +20,7 @@ enum Action {
 	Replaced,
 	Updated,
 	UpdatedTags,
+	RestoredVersion,
 }
 impl Action {
 	fn as_message(self) -> &'static str {
@@ -24,11 +29,72 @@ impl Action {
 			Self::Replaced => "Replaced",
 			Self::Updated => "Updated",
 			Self::UpdatedTags => "Updated tags for",
+			Self::RestoredVersion => "Restored a previous version of",
 		}
 	}
 }
 
-type TagsByCategory = BTreeMap<Option<String>, Vec<(models::TagId, String, bool)>>;
+/// The tags in one category, or the tags without a category.
+pub struct TagGroup {
+	/// The names of the category's ancestors and then its own name, or empty for tags without a category.
+	pub path: Vec<String>,
+	/// The boolean is whether the tag is present on the file.
+	pub tags: Vec<(models::TagId, String, bool)>,
+}
+
+impl TagGroup {
+	/// `None` for tags without a category.
+	pub fn category(&self) -> Option<&str> {
+		self.path.last().map(String::as_str)
+	}
+
+	/// The category with its ancestors, like `media › film › director`, since option groups can't be nested.
+	pub fn label(&self) -> String {
+		if self.path.is_empty() {
+			"(no category)".to_owned()
+		} else {
+			self.path.join(" › ")
+		}
+	}
+}
+
+/// Tags without a category come first, then each category right before its subcategories.
+pub type TagsByCategory = Vec<TagGroup>;
+
+/// With a `file_id` of `None`, all tags are listed as not present.
+pub async fn get_tags_by_category(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	file_id: Option<models::FileId>,
+) -> sqlx::Result<TagsByCategory> {
+	use futures::TryStreamExt as _;
+
+	let mut ret: TagsByCategory = Vec::new();
+	let mut stream = sqlx::query!(r#"SELECT tags.id, tags.name, tag_category_tree.path as "path?", (SELECT count(*) > 0 FROM file_tags WHERE tag = tags.id AND file = $1) as "present!" FROM tags LEFT JOIN tag_category_tree ON tags.category = tag_category_tree.id ORDER BY tag_category_tree.path NULLS FIRST, tags.name"#, file_id).fetch(database);
+	while let Some(record) = stream.try_next().await? {
+		let path = record.path.unwrap_or_default();
+		let group = match ret.last_mut() {
+			Some(group) if group.path == path => group,
+			_ => {
+				ret.push(TagGroup {
+					path,
+					tags: Vec::new(),
+				});
+				ret.last_mut().unwrap()
+			}
+		};
+		group.tags.push((record.id, record.name, record.present));
+	}
+	Ok(ret)
+}
+
+struct Version {
+	number: i32,
+	mime_type: Option<String>,
+	size: Option<i64>,
+	/// `None` if the user was deleted.
+	replaced_by: Option<String>,
+	replaced_time: Timestamp,
+}
 
 #[derive(askama::Template)]
 #[template(path = "files/page.html")]
@@ -36,71 +102,156 @@ struct Template {
 	self_user: models::User,
 	file: models::File,
 	action: Option<Action>,
+	/// The username of the uploader.
+	uploaded_by: Option<String>,
 	tags_by_category: TagsByCategory,
+	/// The present tags, one per line. See [`tag_text`].
+	tags_text: String,
+	/// The required tag categories that the file has no tags in.
+	missing_categories: Vec<String>,
+	/// Newest first.
+	versions: Vec<Version>,
+	feedback: super::favorites::Summary,
+	comments: Vec<super::comments::Comment>,
+	/// Ordered by the collections' names.
+	memberships: Vec<collections::Membership>,
+	/// Every collection, to choose one to add the file to. Empty unless the user is an editor.
+	all_collections: Vec<(models::CollectionId, String)>,
 }
 crate::helpers::impl_into_response!(Template);
 
-impl Template {
-	async fn get_tags_by_category(
-		database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
-		file_id: models::FileId,
-	) -> sqlx::Result<TagsByCategory> {
-		use futures::TryStreamExt as _;
-
-		let mut ret: TagsByCategory = BTreeMap::new();
-		let mut stream = sqlx::query!(r#"SELECT tags.id, tags.name, tag_categories.name as "category?", (SELECT count(*) > 0 FROM file_tags WHERE tag = tags.id AND file = $1) as "present!" FROM tags LEFT JOIN tag_categories ON tags.category = tag_categories.id ORDER BY category NULLS FIRST, name"#, file_id).fetch(database);
-		while let Some(record) = stream.try_next().await? {
-			let tags = ret.entry(record.category).or_insert(Vec::new());
-			tags.push((record.id, record.name, record.present));
+async fn render_page(
+	self_user: models::User,
+	file: models::File,
+	action: Option<Action>,
+	database: &Database,
+) -> Result<Response, ErrorResponse> {
+	let tags_by_category = get_tags_by_category(database, Some(file.id))
+		.await
+		.map_err(error::Sqlx)?;
+	let tags_text = tags_by_category
+		.iter()
+		.flat_map(|group| {
+			group
+				.tags
+				.iter()
+				.filter(|(_id, _name, present)| *present)
+				.map(|(_id, name, _present)| tag_text::format(group.category(), name))
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+	let missing_categories = tag_categories::missing_required(file.id, database)
+		.await
+		.map_err(error::Sqlx)?;
+	let uploaded_by = match file.uploaded_by {
+		Some(user_id) => models::User::by_id(database, user_id)
+			.await
+			.map_err(error::Sqlx)?
+			.map(|user| user.username),
+		None => None,
+	};
+	let versions = sqlx::query_as!(
+		Version,
+		r#"SELECT number, mime_type, size, users.username as "replaced_by?", replaced_time as "replaced_time: Timestamp" FROM file_versions LEFT JOIN users ON file_versions.replaced_by = users.id WHERE file = $1 ORDER BY number DESC"#,
+		file.id,
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let feedback = super::favorites::summary(database, file.id, self_user.id)
+		.await
+		.map_err(error::Sqlx)?;
+	let comments = super::comments::for_file(database, file.id)
+		.await
+		.map_err(error::Sqlx)?;
+	let is_editor = self_user.role >= models::UserRole::Editor;
+	let memberships = collections::memberships(database, file.id, is_editor)
+		.await
+		.map_err(error::Sqlx)?;
+	let all_collections = if is_editor {
+		sqlx::query!("SELECT id, name FROM collections ORDER BY name")
+			.fetch_all(database)
+			.await
+			.map_err(error::Sqlx)?
+			.into_iter()
+			.map(|record| (record.id, record.name))
+			.collect()
+	} else {
+		Vec::new()
+	};
+	Ok(
+		Template {
+			self_user,
+			file,
+			action,
+			uploaded_by,
+			tags_by_category,
+			tags_text,
+			missing_categories,
+			versions,
+			feedback,
+			comments,
+			memberships,
+			all_collections,
 		}
-		Ok(ret)
-	}
+		.into_response(),
+	)
 }
 
 #[derive(serde::Deserialize)]
 pub struct Query {
 	pub direct: Option<String>,
+	pub download: Option<String>,
 	pub created: Option<String>,
 }
 
 pub async fn get_handler(
 	auth::Auth(self_user): auth::Auth,
 	extract::Path((file_id,)): extract::Path<(models::FileId,)>,
-	extract::Query(Query { direct, created }): extract::Query<Query>,
+	extract::Query(Query {
+		direct,
+		download,
+		created,
+	}): extract::Query<Query>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
-	extract::Extension(config): extract::Extension<Arc<Config>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
 	req_parts: http::request::Parts,
 ) -> Result<Response, ErrorResponse> {
 	let database = &*database;
+	// only editors can see files in the trash, so they can decide whether to restore them
+	let show_trashed = self_user.role >= models::UserRole::Editor;
 
 	if direct.is_some() {
-		use tower::Service as _;
-		let fs_path = config.file_storage.join(file_id.to_string());
-		let mut service = tower_http::services::ServeFile::new(fs_path);
-		let request = http::Request::from_parts(req_parts, ());
-		let response = service.call(request).await;
-		let mut response = response.map_err(|err| error::Io("serving file directly", err))?;
-		response.headers_mut().remove("Content-Type");
-		Ok(response.map(|body| {
-			use http_body::Body as _;
-			body.map_err(axum::Error::new).boxed_unsync()
-		}))
+		let record = sqlx::query!(
+			"SELECT name, mime_type FROM files WHERE id = $1 AND (deleted_time IS NULL OR $2)",
+			file_id,
+			show_trashed,
+		)
+		.fetch_optional(database)
+		.await
+		.map_err(error::Sqlx)?
+		.ok_or(error::EntityNotFound("file"))?;
+		let options = serve_file::Options {
+			name: &record.name,
+			mime_type: record.mime_type.as_deref(),
+			disposition: if download.is_some() {
+				serve_file::Disposition::Attachment
+			} else {
+				serve_file::Disposition::Inline
+			},
+		};
+		Ok(
+			serve_file::serve(&storage, Key::File(file_id), &req_parts.headers, options)
+				.await
+				.map_err(|err| error::Io("serving file directly", err))?,
+		)
 	} else {
 		let file = models::File::by_id(database, file_id)
 			.await
 			.map_err(error::Sqlx)?
+			.filter(|file| file.deleted_time.is_none() || show_trashed)
 			.ok_or(error::EntityNotFound("file"))?;
-		Ok(
-			Template {
-				self_user,
-				file,
-				action: created.map(|_| Action::Created),
-				tags_by_category: Template::get_tags_by_category(database, file_id)
-					.await
-					.map_err(error::Sqlx)?,
-			}
-			.into_response(),
-		)
+		render_page(self_user, file, created.map(|_| Action::Created), database).await
 	}
 }
 
@@ -114,6 +265,10 @@ impl axum_easy_multipart::file::MakeTempfile for MakeTempfile {
 	fn tempfile(&self) -> std::io::Result<tempfile::NamedTempFile> {
 		tempfile::Builder::new().tempfile_in(&self.0.file_storage)
 	}
+
+	fn max_size(&self) -> Option<u64> {
+		self.0.upload_limits.file_size
+	}
 }
 
 #[derive(Debug, axum_easy_multipart::FromMultipart)]
@@ -129,24 +284,34 @@ pub enum PostRequest {
 	Update {
 		name: String,
 		description: Option<String>,
-		media_type: models::MediaType,
 	},
 	#[multipart(rename = "update-tags")]
 	UpdateTags { tags: Vec<models::TagId> },
+	/// The tags as text, one per line. See [`tag_text`].
+	#[multipart(rename = "update-tags-text")]
+	UpdateTagsText {
+		/// Present if tags that don't exist should be created.
+		create: Option<String>,
+		text: String,
+	},
+	#[multipart(rename = "restore-version")]
+	RestoreVersion { number: i32 },
 }
 
+/// Moves the file to the trash.
 async fn post_delete_handler(
+	self_user: &models::User,
 	file_id: models::FileId,
-	config: &Config,
 	database: &sqlx::Pool<sqlx::Postgres>,
 ) -> Result<Response, ErrorResponse> {
-	tokio::fs::remove_file(config.file_storage.join(file_id.to_string()))
-		.await
-		.map_err(|err| error::Io("deleting file", err))?;
-	let q_result = sqlx::query!("DELETE FROM files WHERE id = $1", file_id)
-		.execute(database)
-		.await
-		.map_err(error::Sqlx)?;
+	let q_result = sqlx::query!(
+		"UPDATE files SET deleted_time = CURRENT_TIMESTAMP, deleted_by = $2 WHERE id = $1 AND deleted_time IS NULL",
+		file_id,
+		self_user.id,
+	)
+	.execute(database)
+	.await
+	.map_err(error::Sqlx)?;
 	if q_result.rows_affected() == 0 {
 		Err(error::EntityNotFound("file").into())
 	} else {
@@ -154,117 +319,273 @@ async fn post_delete_handler(
 	}
 }
 
-async fn post_replace_handler(
-	self_user: models::User,
+/// Make `temp_path`, which has already been checked to be in `format`, the content of the file. The previous content is kept as a new version.
+async fn replace_content(
+	self_user: &models::User,
 	file_id: models::FileId,
-	temp_file: axum_easy_multipart::file::File<impl axum_easy_multipart::file::MakeTempfile>,
+	temp_path: tempfile::TempPath,
+	format: sniff::Format,
 	config: &Config,
 	database: &sqlx::Pool<sqlx::Postgres>,
-) -> Result<Response, ErrorResponse> {
-	temp_file
-		.temp_path
-		.persist(config.file_storage.join(format!("{file_id}")))
-		.map_err(|error| error::Io("replacing file in filesystem", error.error))?;
-	let media_type = temp_file
-		.content_type
-		.as_ref()
-		.and_then(models::MediaType::from_mime)
-		.ok_or(error::BadContentType)?;
+	storage: &dyn Storage,
+) -> Result<models::File, ErrorResponse> {
+	let media_type = format.media_type;
+	let perceptual_hash = perceptual_hash::hash_file(temp_path.to_path_buf(), media_type).await;
+	let metadata = metadata::extract(temp_path.to_path_buf(), media_type).await;
+
+	let size = tokio::fs::metadata(&temp_path)
+		.await
+		.map_err(|error| error::Io("getting size of replacement", error))?
+		.len();
+
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	// locking the file gives concurrent replacements distinct version numbers
+	let uploaded_by = sqlx::query_scalar!(
+		"SELECT uploaded_by FROM files WHERE id = $1 AND deleted_time IS NULL FOR UPDATE",
+		file_id
+	)
+	.fetch_optional(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("file"))?;
+	// the replaced content is kept as a version, so the new content is added to the uploader's usage
+	if let Some(uploader) = uploaded_by {
+		let (limits, usage) = quota::lock(uploader, config, &mut transaction)
+			.await
+			.map_err(error::Sqlx)?;
+		quota::check(&limits, &usage, i64::try_from(size).unwrap_or(i64::MAX), 0)?;
+	}
+	let version_id = sqlx::query_scalar!(
+		"INSERT INTO file_versions (file, number, media_type, mime_type, size, replaced_by) SELECT id, (SELECT COALESCE(MAX(number), 0) + 1 FROM file_versions WHERE file = $1), media_type, mime_type, size, $2 FROM files WHERE id = $1 RETURNING id",
+		file_id,
+		self_user.id,
+	)
+	.fetch_one(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
 	let file = sqlx::query_as!(
 		models::File,
-		r#"UPDATE files SET media_type = $2 WHERE id = $1 RETURNING id, name, description, media_type as "media_type: models::MediaType""#,
+		r#"UPDATE files SET media_type = $2, mime_type = $3, perceptual_hash = $4, size = $5, width = $6, height = $7, duration = $8, capture_time = $9, camera_model = $10, orientation = $11, modified_time = CURRENT_TIMESTAMP WHERE id = $1 RETURNING id, name, description, media_type as "media_type: models::MediaType", mime_type, perceptual_hash, size, width, height, duration, capture_time, camera_model, orientation, deleted_time as "deleted_time: Timestamp", uploaded_by, uploaded_time as "uploaded_time: Timestamp", modified_time as "modified_time: Timestamp""#,
 		file_id,
 		media_type as _,
+		format.mime_type,
+		perceptual_hash,
+		metadata.size,
+		metadata.width,
+		metadata.height,
+		metadata.duration,
+		metadata.capture_time,
+		metadata.camera_model,
+		metadata.orientation,
 	)
-		.fetch_optional(database)
-		.await
-		.map_err(error::Sqlx)?.ok_or_else(|| {
-			tracing::warn!("file with ID {file_id} existed in filesystem but not in database");
-			error::EntityNotFound("file")
-		})?;
-	Ok(
-		Template {
-			self_user,
-			file,
-			action: Some(Action::Replaced),
-			tags_by_category: Template::get_tags_by_category(database, file_id)
+	.fetch_one(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+
+	let key = Key::File(file_id);
+	let version_key = Key::Version(version_id);
+	let kept_previous = match storage.rename(key, version_key).await {
+		Ok(()) => true,
+		// there is nothing to keep, but replacing the file fixes it
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+			tracing::warn!("file with ID {file_id} existed in database but not in storage");
+			sqlx::query!("DELETE FROM file_versions WHERE id = $1", version_id)
+				.execute(&mut transaction)
 				.await
-				.map_err(error::Sqlx)?,
+				.map_err(error::Sqlx)?;
+			false
 		}
-		.into_response(),
+		Err(error) => return Err(error::Io("keeping previous version", error).into()),
+	};
+	let restore_previous = || async {
+		if kept_previous {
+			if let Err(error) = storage.rename(version_key, key).await {
+				tracing::error!(
+					"could not move previous version of file {file_id} back into place: {error}"
+				);
+			}
+		}
+	};
+	if let Err(error) = storage.put(key, temp_path).await {
+		restore_previous().await;
+		return Err(error::Io("replacing file in storage", error).into());
+	}
+	if let Err(error) = transaction.commit().await {
+		restore_previous().await;
+		return Err(error::Sqlx(error).into());
+	}
+
+	if let Err(error) = versions::prune(Some(file_id), config, database, storage).await {
+		tracing::warn!("could not prune versions of file {file_id}: {error}");
+	}
+
+	Ok(file)
+}
+
+async fn post_replace_handler(
+	self_user: models::User,
+	file_id: models::FileId,
+	temp_file: axum_easy_multipart::file::File<impl axum_easy_multipart::file::MakeTempfile>,
+	config: &Config,
+	database: &sqlx::Pool<sqlx::Postgres>,
+	storage: &dyn Storage,
+) -> Result<Response, ErrorResponse> {
+	let format = sniff::check_upload(
+		temp_file.temp_path.to_path_buf(),
+		temp_file.content_type.as_ref(),
+	)
+	.await?;
+	let file = replace_content(
+		&self_user,
+		file_id,
+		temp_file.temp_path,
+		format,
+		config,
+		database,
+		storage,
 	)
+	.await?;
+	render_page(self_user, file, Some(Action::Replaced), database).await
 }
 
-async fn post_update_handler(
+/// Restoring replaces the file with a copy of the version, so the content being replaced becomes a version itself and the restoration can be undone.
+async fn post_restore_version_handler(
 	self_user: models::User,
 	file_id: models::FileId,
-	name: String,
-	mut description: Option<String>,
-	media_type: models::MediaType,
+	number: i32,
+	config: &Config,
 	database: &sqlx::Pool<sqlx::Postgres>,
+	storage: &dyn Storage,
 ) -> Result<Response, ErrorResponse> {
-	crate::helpers::set_none_if_empty(&mut description);
-	let file = sqlx::query_as!(
-		models::File,
-		r#"UPDATE files SET name = $2, description = $3, media_type = $4 WHERE id = $1 RETURNING id, name, description, media_type as "media_type: models::MediaType""#,
+	let version_id = sqlx::query_scalar!(
+		"SELECT id FROM file_versions WHERE file = $1 AND number = $2",
 		file_id,
-		name,
-		description,
-		media_type as _
+		number,
 	)
 	.fetch_optional(database)
-	.await.map_err(error::Sqlx)?.ok_or(error::EntityNotFound("file"))?;
-	Ok(
-		Template {
-			self_user,
-			file,
-			action: Some(Action::Updated),
-			tags_by_category: Template::get_tags_by_category(database, file_id)
-				.await
-				.map_err(error::Sqlx)?,
-		}
-		.into_response(),
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("version"))?;
+
+	let temp_path = storage::copy_to_temp(storage, Key::Version(version_id), &config.file_storage)
+		.await
+		.map_err(|error| error::Io("copying version", error))?;
+	let format = sniff::check_upload(temp_path.to_path_buf(), None).await?;
+	let file = replace_content(
+		&self_user, file_id, temp_path, format, config, database, storage,
 	)
+	.await?;
+	render_page(self_user, file, Some(Action::RestoredVersion), database).await
 }
 
-async fn post_update_tags_handler(
+async fn post_update_handler(
 	self_user: models::User,
 	file_id: models::FileId,
-	tags: Vec<models::TagId>,
+	name: String,
+	mut description: Option<String>,
 	database: &sqlx::Pool<sqlx::Postgres>,
 ) -> Result<Response, ErrorResponse> {
-	// get it now to return early if it doesn't exist
-	let file = models::File::by_id(database, file_id)
+	crate::helpers::set_none_if_empty(&mut description);
+	let file = update_details(file_id, &name, description.as_deref(), database)
 		.await
 		.map_err(error::Sqlx)?
 		.ok_or(error::EntityNotFound("file"))?;
+	render_page(self_user, file, Some(Action::Updated), database).await
+}
 
-	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+/// Returns `None` if the file doesn't exist or is in the trash.
+async fn update_details(
+	file_id: models::FileId,
+	name: &str,
+	description: Option<&str>,
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+) -> sqlx::Result<Option<models::File>> {
+	sqlx::query_as!(
+		models::File,
+		r#"UPDATE files SET name = $2, description = $3, modified_time = CURRENT_TIMESTAMP WHERE id = $1 AND deleted_time IS NULL RETURNING id, name, description, media_type as "media_type: models::MediaType", mime_type, perceptual_hash, size, width, height, duration, capture_time, camera_model, orientation, deleted_time as "deleted_time: Timestamp", uploaded_by, uploaded_time as "uploaded_time: Timestamp", modified_time as "modified_time: Timestamp""#,
+		file_id,
+		name,
+		description,
+	)
+	.fetch_optional(database)
+	.await
+}
+
+/// Replaces the tags of the file, adding what they imply. Returns the file, or an error if it doesn't exist or is in the trash.
+async fn replace_tags(
+	file_id: models::FileId,
+	tags: &[models::TagId],
+	transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<models::File, ErrorResponse> {
+	// do it first to return early if the file doesn't exist
+	let file = sqlx::query_as!(
+		models::File,
+		r#"UPDATE files SET modified_time = CURRENT_TIMESTAMP WHERE id = $1 AND deleted_time IS NULL RETURNING id, name, description, media_type as "media_type: models::MediaType", mime_type, perceptual_hash, size, width, height, duration, capture_time, camera_model, orientation, deleted_time as "deleted_time: Timestamp", uploaded_by, uploaded_time as "uploaded_time: Timestamp", modified_time as "modified_time: Timestamp""#,
+		file_id,
+	)
+	.fetch_optional(&mut *transaction)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("file"))?;
 	sqlx::query!("DELETE FROM file_tags WHERE file = $1", file_id)
-		.execute(&mut transaction)
+		.execute(&mut *transaction)
 		.await
 		.map_err(error::Sqlx)?;
 	sqlx::query!(
 		"INSERT INTO file_tags (file, tag) (SELECT $1 as file, unnest as tag FROM unnest(cast($2 as int[])))",
 		file_id,
-		&tags
+		tags
 	)
-	.execute(&mut transaction)
+	.execute(&mut *transaction)
 	.await
 	.map_err(error::Sqlx)?;
+	implications::apply(Some(std::slice::from_ref(&file_id)), &mut *transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	tag_categories::check_exclusive(std::slice::from_ref(&file_id), &mut *transaction)
+		.await
+		.map_err(error::Sqlx)??;
+	Ok(file)
+}
+
+async fn post_update_tags_handler(
+	self_user: models::User,
+	file_id: models::FileId,
+	tags: Vec<models::TagId>,
+	database: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Response, ErrorResponse> {
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let file = replace_tags(file_id, &tags, &mut transaction).await?;
 	transaction.commit().await.map_err(error::Sqlx)?;
 
-	Ok(
-		Template {
-			self_user,
-			file,
-			action: Some(Action::UpdatedTags),
-			tags_by_category: Template::get_tags_by_category(database, file_id)
-				.await
-				.map_err(error::Sqlx)?,
+	render_page(self_user, file, Some(Action::UpdatedTags), database).await
+}
+
+async fn post_update_tags_text_handler(
+	self_user: models::User,
+	file_id: models::FileId,
+	text: &str,
+	create: bool,
+	database: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Response, ErrorResponse> {
+	let entries = tag_text::parse(text)?;
+	let created_by = if create {
+		if self_user.role < models::UserRole::Editor {
+			return Err(auth::Error::Forbidden.into());
 		}
-		.into_response(),
-	)
+		Some(self_user.id)
+	} else {
+		None
+	};
+
+	// created tags are rolled back along with everything else if the tags can't be saved
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let mut tags = Vec::with_capacity(entries.len());
+	tag_text::resolve(&entries, created_by, &mut tags, &mut transaction).await?;
+	let file = replace_tags(file_id, &tags, &mut transaction).await?;
+	transaction.commit().await.map_err(error::Sqlx)?;
+
+	render_page(self_user, file, Some(Action::UpdatedTags), database).await
 }
 
 pub async fn post_handler(
@@ -273,22 +594,28 @@ pub async fn post_handler(
 	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
 	extract::Extension(config): extract::Extension<Arc<Config>>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
 ) -> Result<Response, ErrorResponse> {
 	let database = &*database;
+	let storage = &*storage;
 
 	match req {
-		PostRequest::Delete {} => post_delete_handler(file_id, &config, database).await,
+		PostRequest::Delete {} => post_delete_handler(&self_user, file_id, database).await,
 		PostRequest::Replace { file: temp_file } => {
-			post_replace_handler(self_user, file_id, temp_file, &config, database).await
+			post_replace_handler(self_user, file_id, temp_file, &config, database, storage).await
+		}
+		PostRequest::Update { name, description } => {
+			post_update_handler(self_user, file_id, name, description, database).await
 		}
-		PostRequest::Update {
-			name,
-			description,
-			media_type,
-		} => post_update_handler(self_user, file_id, name, description, media_type, database).await,
 		PostRequest::UpdateTags { tags } => {
 			post_update_tags_handler(self_user, file_id, tags, database).await
 		}
+		PostRequest::UpdateTagsText { create, text } => {
+			post_update_tags_text_handler(self_user, file_id, &text, create.is_some(), database).await
+		}
+		PostRequest::RestoreVersion { number } => {
+			post_restore_version_handler(self_user, file_id, number, &config, database, storage).await
+		}
 	}
 }
 
@@ -299,3 +626,46 @@ pub fn configure() -> Router {
 
 	router
 }
+
+#[cfg(test)]
+mod test {
+	/// Needs a database with the migrations applied, so it is skipped unless `DATABASE_URL` is set.
+	#[tokio::test]
+	async fn trashed_file_is_not_updated() {
+		let Ok(url) = std::env::var("DATABASE_URL") else {
+			return;
+		};
+		let database = sqlx::PgPool::connect(&url).await.unwrap();
+		let mut transaction = database.begin().await.unwrap();
+		let user = sqlx::query_scalar!(
+			"INSERT INTO users (username, password) VALUES ('trashed_file_is_not_updated', '') RETURNING id"
+		)
+		.fetch_one(&mut transaction)
+		.await
+		.unwrap();
+		let file = sqlx::query_scalar!(
+			"INSERT INTO files (name, media_type, uploaded_by, deleted_time, deleted_by) VALUES ('trashed', 'image', $1, CURRENT_TIMESTAMP, $1) RETURNING id",
+			user,
+		)
+		.fetch_one(&mut transaction)
+		.await
+		.unwrap();
+
+		let updated = super::update_details(file, "renamed", None, &mut transaction)
+			.await
+			.unwrap();
+		assert!(updated.is_none());
+		assert!(super::replace_tags(file, &[], &mut transaction)
+			.await
+			.is_err());
+
+		sqlx::query!("UPDATE files SET deleted_time = NULL WHERE id = $1", file)
+			.execute(&mut transaction)
+			.await
+			.unwrap();
+		let updated = super::update_details(file, "renamed", None, &mut transaction)
+			.await
+			.unwrap();
+		assert_eq!(updated.unwrap().name, "renamed");
+	}
+}
@@ -1,11 +1,22 @@
 use axum::Router;
 
+mod comments;
+mod favorites;
 pub mod id;
+mod similar;
+mod versions;
 
 pub fn configure() -> Router {
 	let mut app = Router::new();
 
-	app = app.nest("/:file_id", id::configure());
+	app = app.nest(
+		"/:file_id",
+		id::configure()
+			.merge(comments::configure())
+			.merge(favorites::configure())
+			.merge(similar::configure())
+			.merge(versions::configure()),
+	);
 
 	app
 }
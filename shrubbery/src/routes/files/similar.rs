@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse};
+use axum::{extract, Router};
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::auth;
+
+const MAX_RESULTS: i64 = 50;
+
+struct SimilarFile {
+	id: models::FileId,
+	name: String,
+	distance: i64,
+}
+
+#[derive(askama::Template)]
+#[template(path = "files/similar.html")]
+struct Template {
+	self_user: models::User,
+	file: models::File,
+	/// `None` if the file has no perceptual hash.
+	similar: Option<Vec<SimilarFile>>,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Auth(self_user): auth::Auth,
+	extract::Path((file_id,)): extract::Path<(models::FileId,)>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let database = &*database;
+
+	let file = models::File::by_id(database, file_id)
+		.await
+		.map_err(error::Sqlx)?
+		.ok_or(error::EntityNotFound("file"))?;
+
+	let similar = match file.perceptual_hash {
+		Some(hash) => Some(
+			sqlx::query_as!(
+				SimilarFile,
+				r#"SELECT id, name, bit_count((perceptual_hash # $2)::bit(64)) as "distance!" FROM files WHERE id <> $1 AND deleted_time IS NULL AND bit_count((perceptual_hash # $2)::bit(64)) <= $3 ORDER BY 3, id LIMIT $4"#,
+				file_id,
+				hash,
+				i64::from(config.similarity_threshold),
+				MAX_RESULTS,
+			)
+			.fetch_all(database)
+			.await
+			.map_err(error::Sqlx)?,
+		),
+		None => None,
+	};
+
+	Ok(Template {
+		self_user,
+		file,
+		similar,
+	})
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/similar", axum::routing::get(get_handler));
+
+	router
+}
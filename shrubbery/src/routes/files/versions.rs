@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, serve_file};
+use crate::storage::{Key, Storage};
+
+#[derive(serde::Deserialize)]
+pub struct Query {
+	pub download: Option<String>,
+}
+
+async fn get_handler(
+	auth::Auth(_self_user): auth::Auth,
+	extract::Path((file_id, number)): extract::Path<(models::FileId, i32)>,
+	extract::Query(Query { download }): extract::Query<Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+	req_parts: http::request::Parts,
+) -> Result<Response, ErrorResponse> {
+	let record = sqlx::query!(
+		"SELECT file_versions.id, files.name, file_versions.mime_type FROM file_versions INNER JOIN files ON file_versions.file = files.id WHERE file = $1 AND number = $2",
+		file_id,
+		number,
+	)
+	.fetch_optional(&*database)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("version"))?;
+
+	let name = format!("{} (version {number})", record.name);
+	let options = serve_file::Options {
+		name: &name,
+		mime_type: record.mime_type.as_deref(),
+		disposition: if download.is_some() {
+			serve_file::Disposition::Attachment
+		} else {
+			serve_file::Disposition::Inline
+		},
+	};
+	Ok(
+		serve_file::serve(
+			&storage,
+			Key::Version(record.id),
+			&req_parts.headers,
+			options,
+		)
+		.await
+		.map_err(|err| error::Io("serving version", err))?,
+	)
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/versions/:number", axum::routing::get(get_handler));
+
+	router
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Response};
+use axum::{extract, Router};
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{archive, auth, quota, tag_text};
+use crate::routes::files::id::{get_tags_by_category, MakeTempfile, TagsByCategory};
+use crate::routes::upload::{create_file, NewFile};
+use crate::storage::Storage;
+
+#[derive(askama::Template)]
+#[template(path = "files/import.html")]
+struct Template {
+	self_user: models::User,
+	tags_by_category: TagsByCategory,
+}
+crate::helpers::impl_into_response!(Template);
+
+enum Outcome {
+	Imported {
+		file_id: models::FileId,
+		num_tags: usize,
+	},
+	Skipped(&'static str),
+	Failed(String),
+}
+
+struct ReportEntry {
+	path: String,
+	outcome: Outcome,
+	/// Problems with the sidecar that did not prevent the file from being imported.
+	warnings: Vec<String>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "files/import_report.html")]
+struct ReportTemplate {
+	self_user: models::User,
+	entries: Vec<ReportEntry>,
+}
+crate::helpers::impl_into_response!(ReportTemplate);
+
+impl ReportTemplate {
+	fn num_imported(&self) -> usize {
+		self
+			.entries
+			.iter()
+			.filter(|entry| matches!(entry.outcome, Outcome::Imported { .. }))
+			.count()
+	}
+}
+
+async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	Ok(
+		Template {
+			self_user,
+			tags_by_category: get_tags_by_category(&*database, None)
+				.await
+				.map_err(error::Sqlx)?,
+		}
+		.into_response(),
+	)
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+struct PostRequest {
+	description: Option<String>,
+	/// Applied to every file, in addition to the tags from its sidecar.
+	tags: Vec<models::TagId>,
+	archive: axum_easy_multipart::file::File<MakeArchiveTempfile>,
+}
+
+/// Unlike [`MakeTempfile`], there is no size limit, since the limit is for each file in the archive and applies to them as they are extracted. The editor's quota still limits how large the archive can be.
+struct MakeArchiveTempfile(MakeTempfile);
+
+impl axum_easy_multipart::file::MakeTempfile for MakeArchiveTempfile {
+	fn extract_from_extensions(extensions: &http::Extensions) -> Self {
+		Self(MakeTempfile::extract_from_extensions(extensions))
+	}
+
+	fn tempfile(&self) -> std::io::Result<tempfile::NamedTempFile> {
+		self.0.tempfile()
+	}
+}
+
+/// Parses a sidecar, which has one tag per line in the syntax of [`tag_text`]: `category:name`, or just `name` for tags without a category, quoted if necessary.
+///
+/// Blank lines and lines starting with `#` are ignored. Lines that aren't a tag are returned as errors, so that the rest of the tags can still be added.
+fn parse_sidecar(text: &str) -> Vec<Result<tag_text::Entry, &str>> {
+	text
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| {
+			tag_text::parse(line)
+				.ok()
+				.and_then(|entries| entries.into_iter().next())
+				.ok_or(line)
+		})
+		.collect()
+}
+
+/// Finds tags by category and name, creating them if necessary. Tag categories are not created since they need more information than a sidecar has.
+struct TagResolver<'a> {
+	database: &'a Database,
+	created_by: models::UserId,
+	categories: HashMap<String, Option<models::TagCategoryId>>,
+	tags: HashMap<(Option<models::TagCategoryId>, String), models::TagId>,
+}
+
+impl TagResolver<'_> {
+	/// Returns `Ok(None)` if the category does not exist.
+	async fn category(&mut self, name: &str) -> sqlx::Result<Option<models::TagCategoryId>> {
+		if let Some(&id) = self.categories.get(name) {
+			return Ok(id);
+		}
+		let id = sqlx::query_scalar!("SELECT id FROM tag_categories WHERE name = $1", name)
+			.fetch_optional(self.database)
+			.await?;
+		self.categories.insert(name.to_owned(), id);
+		Ok(id)
+	}
+
+	async fn tag(
+		&mut self,
+		category: Option<models::TagCategoryId>,
+		name: &str,
+	) -> sqlx::Result<models::TagId> {
+		let key = (category, name.to_owned());
+		if let Some(&id) = self.tags.get(&key) {
+			return Ok(id);
+		}
+		// an alias is used rather than creating a tag with its name
+		let existing = sqlx::query_scalar!(
+			"SELECT COALESCE((SELECT id FROM tags WHERE name = $1 AND category IS NOT DISTINCT FROM $2), (SELECT tag FROM tag_aliases WHERE name = $1 AND category IS NOT DISTINCT FROM $2)) as id",
+			name,
+			category,
+		)
+		.fetch_one(self.database)
+		.await?;
+		let id = match existing {
+			Some(id) => id,
+			None => {
+				sqlx::query_scalar!(
+					"INSERT INTO tags (name, category, created_by) VALUES ($1, $2, $3) RETURNING id",
+					name,
+					category,
+					self.created_by,
+				)
+				.fetch_one(self.database)
+				.await?
+			}
+		};
+		self.tags.insert(key, id);
+		Ok(id)
+	}
+
+	/// Resolves the tags in a sidecar, adding them to `tags`. Tags that cannot be resolved are reported in `warnings`.
+	async fn resolve_sidecar(
+		&mut self,
+		sidecar: &str,
+		tags: &mut Vec<models::TagId>,
+		warnings: &mut Vec<String>,
+	) -> sqlx::Result<()> {
+		for entry in parse_sidecar(sidecar) {
+			let entry = match entry {
+				Ok(entry) => entry,
+				Err(line) => {
+					warnings.push(format!(
+						"{line:?} was not added because it is not a tag like name or category:name"
+					));
+					continue;
+				}
+			};
+			let category = match &entry.category {
+				Some(category_name) => self.category(category_name).await?,
+				None => None,
+			};
+			if let (Some(_), None) = (&entry.category, category) {
+				warnings.push(format!(
+					"tag {} was not added because the category does not exist",
+					tag_text::format(entry.category.as_deref(), &entry.name)
+				));
+				continue;
+			}
+			let tag = self.tag(category, &entry.name).await?;
+			if !tags.contains(&tag) {
+				tags.push(tag);
+			}
+		}
+		Ok(())
+	}
+}
+
+async fn post_handler(
+	quota::Uploader(self_user): quota::Uploader,
+	axum_easy_multipart::Extractor(mut req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	crate::helpers::set_none_if_empty(&mut req.description);
+
+	// the quota is checked again for each file, but this keeps the archive from being extracted far past it
+	let usage = quota::usage(self_user.id, database)
+		.await
+		.map_err(error::Sqlx)?;
+	let limits = archive::Limits {
+		file_size: config.upload_limits.file_size,
+		total: quota::Limits::for_user(&self_user, &config)
+			.bytes
+			.map(|limit| u64::try_from(limit.saturating_sub(usage.bytes)).unwrap_or(0)),
+	};
+	let archive_path = req.archive.temp_path;
+	let file_storage = config.file_storage.clone();
+	let contents = tokio::task::spawn_blocking(move || {
+		archive::read(&archive_path, limits, || {
+			tempfile::Builder::new().tempfile_in(&file_storage)
+		})
+	})
+	.await
+	.map_err(|error| error::Io("reading archive", error.into()))?
+	.map_err(|error| match error {
+		archive::Error::Io(error) => ErrorResponse::from(error::Io("reading archive", error)),
+		error => error::BadRequest(error.to_string().into()).into(),
+	})?;
+	let mut texts = contents.texts;
+
+	let mut resolver = TagResolver {
+		database,
+		created_by: self_user.id,
+		categories: HashMap::new(),
+		tags: HashMap::new(),
+	};
+	let mut entries = Vec::with_capacity(contents.media.len() + contents.skipped.len());
+	for entry in contents.media {
+		let mut tags = req.tags.clone();
+		let mut warnings = Vec::new();
+		let outcome = match texts.remove(&format!("{}.txt", entry.path)) {
+			Some(sidecar) => resolver
+				.resolve_sidecar(&sidecar, &mut tags, &mut warnings)
+				.await
+				.map_err(|error| error::Sqlx(error).to_string()),
+			None => Ok(()),
+		};
+		let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+		let outcome = match outcome {
+			Ok(()) => match create_file(
+				NewFile {
+					name,
+					description: req.description.as_deref(),
+					tags: &tags,
+					temp_path: entry.temp_path,
+					declared: None,
+				},
+				self_user.id,
+				&config,
+				database,
+				&*storage,
+			)
+			.await
+			{
+				Ok(file_id) => Outcome::Imported {
+					file_id,
+					num_tags: tags.len(),
+				},
+				Err(error) => Outcome::Failed(error.to_string()),
+			},
+			Err(error) => Outcome::Failed(error),
+		};
+		entries.push(ReportEntry {
+			path: entry.path,
+			outcome,
+			warnings,
+		});
+	}
+	entries.extend(
+		contents
+			.skipped
+			.into_iter()
+			.map(|(path, reason)| (path, Outcome::Skipped(reason)))
+			.chain(
+				texts
+					.into_keys()
+					.map(|path| (path, Outcome::Skipped("no file for this sidecar"))),
+			)
+			.map(|(path, outcome)| ReportEntry {
+				path,
+				outcome,
+				warnings: Vec::new(),
+			}),
+	);
+
+	Ok(ReportTemplate { self_user, entries }.into_response())
+}
+
+pub fn configure() -> Router {
+	let mut app = Router::new();
+	app = app.route("/", axum::routing::get(get_handler).post(post_handler));
+	app
+}
+
+#[cfg(test)]
+mod test {
+	#[test]
+	fn parse_sidecar() {
+		use crate::helpers::tag_text::Entry;
+
+		let entry = |category: Option<&str>, name: &str| {
+			Ok(Entry {
+				category: category.map(str::to_owned),
+				name: name.to_owned(),
+			})
+		};
+		assert_eq!(
+			super::parse_sidecar(
+				"animal:cat\n\n# a comment\n  outdoors \ncolor: orange\n\"a: b\":\"big cat\"\nempty:\n"
+			),
+			[
+				entry(Some("animal"), "cat"),
+				entry(None, "outdoors"),
+				entry(Some("color"), "orange"),
+				entry(Some("a: b"), "big cat"),
+				Err("empty:"),
+			]
+		);
+	}
+
+	/// Tags in the export manifest are added back by importing the manifest's lines as a sidecar.
+	#[test]
+	fn sidecar_reads_exported_tags() {
+		use crate::helpers::tag_text;
+
+		let tags = [
+			(None, "big cat"),
+			(Some("a:b"), "c d"),
+			(Some("animal"), "cat"),
+		];
+		let sidecar = tags
+			.iter()
+			.map(|&(category, name)| tag_text::format(category, name))
+			.collect::<Vec<_>>()
+			.join("\n");
+		let parsed = super::parse_sidecar(&sidecar)
+			.into_iter()
+			.map(|entry| {
+				let entry = entry.unwrap();
+				(entry.category, entry.name)
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			parsed,
+			tags.map(|(category, name)| (category.map(str::to_owned), name.to_owned()))
+		);
+	}
+}
@@ -0,0 +1,73 @@
+//! Files that are missing tags in required tag categories, so editors can find what still needs tagging.
+
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, pagination};
+
+struct IncompleteFile {
+	id: models::FileId,
+	name: String,
+	/// The names of the required categories that the file has no tags in.
+	missing: Vec<String>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "missing_tags.html")]
+struct Template {
+	self_user: models::User,
+	/// Oldest first, since they have been waiting the longest.
+	files: Vec<IncompleteFile>,
+	pagination: pagination::Template,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Query(pagination): extract::Query<pagination::Query>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let database = &*database;
+
+	// files in the trash are left out, since they don't need tagging
+	let count = sqlx::query_scalar!(
+		r#"SELECT count(*) as "count!" FROM files WHERE files.deleted_time IS NULL AND EXISTS (SELECT 1 FROM tag_categories WHERE tag_categories.required AND NOT EXISTS (SELECT 1 FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id INNER JOIN tag_category_tree ON tags.category = tag_category_tree.id WHERE file_tags.file = files.id AND tag_categories.name = ANY(tag_category_tree.path)))"#,
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let page_size = pagination.page_size();
+	let num_pages = std::cmp::max((count + page_size - 1) / page_size, 1);
+
+	if pagination.page() >= num_pages {
+		return Err(error::EntityNotFound("page").into());
+	}
+
+	let files = sqlx::query_as!(
+		IncompleteFile,
+		r#"SELECT id as "id!", name as "name!", missing as "missing!" FROM (SELECT files.id, files.name, ARRAY(SELECT tag_categories.name FROM tag_categories WHERE tag_categories.required AND NOT EXISTS (SELECT 1 FROM file_tags INNER JOIN tags ON file_tags.tag = tags.id INNER JOIN tag_category_tree ON tags.category = tag_category_tree.id WHERE file_tags.file = files.id AND tag_categories.name = ANY(tag_category_tree.path)) ORDER BY tag_categories.name) AS missing FROM files WHERE files.deleted_time IS NULL) AS files WHERE cardinality(missing) > 0 ORDER BY id OFFSET $1 LIMIT $2"#,
+		pagination.offset(),
+		pagination.limit(),
+	)
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(Template {
+		self_user,
+		files,
+		pagination: pagination::Template::from_query(pagination, num_pages),
+	})
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/", axum::routing::get(get_handler));
+
+	router
+}
@@ -2,12 +2,21 @@ use axum::Router;
 
 mod _static;
 mod admin;
+mod autocomplete;
+mod bulk_tags;
+mod collections;
+mod comments;
+mod export;
+mod favorites;
 mod files;
+mod import;
 mod login;
 mod logout;
+mod missing_tags;
 mod register;
 mod root;
 mod tags;
+mod trash;
 mod upload;
 
 macro_rules! sub {
@@ -33,7 +42,7 @@ pub fn configure() -> Router {
 	let mut app = Router::new();
 
 	merge!(app; root, _static);
-	sub!(app; admin, files, login, logout, register, tags, upload);
+	sub!(app; admin, autocomplete, bulk_tags, collections, comments, export, favorites, files, import, login, logout, missing_tags, register, tags, trash, upload);
 
 	// `static_router`'s dynamic service, which is loaded in `cfg(debug_assertions)`, uses its own `fallback`, so don't override it
 	#[cfg(not(debug_assertions))]
@@ -19,6 +19,7 @@ struct Template {
 	self_user: models::User,
 	search_results: Option<SearchResults>,
 	page_size: i64,
+	sort: evaluate::Sort,
 }
 crate::helpers::impl_into_response!(Template);
 
@@ -29,6 +30,8 @@ pub struct Query {
 	after: Option<models::FileId>,
 	#[serde(default = "crate::helpers::pagination::default_page_size")]
 	page_size: i64,
+	#[serde(default)]
+	sort: evaluate::Sort,
 }
 
 pub async fn get_handler(
@@ -37,6 +40,7 @@ pub async fn get_handler(
 		viewspec,
 		after,
 		page_size,
+		sort,
 	}): extract::Query<Query>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
@@ -44,7 +48,7 @@ pub async fn get_handler(
 		Some(ViewSpecOrError {
 			raw,
 			parsed: Ok(viewspec),
-		}) => match evaluate::evaluate(&viewspec, &*database, after, page_size).await {
+		}) => match evaluate::evaluate(&viewspec, &*database, after, page_size, sort).await {
 			Ok(results) => Some(SearchResults {
 				query: raw,
 				results: Ok(results),
@@ -72,6 +76,7 @@ pub async fn get_handler(
 		self_user,
 		search_results,
 		page_size,
+		sort,
 	})
 }
 
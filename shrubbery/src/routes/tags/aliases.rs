@@ -0,0 +1,125 @@
+//! Alternate names for a tag, such as `nyc` for `place:new york`.
+//!
+//! Aliases work wherever tags are looked up by name, like searching and the file page, but files are always tagged with the tag itself.
+
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, OrNull};
+use crate::timestamp::Timestamp;
+
+pub struct Alias {
+	pub id: models::TagAliasId,
+	pub name: String,
+	pub category: Option<String>,
+	pub created_time: Timestamp,
+	/// `None` if the user was deleted.
+	pub created_by: Option<String>,
+}
+
+pub async fn for_tag(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	tag_id: models::TagId,
+) -> sqlx::Result<Vec<Alias>> {
+	sqlx::query_as!(
+		Alias,
+		r#"SELECT tag_aliases.id, tag_aliases.name, tag_categories.name as "category?", tag_aliases.created_time as "created_time: Timestamp", users.username as "created_by?" FROM tag_aliases LEFT JOIN tag_categories ON tag_aliases.category = tag_categories.id LEFT JOIN users ON tag_aliases.created_by = users.id WHERE tag_aliases.tag = $1 ORDER BY tag_categories.name NULLS FIRST, tag_aliases.name"#,
+		tag_id,
+	)
+	.fetch_all(database)
+	.await
+}
+
+fn redirect_to_tag(tag_id: models::TagId) -> Response {
+	Redirect::to(&format!("/tags/{tag_id}#aliases")).into_response()
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CreateRequest {
+	name: String,
+	category: OrNull<models::TagCategoryId>,
+}
+
+async fn create_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Path((tag_id,)): extract::Path<(models::TagId,)>,
+	extract::Form(request): extract::Form<CreateRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+	let name = request.name.trim();
+	if name.is_empty() {
+		return Err(error::BadRequest("aliases cannot be empty".into()).into());
+	}
+	let category = request.category.into_option();
+
+	// tags are looked up before aliases, so the alias would never be used
+	let tag_exists = sqlx::query_scalar!(
+		r#"SELECT EXISTS (SELECT 1 FROM tags WHERE name = $1 AND category IS NOT DISTINCT FROM $2) as "exists!""#,
+		name,
+		category,
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?;
+	if tag_exists {
+		return Err(
+			error::BadRequest("a tag with this name already exists in this category".into()).into(),
+		);
+	}
+
+	sqlx::query!(
+		"INSERT INTO tag_aliases (name, category, tag, created_by) VALUES ($1, $2, $3, $4)",
+		name,
+		category,
+		tag_id,
+		self_user.id,
+	)
+	.execute(database)
+	.await
+	.map_err(|sql_error| match sql_error {
+		sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23505") => {
+			ErrorResponse::from(error::BadRequest(
+				"an alias with this name already exists in this category".into(),
+			))
+		}
+		// a foreign key violation, from the tag or category not existing
+		sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23503") => {
+			error::EntityNotFound("tag or tag category").into()
+		}
+		sql_error => error::Sqlx(sql_error).into(),
+	})?;
+	Ok(redirect_to_tag(tag_id))
+}
+
+async fn delete_handler(
+	_self_user: auth::Editor,
+	extract::Path((tag_id, alias_id)): extract::Path<(models::TagId, models::TagAliasId)>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	sqlx::query!(
+		"DELETE FROM tag_aliases WHERE id = $1 AND tag = $2",
+		alias_id,
+		tag_id,
+	)
+	.execute(&*database)
+	.await
+	.map_err(error::Sqlx)?;
+	Ok(redirect_to_tag(tag_id))
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/aliases", axum::routing::post(create_handler));
+	router = router.route(
+		"/aliases/:alias_id/delete",
+		axum::routing::post(delete_handler),
+	);
+
+	router
+}
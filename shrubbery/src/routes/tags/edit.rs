@@ -29,16 +29,23 @@ struct Template {
 	self_user: models::User,
 	requested_tag: Tag,
 	tag_categories: Vec<(models::TagCategoryId, String)>,
+	/// Every other tag, as `category:name`, to choose one to merge into.
+	other_tags: Vec<(models::TagId, String)>,
+	aliases: Vec<super::aliases::Alias>,
+	merges: Vec<super::merge::Merge>,
+	/// The tags that this tag implies, as `category:name`.
+	implies: Vec<(models::TagId, String)>,
+	/// The tags that imply this tag, as `category:name`.
+	implied_by: Vec<(models::TagId, String)>,
 }
 crate::helpers::impl_into_response!(Template);
 
-pub async fn get_handler(
-	auth::Admin(self_user): auth::Admin,
-	extract::Path((tag_id,)): extract::Path<(models::TagId,)>,
-	extract::Extension(database): extract::Extension<Arc<Database>>,
-) -> Result<impl IntoResponse, ErrorResponse> {
-	let database = &*database;
-
+async fn render(
+	updated: bool,
+	self_user: models::User,
+	tag_id: models::TagId,
+	database: &Database,
+) -> Result<Template, ErrorResponse> {
 	let requested_tag = Tag::by_id(database, tag_id)
 		.await
 		.map_err(error::Sqlx)?
@@ -48,14 +55,48 @@ pub async fn get_handler(
 		.await
 		.map_err(error::Sqlx)?;
 
+	let other_tags = sqlx::query!(
+		r#"SELECT tags.id, CASE WHEN tag_categories.name IS NULL THEN tags.name ELSE tag_categories.name || ':' || tags.name END as "label!" FROM tags LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE tags.id <> $1 ORDER BY tag_categories.name NULLS FIRST, tags.name"#,
+		tag_id,
+	)
+	.map(|record| (record.id, record.label))
+	.fetch_all(database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	let aliases = super::aliases::for_tag(database, tag_id)
+		.await
+		.map_err(error::Sqlx)?;
+
+	let merges = super::merge::merges_into(database, tag_id)
+		.await
+		.map_err(error::Sqlx)?;
+
+	let (implies, implied_by) = super::implications::for_tag(database, tag_id)
+		.await
+		.map_err(error::Sqlx)?;
+
 	Ok(Template {
-		updated: false,
+		updated,
 		self_user,
 		requested_tag,
 		tag_categories,
+		other_tags,
+		aliases,
+		merges,
+		implies,
+		implied_by,
 	})
 }
 
+pub async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Path((tag_id,)): extract::Path<(models::TagId,)>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	render(false, self_user, tag_id, &database).await
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct PostRequest {
 	name: String,
@@ -82,23 +123,17 @@ pub async fn post_handler(
 	)
 	.execute(database)
 	.await
-	.map_err(error::Sqlx)?;
-
-	let requested_tag = Tag::by_id(database, tag_id)
-		.await
-		.map_err(error::Sqlx)?
-		.ok_or(error::EntityNotFound("tag"))?;
-
-	let tag_categories = super::shared::get_tag_categories_lean(database)
-		.await
-		.map_err(error::Sqlx)?;
-
-	Ok(Template {
-		updated: true,
-		self_user,
-		requested_tag,
-		tag_categories,
-	})
+	.map_err(|sql_error| match sql_error {
+		// a unique violation, which is the only one on this table
+		sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23505") => {
+			ErrorResponse::from(error::BadRequest(
+				"a tag with this name already exists in this category; merge into it instead".into(),
+			))
+		}
+		sql_error => error::Sqlx(sql_error).into(),
+	})?;
+
+	render(true, self_user, tag_id, database).await
 }
 
 pub fn configure() -> Router {
@@ -0,0 +1,95 @@
+//! Managing the implications of a tag. See [`crate::helpers::implications`].
+
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, implications};
+
+/// The tags that `tag_id` implies, and the tags that imply it, as `category:name`.
+pub async fn for_tag(
+	database: &Database,
+	tag_id: models::TagId,
+) -> sqlx::Result<(Vec<(models::TagId, String)>, Vec<(models::TagId, String)>)> {
+	let implies = sqlx::query!(
+		r#"SELECT tags.id, CASE WHEN tag_categories.name IS NULL THEN tags.name ELSE tag_categories.name || ':' || tags.name END as "label!" FROM tag_implications INNER JOIN tags ON tag_implications.implied = tags.id LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE tag_implications.tag = $1 ORDER BY tag_categories.name NULLS FIRST, tags.name"#,
+		tag_id,
+	)
+	.map(|record| (record.id, record.label))
+	.fetch_all(database)
+	.await?;
+	let implied_by = sqlx::query!(
+		r#"SELECT tags.id, CASE WHEN tag_categories.name IS NULL THEN tags.name ELSE tag_categories.name || ':' || tags.name END as "label!" FROM tag_implications INNER JOIN tags ON tag_implications.tag = tags.id LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE tag_implications.implied = $1 ORDER BY tag_categories.name NULLS FIRST, tags.name"#,
+		tag_id,
+	)
+	.map(|record| (record.id, record.label))
+	.fetch_all(database)
+	.await?;
+	Ok((implies, implied_by))
+}
+
+fn redirect_to_tag(tag_id: models::TagId) -> Response {
+	Redirect::to(&format!("/tags/{tag_id}#implications")).into_response()
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CreateRequest {
+	implied: models::TagId,
+}
+
+async fn create_handler(
+	auth::Admin(self_user): auth::Admin,
+	extract::Path((tag_id,)): extract::Path<(models::TagId,)>,
+	extract::Form(request): extract::Form<CreateRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	if request.implied == tag_id {
+		return Err(error::BadRequest("a tag cannot imply itself".into()).into());
+	}
+	implications::add(tag_id, request.implied, self_user.id, &database)
+		.await
+		.map_err(|sql_error| match sql_error {
+			sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23505") => {
+				ErrorResponse::from(error::BadRequest(
+					"this tag already implies that tag".into(),
+				))
+			}
+			// a foreign key violation, from either tag not existing
+			sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23503") => {
+				error::EntityNotFound("tag").into()
+			}
+			sql_error => error::Sqlx(sql_error).into(),
+		})??;
+	Ok(redirect_to_tag(tag_id))
+}
+
+async fn delete_handler(
+	_self_user: auth::Admin,
+	extract::Path((tag_id, implied)): extract::Path<(models::TagId, models::TagId)>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	sqlx::query!(
+		"DELETE FROM tag_implications WHERE tag = $1 AND implied = $2",
+		tag_id,
+		implied,
+	)
+	.execute(&*database)
+	.await
+	.map_err(error::Sqlx)?;
+	Ok(redirect_to_tag(tag_id))
+}
+
+pub fn configure() -> Router {
+	let mut router = Router::new();
+
+	router = router.route("/implications", axum::routing::post(create_handler));
+	router = router.route(
+		"/implications/:implied/delete",
+		axum::routing::post(delete_handler),
+	);
+
+	router
+}
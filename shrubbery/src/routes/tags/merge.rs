@@ -0,0 +1,163 @@
+//! Merging one tag into another, for when the same tag was created twice.
+//!
+//! Renaming a tag to the name of an existing tag violates `UNIQUE(name, category)`, and deleting it would lose its files, so its files are moved to the other tag first, along with its aliases and implications.
+
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect};
+use axum::{extract, Router};
+
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, implications, tag_categories};
+use crate::timestamp::Timestamp;
+
+/// A merge into a tag, for its history.
+pub struct Merge {
+	pub source_name: String,
+	pub source_category: Option<String>,
+	pub num_files: i32,
+	pub kept_alias: bool,
+	pub merged_time: Timestamp,
+	/// `None` if the user was deleted.
+	pub merged_by: Option<String>,
+}
+
+/// Most recent first.
+pub async fn merges_into(
+	database: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+	tag_id: models::TagId,
+) -> sqlx::Result<Vec<Merge>> {
+	sqlx::query_as!(
+		Merge,
+		r#"SELECT source_name, source_category, num_files, kept_alias, merged_time as "merged_time: Timestamp", users.username as "merged_by?" FROM tag_merges LEFT JOIN users ON tag_merges.merged_by = users.id WHERE target = $1 ORDER BY merged_time DESC"#,
+		tag_id,
+	)
+	.fetch_all(database)
+	.await
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PostRequest {
+	target: models::TagId,
+	/// Present if the merged tag's name should keep working as an alias of the target.
+	keep_alias: Option<String>,
+}
+
+async fn post_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Path((tag_id,)): extract::Path<(models::TagId,)>,
+	extract::Form(request): extract::Form<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+	let target = request.target;
+	if target == tag_id {
+		return Err(error::BadRequest("a tag cannot be merged into itself".into()).into());
+	}
+
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	// lock both tags so that neither is changed or deleted during the merge
+	let source = sqlx::query!(
+		r#"SELECT tags.name, tags.category, tag_categories.name as "category_name?" FROM tags LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE tags.id = $1 FOR UPDATE OF tags"#,
+		tag_id,
+	)
+	.fetch_optional(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?
+	.ok_or(error::EntityNotFound("tag"))?;
+	sqlx::query!("SELECT id FROM tags WHERE id = $1 FOR UPDATE", target)
+		.fetch_optional(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?
+		.ok_or(error::EntityNotFound("tag to merge into"))?;
+
+	let files = sqlx::query_scalar!(
+		"UPDATE files SET modified_time = CURRENT_TIMESTAMP WHERE id IN (SELECT file FROM file_tags WHERE tag = $1) RETURNING id",
+		tag_id,
+	)
+	.fetch_all(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	// files that already have both tags keep their existing row for the target
+	sqlx::query!(
+		"INSERT INTO file_tags (file, tag) SELECT file, $2 FROM file_tags WHERE tag = $1 ON CONFLICT (file, tag) DO NOTHING",
+		tag_id,
+		target,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	// aliases of the merged tag would otherwise be deleted along with it
+	sqlx::query!(
+		"UPDATE tag_aliases SET tag = $2 WHERE tag = $1",
+		tag_id,
+		target,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	let keep_alias = request.keep_alias.is_some();
+	if keep_alias {
+		sqlx::query!(
+			"INSERT INTO tag_aliases (name, category, tag, created_by) VALUES ($1, $2, $3, $4)",
+			source.name,
+			source.category,
+			target,
+			self_user.id,
+		)
+		.execute(&mut transaction)
+		.await
+		.map_err(|sql_error| match sql_error {
+			sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23505") => {
+				ErrorResponse::from(error::BadRequest(
+					"an alias with this tag's name already exists in its category, so the name can't be kept as an alias".into(),
+				))
+			}
+			sql_error => error::Sqlx(sql_error).into(),
+		})?;
+	}
+	sqlx::query!(
+		"INSERT INTO tag_merges (source_name, source_category, target, num_files, kept_alias, merged_by) VALUES ($1, $2, $3, $4, $5, $6)",
+		source.name,
+		source.category_name,
+		target,
+		i32::try_from(files.len()).unwrap_or(i32::MAX),
+		keep_alias,
+		self_user.id,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	// implications of the merged tag would otherwise be deleted along with it
+	let moved = implications::move_to(tag_id, target, &mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	if !moved {
+		return Err(
+			error::BadRequest("the implications of the two tags would form a cycle when combined".into())
+				.into(),
+		);
+	}
+	sqlx::query!("DELETE FROM tags WHERE id = $1", tag_id)
+		.execute(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	// the merged files now have the target, and files that already had it may have gained implications
+	let tagged = sqlx::query_scalar!("SELECT file FROM file_tags WHERE tag = $1", target)
+		.fetch_all(&mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	implications::apply(Some(tagged.as_slice()), &mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	tag_categories::check_exclusive(&tagged, &mut transaction)
+		.await
+		.map_err(error::Sqlx)??;
+	transaction.commit().await.map_err(error::Sqlx)?;
+
+	Ok(Redirect::to(&format!("/tags/{target}")))
+}
+
+pub fn configure() -> Router {
+	Router::new().route("/merge", axum::routing::post(post_handler))
+}
@@ -1,3 +1,4 @@
+use std::fmt::{self, Display, Formatter};
 use std::sync::Arc;
 
 use axum::response::{ErrorResponse, IntoResponse};
@@ -6,10 +7,13 @@ use axum::{extract, Router};
 
 use crate::database::{models, Database};
 use crate::error;
-use crate::helpers::{auth, pagination};
+use crate::helpers::{auth, escape_like, pagination, percent, tag_text};
 
+mod aliases;
 mod delete;
 mod edit;
+mod implications;
+mod merge;
 mod shared;
 
 struct Tag {
@@ -17,47 +21,166 @@ struct Tag {
 	pub name: String,
 	pub description: Option<String>,
 	pub category: Option<String>,
+	pub num_files: i64,
 	pub created_time: crate::timestamp::Timestamp,
 	pub created_by: Option<String>,
 }
 
+impl Tag {
+	/// The search for files with exactly this tag, already encoded.
+	fn search(&self) -> String {
+		let search = match &self.category {
+			Some(category) => tag_text::format(Some(category), &self.name),
+			// a bare name would also match tags with the name in any category, and aliases
+			None => format!(":{}", viewspec::lex::quote(&self.name)),
+		};
+		percent::encode(search.as_bytes())
+	}
+}
+
+/// The order of the tags index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+	/// The order that the tags were created in.
+	#[default]
+	Oldest,
+	Newest,
+	Name,
+	MostUsed,
+	/// By category and then by name, with tags without a category first.
+	Category,
+}
+
+impl Sort {
+	const ALL: [Self; 5] = [
+		Self::Oldest,
+		Self::Newest,
+		Self::Name,
+		Self::MostUsed,
+		Self::Category,
+	];
+
+	fn as_str(self) -> &'static str {
+		match self {
+			Self::Oldest => "oldest",
+			Self::Newest => "newest",
+			Self::Name => "name",
+			Self::MostUsed => "most_used",
+			Self::Category => "category",
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			Self::Oldest => "Oldest first",
+			Self::Newest => "Newest first",
+			Self::Name => "By name",
+			Self::MostUsed => "Most used first",
+			Self::Category => "By category",
+		}
+	}
+
+	pub fn display_options(self) -> impl Display + 'static {
+		struct Helper(Sort);
+		impl Display for Helper {
+			fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+				for possibility in Sort::ALL {
+					let selected = if self.0 == possibility {
+						" selected"
+					} else {
+						""
+					};
+					write!(
+						formatter,
+						"<option value=\"{}\"{selected}>{}</option>",
+						possibility.as_str(),
+						possibility.description(),
+					)?;
+				}
+				Ok(())
+			}
+		}
+
+		Helper(self)
+	}
+}
+
+impl Display for Sort {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		formatter.write_str(self.as_str())
+	}
+}
+
 #[derive(askama::Template)]
 #[template(path = "tags/index.html")]
 struct Template {
 	self_user: models::User,
 	tags: Vec<Tag>,
 	tag_categories: Vec<(models::TagCategoryId, String)>,
+	filter: String,
+	sort: Sort,
 	pagination: pagination::Template,
 }
 crate::helpers::impl_into_response!(Template);
 
+#[derive(serde::Deserialize)]
+pub struct Query {
+	/// Only tags whose name, category or `category:name` contains this, ignoring case.
+	#[serde(default)]
+	filter: String,
+	#[serde(default)]
+	sort: Sort,
+}
+
 pub async fn get_handler(
 	auth::Auth(self_user): auth::Auth,
 	extract::Query(pagination): extract::Query<pagination::Query>,
+	extract::Query(Query { filter, sort }): extract::Query<Query>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
 	let database = &*database;
 
-	let num_pages = std::cmp::max(
-		models::Tag::count(database).await.map_err(error::Sqlx)? / pagination.page_size(),
-		1,
-	);
+	let filter = filter.trim().to_owned();
+	let pattern = (!filter.is_empty()).then(|| format!("%{}%", escape_like(&filter)));
+
+	let count = sqlx::query_scalar!(
+		r#"SELECT count(*) as "count!" FROM tags LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE $1::text IS NULL OR tags.name ILIKE $1 OR tag_categories.name ILIKE $1 OR tag_categories.name || ':' || tags.name ILIKE $1"#,
+		pattern,
+	)
+	.fetch_one(database)
+	.await
+	.map_err(error::Sqlx)?;
+	let page_size = pagination.page_size();
+	let num_pages = std::cmp::max((count + page_size - 1) / page_size, 1);
 
 	if pagination.page() >= num_pages {
 		return Err(error::EntityNotFound("page").into());
 	}
 
-	let tags = sqlx::query_as!(Tag, r#"SELECT tags.id, tags.name, tags.description, tag_categories.name as "category?", tags.created_time as "created_time: crate::timestamp::Timestamp", users.username as "created_by?" FROM tags LEFT JOIN users ON tags.created_by = users.id LEFT JOIN tag_categories ON tags.category = tag_categories.id ORDER BY tags.id OFFSET $1 LIMIT $2"#, pagination.offset(), pagination.limit()).fetch_all(database).await.map_err(error::Sqlx)?;
+	// the number of files is kept on each tag by triggers, so sorting by it doesn't count every tag's files
+	let tags = sqlx::query_as!(Tag, r#"SELECT tags.id, tags.name, tags.description, tag_categories.name as "category?", tags.num_files, tags.created_time as "created_time: crate::timestamp::Timestamp", users.username as "created_by?" FROM tags LEFT JOIN users ON tags.created_by = users.id LEFT JOIN tag_categories ON tags.category = tag_categories.id WHERE $3::text IS NULL OR tags.name ILIKE $3 OR tag_categories.name ILIKE $3 OR tag_categories.name || ':' || tags.name ILIKE $3 ORDER BY CASE WHEN $4 = 'most_used' THEN tags.num_files END DESC, CASE WHEN $4 = 'category' THEN tag_categories.name END NULLS FIRST, CASE WHEN $4 IN ('name', 'category') THEN tags.name END, CASE WHEN $4 = 'newest' THEN tags.id END DESC, tags.id OFFSET $1 LIMIT $2"#, pagination.offset(), pagination.limit(), pattern, sort.as_str()).fetch_all(database).await.map_err(error::Sqlx)?;
 
 	let tag_categories = shared::get_tag_categories_lean(database)
 		.await
 		.map_err(error::Sqlx)?;
 
+	// keep the filter and sort when changing pages or deleting a tag
+	let mut extra = Vec::new();
+	if !filter.is_empty() {
+		extra.push(format!("filter={}", percent::encode(filter.as_bytes())));
+	}
+	if sort != Sort::default() {
+		extra.push(format!("sort={sort}"));
+	}
+
 	Ok(Template {
 		self_user,
 		tags,
 		tag_categories,
-		pagination: pagination::Template::from_query(pagination, num_pages),
+		filter,
+		sort,
+		pagination: pagination::Template::from_query(pagination, num_pages).with_extra(extra.join("&")),
 	})
 }
 
@@ -71,6 +194,7 @@ pub struct CreateRequest {
 pub async fn post_handler(
 	auth::Editor(self_user): auth::Editor,
 	pagination: extract::Query<pagination::Query>,
+	query: extract::Query<Query>,
 	extract::Form(req): extract::Form<CreateRequest>,
 	extract::Extension(database): extract::Extension<Arc<Database>>,
 ) -> Result<impl IntoResponse, ErrorResponse> {
@@ -93,6 +217,7 @@ pub async fn post_handler(
 	get_handler(
 		auth::Auth(self_user),
 		pagination,
+		query,
 		extract::Extension(database),
 	)
 	.await
@@ -101,5 +226,12 @@ pub async fn post_handler(
 pub fn configure() -> Router {
 	Router::new()
 		.route("/", get(get_handler).post(post_handler))
-		.nest("/:tag_id", delete::configure().merge(edit::configure()))
+		.nest(
+			"/:tag_id",
+			aliases::configure()
+				.merge(delete::configure())
+				.merge(edit::configure())
+				.merge(implications::configure())
+				.merge(merge::configure()),
+		)
 }
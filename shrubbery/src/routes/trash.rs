@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use axum::response::{ErrorResponse, IntoResponse, Redirect, Response};
+use axum::{extract, Router};
+
+use crate::config::Config;
+use crate::database::{models, Database};
+use crate::error;
+use crate::helpers::{auth, trash};
+use crate::storage::Storage;
+use crate::timestamp::Timestamp;
+
+struct TrashedFile {
+	id: models::FileId,
+	name: String,
+	deleted_time: Timestamp,
+	/// `None` if the user was deleted.
+	deleted_by: Option<String>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "trash.html")]
+struct Template {
+	self_user: models::User,
+	files: Vec<TrashedFile>,
+	/// Zero if files are never purged automatically.
+	retention_days: u32,
+}
+crate::helpers::impl_into_response!(Template);
+
+async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+) -> Result<Response, ErrorResponse> {
+	let files = sqlx::query_as!(
+		TrashedFile,
+		r#"SELECT files.id, files.name, files.deleted_time as "deleted_time!: Timestamp", users.username as "deleted_by?" FROM files LEFT JOIN users ON files.deleted_by = users.id WHERE files.deleted_time IS NOT NULL ORDER BY files.deleted_time DESC"#
+	)
+	.fetch_all(&*database)
+	.await
+	.map_err(error::Sqlx)?;
+
+	Ok(
+		Template {
+			self_user,
+			files,
+			retention_days: config.trash_retention_days,
+		}
+		.into_response(),
+	)
+}
+
+#[derive(axum_easy_multipart::FromMultipart)]
+#[multipart(tag = "action")]
+enum PostRequest {
+	#[multipart(rename = "restore")]
+	Restore { file: models::FileId },
+	#[multipart(rename = "purge")]
+	Purge { file: models::FileId },
+}
+
+async fn post_handler(
+	auth::Editor(_self_user): auth::Editor,
+	axum_easy_multipart::Extractor(req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+) -> Result<Response, ErrorResponse> {
+	let database = &*database;
+
+	match req {
+		PostRequest::Restore { file } => {
+			let q_result = sqlx::query!(
+				"UPDATE files SET deleted_time = NULL, deleted_by = NULL WHERE id = $1 AND deleted_time IS NOT NULL",
+				file,
+			)
+			.execute(database)
+			.await
+			.map_err(error::Sqlx)?;
+			if q_result.rows_affected() == 0 {
+				return Err(error::EntityNotFound("file in trash").into());
+			}
+			Ok(Redirect::to(&format!("/files/{file}")).into_response())
+		}
+		PostRequest::Purge { file } => {
+			let purged = trash::purge(&[file], database, &*storage)
+				.await
+				.map_err(error::Sqlx)?;
+			if purged.is_empty() {
+				return Err(error::EntityNotFound("file in trash").into());
+			}
+			Ok(Redirect::to("/trash").into_response())
+		}
+	}
+}
+
+pub fn configure() -> Router {
+	let mut app = Router::new();
+	app = app.route("/", axum::routing::get(get_handler).post(post_handler));
+	app
+}
@@ -6,65 +6,249 @@ use axum::{extract, Router};
 use crate::config::Config;
 use crate::database::{models, Database};
 use crate::error;
-use crate::helpers::auth;
+use crate::helpers::{auth, implications, metadata, perceptual_hash, quota, sniff, tag_categories};
+use crate::routes::files::id::{get_tags_by_category, MakeTempfile, TagsByCategory};
+use crate::storage::{Key, Storage};
 
 #[derive(askama::Template)]
 #[template(path = "files/upload.html")]
 struct Template {
 	self_user: models::User,
+	tags_by_category: TagsByCategory,
 }
 crate::helpers::impl_into_response!(Template);
 
-async fn get_handler(auth::Editor(self_user): auth::Editor) -> impl IntoResponse {
-	Template { self_user }
+struct Outcome {
+	name: String,
+	result: Result<models::FileId, String>,
+}
+
+#[derive(askama::Template)]
+#[template(path = "files/upload_summary.html")]
+struct SummaryTemplate {
+	self_user: models::User,
+	outcomes: Vec<Outcome>,
+}
+crate::helpers::impl_into_response!(SummaryTemplate);
+
+impl SummaryTemplate {
+	fn num_succeeded(&self) -> usize {
+		self
+			.outcomes
+			.iter()
+			.filter(|outcome| outcome.result.is_ok())
+			.count()
+	}
+}
+
+async fn get_handler(
+	auth::Editor(self_user): auth::Editor,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+) -> Result<Response, ErrorResponse> {
+	Ok(
+		Template {
+			self_user,
+			tags_by_category: get_tags_by_category(&*database, None)
+				.await
+				.map_err(error::Sqlx)?,
+		}
+		.into_response(),
+	)
 }
 
 #[derive(axum_easy_multipart::FromMultipart)]
 struct PostRequest {
-	name: String,
+	/// Only used if a single file is uploaded.
+	name: Option<String>,
 	description: Option<String>,
-	file: axum_easy_multipart::file::File<crate::routes::files::id::MakeTempfile>,
+	tags: Vec<models::TagId>,
+	files: Vec<axum_easy_multipart::file::File<MakeTempfile>>,
 }
 
-async fn post_handler(
-	auth::Editor(_self_user): auth::Editor,
-	axum_easy_multipart::Extractor(mut req): axum_easy_multipart::Extractor<PostRequest>,
-	extract::Extension(database): extract::Extension<Arc<Database>>,
-	extract::Extension(config): extract::Extension<Arc<Config>>,
-) -> Result<Response, ErrorResponse> {
-	let database = &*database;
-	req.description = req.description.filter(|desc| !desc.is_empty());
-
-	let media_type = req
-		.file
-		.content_type
-		.as_ref()
-		.and_then(models::MediaType::from_mime)
-		.ok_or(error::BadContentType)?;
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+	#[error(transparent)]
+	Format(#[from] sniff::CheckError),
+	#[error(transparent)]
+	Sqlx(#[from] error::Sqlx),
+	#[error(transparent)]
+	Io(#[from] error::Io),
+	#[error(transparent)]
+	Quota(#[from] quota::Exceeded),
+	#[error(transparent)]
+	Tags(#[from] tag_categories::ExclusiveViolation),
+}
+
+pub struct NewFile<'a> {
+	pub name: &'a str,
+	pub description: Option<&'a str>,
+	pub tags: &'a [models::TagId],
+	/// In the file storage directory, so it can be moved into storage.
+	pub temp_path: tempfile::TempPath,
+	/// The content type declared by the client, if any.
+	pub declared: Option<&'a mime::Mime>,
+}
+
+/// Creates a file that counts towards the quota of `uploader`.
+pub async fn create_file(
+	NewFile {
+		name,
+		description,
+		tags,
+		temp_path,
+		declared,
+	}: NewFile<'_>,
+	uploader: models::UserId,
+	config: &Config,
+	database: &Database,
+	storage: &dyn Storage,
+) -> Result<models::FileId, UploadError> {
+	// files from archives have not been checked against the limit yet
+	let size = tokio::fs::metadata(&temp_path)
+		.await
+		.map_err(|error| error::Io("getting size of upload", error))?
+		.len();
+	let size = i64::try_from(size).unwrap_or(i64::MAX);
+	if let Some(limit) = quota::max_file_size(config) {
+		if size > limit {
+			return Err(quota::Exceeded::FileSize { limit }.into());
+		}
+	}
+
+	let format = sniff::check_upload(temp_path.to_path_buf(), declared).await?;
+	let media_type = format.media_type;
+	let perceptual_hash = perceptual_hash::hash_file(temp_path.to_path_buf(), media_type).await;
+	let metadata = metadata::extract(temp_path.to_path_buf(), media_type).await;
+
+	let mut transaction = database.begin().await.map_err(error::Sqlx)?;
+	let (limits, usage) = quota::lock(uploader, config, &mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	quota::check(&limits, &usage, size, 1)?;
 	let record = sqlx::query!(
-		"INSERT INTO files (name, description, media_type) VALUES ($1, $2, $3) RETURNING id",
-		req.name,
-		req.description,
+		"INSERT INTO files (name, description, media_type, mime_type, perceptual_hash, size, width, height, duration, capture_time, camera_model, orientation, uploaded_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING id",
+		name,
+		description,
 		media_type as _,
+		format.mime_type,
+		perceptual_hash,
+		metadata.size,
+		metadata.width,
+		metadata.height,
+		metadata.duration,
+		metadata.capture_time,
+		metadata.camera_model,
+		metadata.orientation,
+		uploader,
 	)
-	.fetch_one(database)
+	.fetch_one(&mut transaction)
 	.await
 	.map_err(error::Sqlx)?;
-
 	let file_id = record.id;
-	match req
-		.file
-		.temp_path
-		.persist_noclobber(config.file_storage.join(format!("{file_id}")))
-		.map_err(|error| error.error)
+	sqlx::query!(
+		"INSERT INTO file_tags (file, tag) (SELECT $1 as file, unnest as tag FROM unnest(cast($2 as int[])))",
+		file_id,
+		tags,
+	)
+	.execute(&mut transaction)
+	.await
+	.map_err(error::Sqlx)?;
+	implications::apply(Some(std::slice::from_ref(&file_id)), &mut transaction)
+		.await
+		.map_err(error::Sqlx)?;
+	tag_categories::check_exclusive(std::slice::from_ref(&file_id), &mut transaction)
+		.await
+		.map_err(error::Sqlx)??;
+
+	// the ID is new, so anything already stored under it is not ours to replace
+	let key = Key::File(file_id);
+	if storage
+		.exists(key)
+		.await
+		.map_err(|error| error::Io("checking storage", error))?
 	{
-		Ok(()) => Ok(Redirect::to(&format!("/files/{file_id}?created")).into_response()),
-		Err(error) => {
-			let _ = sqlx::query!("DELETE FROM files WHERE id = $1", file_id)
-				.execute(database)
-				.await;
-			Err(error::Io("storing file in filesystem", error).into())
-		}
+		return Err(
+			error::Io(
+				"storing file",
+				std::io::Error::new(
+					std::io::ErrorKind::AlreadyExists,
+					format!("{key} is already stored"),
+				),
+			)
+			.into(),
+		);
+	}
+	storage
+		.put(key, temp_path)
+		.await
+		.map_err(|error| error::Io("storing file", error))?;
+	if let Err(error) = transaction.commit().await {
+		let _ = storage.delete(key).await;
+		return Err(error::Sqlx(error).into());
+	}
+
+	Ok(file_id)
+}
+
+async fn post_handler(
+	quota::Uploader(self_user): quota::Uploader,
+	axum_easy_multipart::Extractor(mut req): axum_easy_multipart::Extractor<PostRequest>,
+	extract::Extension(config): extract::Extension<Arc<Config>>,
+	extract::Extension(database): extract::Extension<Arc<Database>>,
+	extract::Extension(storage): extract::Extension<Arc<dyn Storage>>,
+) -> Result<Response, ErrorResponse> {
+	crate::helpers::set_none_if_empty(&mut req.name);
+	crate::helpers::set_none_if_empty(&mut req.description);
+	// browsers send a single empty file if none were selected
+	req
+		.files
+		.retain(|file| file.size > 0 || !matches!(file.file_name.as_deref(), None | Some("")));
+	if req.files.is_empty() {
+		return Err(error::BadRequest("no files were uploaded".into()).into());
+	}
+
+	let single = req.files.len() == 1;
+	let mut outcomes = Vec::with_capacity(req.files.len());
+	for file in req.files {
+		let name = match (&req.name, &file.file_name) {
+			(Some(name), _) if single => name.clone(),
+			(_, Some(file_name)) if !file_name.is_empty() => file_name.clone(),
+			_ => "Untitled".to_owned(),
+		};
+		// partial failures should not prevent the rest of the files from being uploaded
+		let result = create_file(
+			NewFile {
+				name: &name,
+				description: req.description.as_deref(),
+				tags: &req.tags,
+				temp_path: file.temp_path,
+				declared: file.content_type.as_ref(),
+			},
+			self_user.id,
+			&config,
+			&database,
+			&*storage,
+		)
+		.await
+		.map_err(|error| {
+			tracing::warn!("could not upload {name:?}: {error}");
+			error.to_string()
+		});
+		outcomes.push(Outcome { name, result });
+	}
+
+	match outcomes.as_slice() {
+		[Outcome {
+			result: Ok(file_id),
+			..
+		}] => Ok(Redirect::to(&format!("/files/{file_id}?created")).into_response()),
+		_ => Ok(
+			SummaryTemplate {
+				self_user,
+				outcomes,
+			}
+			.into_response(),
+		),
 	}
 }
 
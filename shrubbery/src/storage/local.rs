@@ -0,0 +1,186 @@
+//! Storage in a directory of the local filesystem.
+
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt as _;
+use sha2::Digest as _;
+use tempfile::TempPath;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+use tokio_util::io::ReaderStream;
+
+use super::{ByteStream, Key, Metadata, Storage, VERSIONS};
+use crate::database::models;
+
+/// Content is stored at `{root}/{key}`, or with sharding, in subdirectories named by pairs of hexadecimal digits from a hash of the key.
+///
+/// Temporary files passed to [`Storage::put`] must be on the same filesystem as the root, so they can be moved into place.
+#[derive(Clone)]
+pub struct Local {
+	root: PathBuf,
+	shard_levels: u8,
+}
+
+impl Local {
+	pub fn new(root: PathBuf, shard_levels: u8) -> Self {
+		Self { root, shard_levels }
+	}
+
+	pub fn path(&self, key: Key) -> PathBuf {
+		let mut path = self.root.clone();
+		if let Some(directory) = key.directory() {
+			path.push(directory);
+		}
+		let name = key.name();
+		if self.shard_levels > 0 {
+			let hash = hex::encode(sha2::Sha256::digest(name.as_bytes()));
+			// SHA-256 has 32 bytes, so there are at most 32 levels
+			for level in 0..usize::from(self.shard_levels).min(32) {
+				path.push(&hash[level * 2..level * 2 + 2]);
+			}
+		}
+		path.push(name);
+		path
+	}
+
+	async fn create_parent(&self, key: Key) -> io::Result<PathBuf> {
+		let path = self.path(key);
+		if let Some(parent) = path.parent() {
+			tokio::fs::create_dir_all(parent).await?;
+		}
+		Ok(path)
+	}
+
+	/// Collects the keys of content in `directory`, descending into shard directories. Only content that is where [`Self::path`] expects it is included, so the result does not depend on what else is in the directories.
+	fn list_blocking(
+		&self,
+		directory: &Path,
+		depth: u8,
+		make_key: fn(models::FileId) -> Key,
+		keys: &mut Vec<Key>,
+	) -> io::Result<()> {
+		let entries = match std::fs::read_dir(directory) {
+			Ok(entries) => entries,
+			Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+			Err(error) => return Err(error),
+		};
+		for entry in entries {
+			let entry = entry?;
+			let file_type = entry.file_type()?;
+			// names that are not Unicode cannot be keys, so it does not matter that they are mangled
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			if depth < self.shard_levels {
+				if file_type.is_dir() && name.len() == 2 {
+					self.list_blocking(&entry.path(), depth + 1, make_key, keys)?;
+				}
+			} else if let (true, Ok(id)) = (file_type.is_file(), name.parse()) {
+				let key = make_key(id);
+				if self.path(key) == entry.path() {
+					keys.push(key);
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl Storage for Local {
+	async fn put(&self, key: Key, temp_path: TempPath) -> io::Result<()> {
+		let path = self.create_parent(key).await?;
+		temp_path.persist(path).map_err(|error| error.error)
+	}
+
+	async fn get(&self, key: Key, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+		let mut file = tokio::fs::File::open(self.path(key)).await?;
+		Ok(match range {
+			Some((start, end)) => {
+				file.seek(SeekFrom::Start(start)).await?;
+				ReaderStream::new(file.take(end - start + 1)).boxed()
+			}
+			None => ReaderStream::new(file).boxed(),
+		})
+	}
+
+	async fn metadata(&self, key: Key) -> io::Result<Option<Metadata>> {
+		match tokio::fs::metadata(self.path(key)).await {
+			Ok(fs_metadata) => Ok(Some(Metadata {
+				size: fs_metadata.len(),
+				modified: fs_metadata.modified().ok(),
+				tag: None,
+			})),
+			Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(error) => Err(error),
+		}
+	}
+
+	async fn delete(&self, key: Key) -> io::Result<()> {
+		match tokio::fs::remove_file(self.path(key)).await {
+			Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+			result => result,
+		}
+	}
+
+	async fn rename(&self, from: Key, to: Key) -> io::Result<()> {
+		let to = self.create_parent(to).await?;
+		tokio::fs::rename(self.path(from), to).await
+	}
+
+	async fn list(&self) -> io::Result<Vec<Key>> {
+		let local = self.clone();
+		tokio::task::spawn_blocking(move || {
+			let mut keys = Vec::new();
+			local.list_blocking(&local.root, 0, Key::File, &mut keys)?;
+			local.list_blocking(&local.root.join(VERSIONS), 0, Key::Version, &mut keys)?;
+			Ok(keys)
+		})
+		.await?
+	}
+
+	async fn quarantine(&self, key: Key) -> io::Result<()> {
+		let to = self.root.join(key.quarantine_path());
+		if let Some(parent) = to.parent() {
+			tokio::fs::create_dir_all(parent).await?;
+		}
+		tokio::fs::rename(self.path(key), to).await
+	}
+
+	fn local_path(&self, key: Key) -> Option<PathBuf> {
+		Some(self.path(key))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::Path;
+
+	use super::{Key, Local};
+
+	#[test]
+	fn path() {
+		let unsharded = Local::new("/files".into(), 0);
+		assert_eq!(unsharded.path(Key::File(1)), Path::new("/files/1"));
+		assert_eq!(
+			unsharded.path(Key::Version(1)),
+			Path::new("/files/versions/1")
+		);
+
+		// the SHA-256 of "1" starts with `6b86b2`
+		let sharded = Local::new("/files".into(), 2);
+		assert_eq!(sharded.path(Key::File(1)), Path::new("/files/6b/86/1"));
+		assert_eq!(
+			sharded.path(Key::Version(1)),
+			Path::new("/files/versions/6b/86/1")
+		);
+	}
+
+	#[tokio::test]
+	async fn backend() {
+		for shard_levels in [0, 2] {
+			let dir = tempfile::tempdir().unwrap();
+			let storage = Local::new(dir.path().to_owned(), shard_levels);
+			super::super::test::check_backend(&storage, dir.path()).await;
+		}
+	}
+}
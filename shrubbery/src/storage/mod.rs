@@ -0,0 +1,274 @@
+//! Where the content of files and their old versions is stored.
+//!
+//! The database only refers to content by [`Key`], so backends can store it however they like.
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::Bytes;
+use futures::stream::BoxStream;
+use futures::TryStreamExt as _;
+use tempfile::TempPath;
+use tokio::io::AsyncWriteExt as _;
+
+use crate::config::{Config, StorageBackend};
+use crate::database::models;
+
+pub mod local;
+pub mod s3;
+
+pub use local::Local;
+pub use s3::S3;
+
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Where old versions are stored, relative to the root of the storage.
+const VERSIONS: &str = "versions";
+/// Where content is moved by [`Storage::quarantine`], relative to the root of the storage.
+pub const QUARANTINE: &str = "quarantine";
+
+/// Identifies stored content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Key {
+	File(models::FileId),
+	Version(models::FileVersionId),
+}
+
+impl Key {
+	/// The directory that keys of this kind are stored in, relative to the root of the storage.
+	fn directory(self) -> Option<&'static str> {
+		match self {
+			Self::File(_) => None,
+			Self::Version(_) => Some(VERSIONS),
+		}
+	}
+
+	fn name(self) -> String {
+		match self {
+			Self::File(id) | Self::Version(id) => id.to_string(),
+		}
+	}
+
+	/// The inverse of the [`Display`] implementation. Only canonical names are accepted, so `012` is not the same as `12`.
+	fn from_path(path: &str) -> Option<Self> {
+		let (make_key, name): (fn(models::FileId) -> Self, _) = match path.split_once('/') {
+			None => (Self::File, path),
+			Some((VERSIONS, name)) => (Self::Version, name),
+			Some(_) => return None,
+		};
+		let key = make_key(name.parse().ok()?);
+		(key.name() == name).then_some(key)
+	}
+
+	/// Where the content is moved by [`Storage::quarantine`], relative to the root of the storage. The time is included so that content quarantined more than once under the same key is kept.
+	fn quarantine_path(self) -> String {
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |since_epoch| since_epoch.as_secs());
+		format!("{QUARANTINE}/{self}.{now}")
+	}
+}
+
+impl Display for Key {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self.directory() {
+			Some(directory) => write!(formatter, "{directory}/{}", self.name()),
+			None => formatter.write_str(&self.name()),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+	pub size: u64,
+	pub modified: Option<SystemTime>,
+	/// An identifier that changes whenever the content does, if the backend has one.
+	pub tag: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+	/// Stores the content of a temporary file, replacing anything already stored under `key`.
+	async fn put(&self, key: Key, temp_path: TempPath) -> io::Result<()>;
+
+	/// Streams the content, or only the inclusive byte range `range` of it. Fails with [`io::ErrorKind::NotFound`] if nothing is stored under `key`.
+	async fn get(&self, key: Key, range: Option<(u64, u64)>) -> io::Result<ByteStream>;
+
+	/// Returns `None` if nothing is stored under `key`.
+	async fn metadata(&self, key: Key) -> io::Result<Option<Metadata>>;
+
+	async fn exists(&self, key: Key) -> io::Result<bool> {
+		Ok(self.metadata(key).await?.is_some())
+	}
+
+	/// Succeeds if nothing is stored under `key`.
+	async fn delete(&self, key: Key) -> io::Result<()>;
+
+	/// Moves content to another key, replacing anything already stored there.
+	async fn rename(&self, from: Key, to: Key) -> io::Result<()>;
+
+	/// Lists the keys of all stored content. Anything else in the storage, like quarantined content, is skipped.
+	async fn list(&self) -> io::Result<Vec<Key>>;
+
+	/// Moves content into the [`QUARANTINE`] area, where it is no longer listed but can be inspected and restored by hand.
+	async fn quarantine(&self, key: Key) -> io::Result<()>;
+
+	/// The path of the content in the local filesystem, if it is stored there, so that it can be read without making a copy.
+	fn local_path(&self, _key: Key) -> Option<PathBuf> {
+		None
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("S3 storage: {0}")]
+	S3(#[from] s3::Error),
+}
+
+pub fn from_config(config: &Config) -> Result<Arc<dyn Storage>, Error> {
+	Ok(match &config.storage {
+		StorageBackend::Local { shard_levels } => {
+			Arc::new(Local::new(config.file_storage.clone(), *shard_levels))
+		}
+		StorageBackend::S3(s3_config) => Arc::new(S3::new(s3_config)?),
+	})
+}
+
+/// Content that can be read from a path in the local filesystem.
+pub enum LocalCopy {
+	/// The content itself.
+	Direct(PathBuf),
+	/// A copy that is deleted when this is dropped.
+	Temporary(TempPath),
+}
+
+impl Deref for LocalCopy {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		match self {
+			Self::Direct(path) => path,
+			Self::Temporary(temp_path) => temp_path,
+		}
+	}
+}
+
+/// Makes the content available in the local filesystem, for processing that needs a path, copying it into `temp_dir` if necessary.
+pub async fn local_copy(storage: &dyn Storage, key: Key, temp_dir: &Path) -> io::Result<LocalCopy> {
+	if let Some(path) = storage.local_path(key) {
+		return Ok(LocalCopy::Direct(path));
+	}
+	Ok(LocalCopy::Temporary(
+		copy_to_temp(storage, key, temp_dir).await?,
+	))
+}
+
+/// Copies the content into a new temporary file in `temp_dir`, which can be modified or stored elsewhere without affecting the original.
+pub async fn copy_to_temp(
+	storage: &dyn Storage,
+	key: Key,
+	temp_dir: &Path,
+) -> io::Result<TempPath> {
+	let temp_path = tempfile::Builder::new()
+		.tempfile_in(temp_dir)?
+		.into_temp_path();
+	let mut file = tokio::fs::File::create(&temp_path).await?;
+	let mut stream = storage.get(key, None).await?;
+	while let Some(chunk) = stream.try_next().await? {
+		file.write_all(&chunk).await?;
+	}
+	file.flush().await?;
+	Ok(temp_path)
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::Path;
+
+	use futures::TryStreamExt as _;
+	use tokio::io::AsyncWriteExt as _;
+
+	use super::{Key, Storage};
+
+	async fn read(storage: &dyn Storage, key: Key, range: Option<(u64, u64)>) -> Vec<u8> {
+		let chunks: Vec<_> = storage
+			.get(key, range)
+			.await
+			.unwrap()
+			.try_collect()
+			.await
+			.unwrap();
+		chunks.concat()
+	}
+
+	async fn put(storage: &dyn Storage, key: Key, content: &[u8], temp_dir: &Path) {
+		let temp_path = tempfile::Builder::new()
+			.tempfile_in(temp_dir)
+			.unwrap()
+			.into_temp_path();
+		let mut file = tokio::fs::File::create(&temp_path).await.unwrap();
+		file.write_all(content).await.unwrap();
+		file.flush().await.unwrap();
+		storage.put(key, temp_path).await.unwrap();
+	}
+
+	/// Checks the behavior that is common to all backends.
+	pub async fn check_backend(storage: &dyn Storage, temp_dir: &Path) {
+		let file = Key::File(1);
+		let version = Key::Version(1);
+
+		assert!(!storage.exists(file).await.unwrap());
+		assert_eq!(
+			storage.get(file, None).await.err().unwrap().kind(),
+			std::io::ErrorKind::NotFound
+		);
+
+		put(storage, file, b"0123456789", temp_dir).await;
+		assert_eq!(read(storage, file, None).await, b"0123456789");
+		assert_eq!(read(storage, file, Some((2, 4))).await, b"234");
+		assert_eq!(storage.metadata(file).await.unwrap().unwrap().size, 10);
+
+		put(storage, file, b"replaced", temp_dir).await;
+		assert_eq!(read(storage, file, None).await, b"replaced");
+
+		let copy = super::copy_to_temp(storage, file, temp_dir).await.unwrap();
+		assert_eq!(std::fs::read(&copy).unwrap(), b"replaced");
+
+		storage.rename(file, version).await.unwrap();
+		assert!(!storage.exists(file).await.unwrap());
+		assert_eq!(read(storage, version, None).await, b"replaced");
+
+		let other_files = [Key::File(2), Key::File(3), Key::File(10)];
+		for key in other_files {
+			put(storage, key, b"other", temp_dir).await;
+		}
+		let mut listed = storage.list().await.unwrap();
+		listed.sort_unstable();
+		assert_eq!(listed, [other_files.as_slice(), &[version]].concat());
+		for key in other_files {
+			storage.quarantine(key).await.unwrap();
+		}
+		assert!(!storage.exists(Key::File(2)).await.unwrap());
+		assert_eq!(storage.list().await.unwrap(), [version]);
+
+		storage.delete(version).await.unwrap();
+		assert!(!storage.exists(version).await.unwrap());
+		storage.delete(version).await.unwrap();
+	}
+
+	#[test]
+	fn key_display() {
+		assert_eq!(Key::File(12).to_string(), "12");
+		assert_eq!(Key::Version(3).to_string(), "versions/3");
+
+		assert_eq!(Key::from_path("12"), Some(Key::File(12)));
+		assert_eq!(Key::from_path("versions/3"), Some(Key::Version(3)));
+		assert_eq!(Key::from_path("012"), None);
+		assert_eq!(Key::from_path("quarantine/12.0"), None);
+		assert_eq!(Key::from_path(".tmpAbC123"), None);
+	}
+}
@@ -0,0 +1,491 @@
+//! Storage in a bucket of Amazon S3 or a compatible server.
+
+use std::io;
+use std::time::SystemTime;
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use headers::HeaderMapExt as _;
+use http::header::{HeaderName, AUTHORIZATION, CONTENT_LENGTH, ETAG, RANGE};
+use http::uri::{InvalidUri, Scheme};
+use http::{Method, StatusCode, Uri};
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper_rustls::HttpsConnector;
+use tempfile::TempPath;
+use time::OffsetDateTime;
+use tokio_util::io::ReaderStream;
+
+use super::{ByteStream, Key, Metadata, Storage};
+use crate::config::S3Config;
+
+mod sign;
+
+const COPY_SOURCE: HeaderName = HeaderName::from_static("x-amz-copy-source");
+/// How much of the body of an error response is included in the error.
+const MAX_ERROR_BODY: usize = 512;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("invalid endpoint: {0}")]
+	Endpoint(#[from] InvalidUri),
+	#[error("endpoint has no host")]
+	NoHost,
+}
+
+/// Objects are named `{prefix}{key}`.
+pub struct S3 {
+	client: hyper::Client<HttpsConnector<HttpConnector>>,
+	scheme: Scheme,
+	/// Includes the bucket, unless requests are path-style.
+	host: String,
+	/// Empty, unless requests are path-style.
+	bucket_path: String,
+	bucket: String,
+	prefix: String,
+	region: String,
+	access_key_id: String,
+	secret_access_key: String,
+}
+
+/// Turns unsuccessful responses into errors, keeping the start of the body since S3 explains errors there.
+async fn check(response: http::Response<Body>) -> io::Result<http::Response<Body>> {
+	let status = response.status();
+	if status.is_success() {
+		return Ok(response);
+	}
+	let kind = match status {
+		StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+		StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+		_ => io::ErrorKind::Other,
+	};
+	let body = hyper::body::to_bytes(response.into_body())
+		.await
+		.unwrap_or_default();
+	let body = String::from_utf8_lossy(&body[..body.len().min(MAX_ERROR_BODY)]);
+	Err(io::Error::new(
+		kind,
+		format!("S3 responded with {status}: {body}"),
+	))
+}
+
+impl S3 {
+	pub fn new(config: &S3Config) -> Result<Self, Error> {
+		let endpoint: Uri = config.endpoint.parse()?;
+		let authority = endpoint.authority().ok_or(Error::NoHost)?;
+		let (host, bucket_path) = if config.path_style {
+			(
+				authority.to_string(),
+				format!("/{}", sign::uri_encode(&config.bucket, true)),
+			)
+		} else {
+			(format!("{}.{authority}", config.bucket), String::new())
+		};
+		let connector = hyper_rustls::HttpsConnectorBuilder::new()
+			.with_webpki_roots()
+			.https_or_http()
+			.enable_http1()
+			.build();
+		Ok(Self {
+			client: hyper::Client::builder().build(connector),
+			scheme: endpoint.scheme().cloned().unwrap_or(Scheme::HTTPS),
+			host,
+			bucket_path,
+			bucket: config.bucket.clone(),
+			prefix: config.prefix.clone(),
+			region: config.region.clone(),
+			access_key_id: config.access_key_id.clone(),
+			secret_access_key: config.secret_access_key.clone(),
+		})
+	}
+
+	fn object_name(&self, key: Key) -> String {
+		format!("{}{key}", self.prefix)
+	}
+
+	/// The path of the object in requests, or of the bucket if `name` is empty.
+	fn object_path(&self, name: &str) -> String {
+		format!("{}/{}", self.bucket_path, sign::uri_encode(name, false))
+	}
+
+	/// Signs and sends a request for the object stored under `key`. All of `headers` are signed.
+	async fn send(
+		&self,
+		method: Method,
+		key: Key,
+		headers: &[(HeaderName, String)],
+		body: Body,
+	) -> io::Result<http::Response<Body>> {
+		let path = self.object_path(&self.object_name(key));
+		self.send_to(method, &path, &[], headers, body).await
+	}
+
+	/// Signs and sends a request. `path` and `query` must already be encoded with [`sign::uri_encode`].
+	async fn send_to(
+		&self,
+		method: Method,
+		path: &str,
+		query: &[(String, String)],
+		headers: &[(HeaderName, String)],
+		body: Body,
+	) -> io::Result<http::Response<Body>> {
+		let now = OffsetDateTime::now_utc();
+		let amz_date = sign::amz_date(now);
+		let mut signed_headers = vec![
+			("host", self.host.as_str()),
+			("x-amz-content-sha256", sign::UNSIGNED_PAYLOAD),
+			("x-amz-date", amz_date.as_str()),
+		];
+		signed_headers.extend(
+			headers
+				.iter()
+				.map(|(name, value)| (name.as_str(), value.as_str())),
+		);
+		let authorization = sign::authorization(
+			&sign::Request {
+				method: method.as_str(),
+				path,
+				query,
+				headers: &signed_headers,
+				payload_hash: sign::UNSIGNED_PAYLOAD,
+			},
+			&sign::Credentials {
+				access_key_id: &self.access_key_id,
+				secret_access_key: &self.secret_access_key,
+			},
+			&self.region,
+			now,
+		);
+
+		let query: Vec<_> = query
+			.iter()
+			.map(|(name, value)| format!("{name}={value}"))
+			.collect();
+		let uri = if query.is_empty() {
+			format!("{}://{}{path}", self.scheme, self.host)
+		} else {
+			format!("{}://{}{path}?{}", self.scheme, self.host, query.join("&"))
+		};
+		let mut request = http::Request::builder()
+			.method(method)
+			.uri(uri)
+			.header(AUTHORIZATION, authorization);
+		for (name, value) in signed_headers {
+			request = request.header(name, value);
+		}
+		let request = request
+			.body(body)
+			.map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+		self.client.request(request).await.map_err(io::Error::other)
+	}
+}
+
+#[async_trait::async_trait]
+impl Storage for S3 {
+	async fn put(&self, key: Key, temp_path: TempPath) -> io::Result<()> {
+		let file = tokio::fs::File::open(&temp_path).await?;
+		let size = file.metadata().await?.len();
+		let body = Body::wrap_stream(ReaderStream::new(file));
+		let response = self
+			.send(
+				Method::PUT,
+				key,
+				&[(CONTENT_LENGTH, size.to_string())],
+				body,
+			)
+			.await?;
+		check(response).await?;
+		Ok(())
+	}
+
+	async fn get(&self, key: Key, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+		let headers = match range {
+			Some((start, end)) => vec![(RANGE, format!("bytes={start}-{end}"))],
+			None => Vec::new(),
+		};
+		let response = self.send(Method::GET, key, &headers, Body::empty()).await?;
+		let response = check(response).await?;
+		Ok(response.into_body().map_err(io::Error::other).boxed())
+	}
+
+	async fn metadata(&self, key: Key) -> io::Result<Option<Metadata>> {
+		let response = self.send(Method::HEAD, key, &[], Body::empty()).await?;
+		if response.status() == StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+		let response = check(response).await?;
+		let headers = response.headers();
+		let size = headers
+			.typed_get::<headers::ContentLength>()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing content length"))?
+			.0;
+		Ok(Some(Metadata {
+			size,
+			modified: headers
+				.typed_get::<headers::LastModified>()
+				.map(SystemTime::from),
+			tag: headers
+				.get(ETAG)
+				.and_then(|etag| etag.to_str().ok())
+				.map(|etag| etag.trim_matches('"').to_owned()),
+		}))
+	}
+
+	async fn delete(&self, key: Key) -> io::Result<()> {
+		let response = self.send(Method::DELETE, key, &[], Body::empty()).await?;
+		if response.status() != StatusCode::NOT_FOUND {
+			check(response).await?;
+		}
+		Ok(())
+	}
+
+	async fn rename(&self, from: Key, to: Key) -> io::Result<()> {
+		self.move_object(from, &self.object_name(to)).await
+	}
+
+	async fn list(&self) -> io::Result<Vec<Key>> {
+		let mut keys = Vec::new();
+		let mut continuation_token: Option<String> = None;
+		loop {
+			let mut query = vec![
+				("list-type".to_owned(), "2".to_owned()),
+				("prefix".to_owned(), sign::uri_encode(&self.prefix, true)),
+			];
+			if let Some(token) = &continuation_token {
+				query.push((
+					"continuation-token".to_owned(),
+					sign::uri_encode(token, true),
+				));
+			}
+			let response = self
+				.send_to(
+					Method::GET,
+					&self.object_path(""),
+					&query,
+					&[],
+					Body::empty(),
+				)
+				.await?;
+			let body = hyper::body::to_bytes(check(response).await?.into_body())
+				.await
+				.map_err(io::Error::other)?;
+			let body = std::str::from_utf8(&body)
+				.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+			keys.extend(elements(body, "Key").filter_map(|name| {
+				let name = unescape(name);
+				Key::from_path(name.strip_prefix(self.prefix.as_str())?)
+			}));
+
+			let truncated = elements(body, "IsTruncated").next() == Some("true");
+			continuation_token = match elements(body, "NextContinuationToken").next() {
+				Some(token) if truncated => Some(unescape(token)),
+				_ => return Ok(keys),
+			};
+		}
+	}
+
+	async fn quarantine(&self, key: Key) -> io::Result<()> {
+		let to = format!("{}{}", self.prefix, key.quarantine_path());
+		self.move_object(key, &to).await
+	}
+}
+
+impl S3 {
+	/// S3 cannot rename objects, so this copies and then deletes.
+	async fn move_object(&self, from: Key, to: &str) -> io::Result<()> {
+		let source = format!(
+			"/{}/{}",
+			sign::uri_encode(&self.bucket, true),
+			sign::uri_encode(&self.object_name(from), false)
+		);
+		let response = self
+			.send_to(
+				Method::PUT,
+				&self.object_path(to),
+				&[],
+				&[(COPY_SOURCE, source)],
+				Body::empty(),
+			)
+			.await?;
+		check(response).await?;
+		self.delete(from).await
+	}
+}
+
+/// The text of each `<name>` element in `xml`. The responses from S3 are simple enough that this does not need a real XML parser.
+fn elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+	let open = format!("<{name}>");
+	let close = format!("</{name}>");
+	let mut rest = xml;
+	std::iter::from_fn(move || {
+		let start = rest.find(&open)? + open.len();
+		let end = start + rest[start..].find(&close)?;
+		let text = &rest[start..end];
+		rest = &rest[end + close.len()..];
+		Some(text)
+	})
+}
+
+/// Replaces the entities that S3 uses in element text. `&amp;` is replaced last so that escaped entities are not unescaped twice.
+fn unescape(text: &str) -> String {
+	text
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::{BTreeMap, HashMap};
+	use std::fmt::Write as _;
+	use std::net::{Ipv4Addr, TcpListener};
+	use std::sync::{Arc, Mutex};
+
+	use axum::body::Bytes;
+	use axum::extract;
+	use axum::handler::Handler as _;
+	use axum::response::{IntoResponse as _, Response};
+	use http::header::{HeaderMap, AUTHORIZATION, RANGE};
+	use http::{Method, StatusCode, Uri};
+
+	use super::{elements, unescape, S3};
+	use crate::config::S3Config;
+
+	/// Keyed by the path of the object, including the bucket.
+	type Objects = Arc<Mutex<BTreeMap<String, Bytes>>>;
+
+	const BUCKET_PATH: &str = "/bucket/";
+	/// Small, so that listing has to follow continuation tokens.
+	const LIST_PAGE_SIZE: usize = 2;
+
+	/// Parses a range of the form `bytes={start}-{end}`, which is all that the client sends.
+	fn parse_range(range: &str) -> Option<(usize, usize)> {
+		let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+		Some((start.parse().ok()?, end.parse().ok()?))
+	}
+
+	/// Lists objects like `ListObjectsV2`, using the last name on the page as the continuation token.
+	fn list(objects: &BTreeMap<String, Bytes>, query: &str) -> Response {
+		let params: HashMap<_, _> = query
+			.split('&')
+			.filter_map(|param| param.split_once('='))
+			.map(|(name, value)| {
+				let value = percent_encoding::percent_decode_str(value)
+					.decode_utf8()
+					.unwrap();
+				(name, value.into_owned())
+			})
+			.collect();
+		let prefix = params.get("prefix").map_or("", String::as_str);
+		let token = params.get("continuation-token");
+		let names: Vec<_> = objects
+			.keys()
+			.filter_map(|path| path.strip_prefix(BUCKET_PATH))
+			.filter(|name| name.starts_with(prefix) && token.is_none_or(|token| *name > token.as_str()))
+			.collect();
+		let page = &names[..names.len().min(LIST_PAGE_SIZE)];
+		let truncated = names.len() > page.len();
+
+		let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult>");
+		for name in page {
+			write!(xml, "<Contents><Key>{name}</Key></Contents>").unwrap();
+		}
+		write!(xml, "<IsTruncated>{truncated}</IsTruncated>").unwrap();
+		if let (true, Some(last)) = (truncated, page.last()) {
+			write!(xml, "<NextContinuationToken>{last}</NextContinuationToken>").unwrap();
+		}
+		xml.push_str("</ListBucketResult>");
+		xml.into_response()
+	}
+
+	/// A minimal stand-in for an S3 server. It only checks that requests are signed, not the signatures themselves.
+	async fn stand_in(
+		extract::Extension(objects): extract::Extension<Objects>,
+		method: Method,
+		uri: Uri,
+		headers: HeaderMap,
+		body: Bytes,
+	) -> Response {
+		if !matches!(
+			headers.get(AUTHORIZATION),
+			Some(authorization) if authorization.as_bytes().starts_with(b"AWS4-HMAC-SHA256 Credential=test/")
+		) {
+			return StatusCode::FORBIDDEN.into_response();
+		}
+
+		let mut objects = objects.lock().unwrap();
+		let path = uri.path().to_owned();
+		match method {
+			Method::PUT => {
+				let content = match headers.get("x-amz-copy-source") {
+					Some(source) => match objects.get(source.to_str().unwrap()) {
+						Some(content) => content.clone(),
+						None => return StatusCode::NOT_FOUND.into_response(),
+					},
+					None => body,
+				};
+				objects.insert(path, content);
+				StatusCode::OK.into_response()
+			}
+			Method::GET if uri.path() == BUCKET_PATH => list(&objects, uri.query().unwrap_or("")),
+			Method::GET | Method::HEAD => {
+				let content = match objects.get(&path) {
+					Some(content) => content.clone(),
+					None => return StatusCode::NOT_FOUND.into_response(),
+				};
+				match headers
+					.get(RANGE)
+					.and_then(|range| parse_range(range.to_str().ok()?))
+				{
+					Some((start, end)) => {
+						(StatusCode::PARTIAL_CONTENT, content.slice(start..=end)).into_response()
+					}
+					None => content.into_response(),
+				}
+			}
+			Method::DELETE => {
+				objects.remove(&path);
+				StatusCode::NO_CONTENT.into_response()
+			}
+			_ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+		}
+	}
+
+	#[tokio::test]
+	async fn backend() {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let address = listener.local_addr().unwrap();
+		let app = axum::Router::new()
+			.fallback(stand_in.into_service())
+			.layer(extract::Extension(Objects::default()));
+		tokio::spawn(
+			axum::Server::from_tcp(listener)
+				.unwrap()
+				.serve(app.into_make_service()),
+		);
+
+		let storage = S3::new(&S3Config {
+			endpoint: format!("http://{address}"),
+			bucket: "bucket".into(),
+			region: "us-east-1".into(),
+			access_key_id: "test".into(),
+			secret_access_key: "secret".into(),
+			path_style: true,
+			prefix: "shrubbery/".into(),
+		})
+		.unwrap();
+		let temp_dir = tempfile::tempdir().unwrap();
+		super::super::test::check_backend(&storage, temp_dir.path()).await;
+	}
+
+	#[test]
+	fn xml() {
+		let xml = "<ListBucketResult><Contents><Key>a&amp;lt;b</Key></Contents><Contents><Key>c</Key></Contents><IsTruncated>false</IsTruncated></ListBucketResult>";
+		let keys: Vec<_> = elements(xml, "Key").map(unescape).collect();
+		assert_eq!(keys, ["a&lt;b", "c"]);
+		assert_eq!(elements(xml, "IsTruncated").next(), Some("false"));
+		assert_eq!(elements(xml, "NextContinuationToken").next(), None);
+	}
+}
@@ -25,7 +25,7 @@ struct Lexer<I: Iterator<Item = u8>> {
 }
 
 fn char_is_special(ch: u8) -> bool {
-	b"&|!():\"".contains(&ch)
+	b"&|!():\"@".contains(&ch)
 }
 
 impl<I: Iterator<Item = u8>> Lexer<I> {
@@ -245,6 +245,7 @@ impl<I: Iterator<Item = u8>> Iterator for Lexer<I> {
 		self.next_skip_whitespace().map(|(location, ch)| {
 			match ch {
 				b'&' => Token::And,
+				b'@' => Token::At,
 				b'|' => Token::Or,
 				b'!' => Token::Not,
 				b'(' => Token::OpenParen,
@@ -282,3 +283,34 @@ impl<I: Iterator<Item = u8>> Iterator for Lexer<I> {
 pub fn lex(input: impl IntoIterator<Item = u8>) -> impl Iterator<Item = SpannedToken> {
 	Lexer::new(input.into_iter())
 }
+
+/// Format `content` so that it lexes back into a single string token with the same content.
+///
+/// It is only quoted if it can't be expressed as a bare string, so that simple names stay readable.
+#[must_use]
+pub fn quote(content: &str) -> std::borrow::Cow<'_, str> {
+	let can_be_bare = !content.is_empty()
+		&& content.trim() == content
+		&& !content
+			.bytes()
+			.any(|ch| char_is_special(ch) || ch.is_ascii_control());
+	if can_be_bare {
+		return content.into();
+	}
+
+	let mut quoted = String::with_capacity(content.len() + 2);
+	quoted.push('"');
+	for ch in content.chars() {
+		match ch {
+			'\\' => quoted.push_str(r"\\"),
+			'"' => quoted.push_str(r#"\""#),
+			'\n' => quoted.push_str(r"\n"),
+			'\r' => quoted.push_str(r"\r"),
+			'\t' => quoted.push_str(r"\t"),
+			'\0' => quoted.push_str(r"\0"),
+			ch => quoted.push(ch),
+		}
+	}
+	quoted.push('"');
+	quoted.into()
+}
@@ -25,6 +25,33 @@ fn bare_string() {
 	);
 }
 
+#[test]
+fn quote() {
+	for content in [
+		"ab c",
+		"a:b",
+		" padded ",
+		"",
+		"quote\"back\\slash",
+		"line\nbreak",
+		"@at",
+	] {
+		let quoted = super::quote(content);
+		assert_eq!(
+			lex_to_vec(&quoted)
+				.into_iter()
+				.map(|token| token.token)
+				.collect::<Vec<_>>(),
+			[Token::String {
+				content: content.into(),
+				bare: quoted == content,
+			}],
+			"{quoted}",
+		);
+	}
+	assert_eq!(super::quote("new york"), "new york");
+}
+
 struct GenerationCtx {
 	current_location: Location,
 	parsed: Vec<SpannedToken>,
@@ -41,7 +68,7 @@ impl super::token::Type {
 	}
 
 	fn random() -> Self {
-		match rand::Rng::gen_range(&mut r(), 0..8) {
+		match rand::Rng::gen_range(&mut r(), 0..9) {
 			0 | 1 => Self::String,
 			2 => Self::And,
 			3 => Self::Or,
@@ -49,6 +76,7 @@ impl super::token::Type {
 			5 => Self::OpenParen,
 			6 => Self::CloseParen,
 			7 => Self::Colon,
+			8 => Self::At,
 			_ => unreachable!("random token type index out of range"),
 		}
 	}
@@ -121,6 +149,7 @@ impl super::token::Type {
 			Self::OpenParen => ('(', Token::OpenParen),
 			Self::CloseParen => (')', Token::CloseParen),
 			Self::Colon => (':', Token::Colon),
+			Self::At => ('@', Token::At),
 			Self::Error(_) => unreachable!("test token generator will never produce error tokens"),
 		};
 		raw.push(single_char);
@@ -176,3 +205,24 @@ fn instrumented_random() {
 		}
 	}
 }
+
+#[test]
+fn property() {
+	assert_eq!(
+		lex_to_vec("@width:>1"),
+		[
+			Token::At.with_span(Span::single(0)),
+			Token::String {
+				content: "width".into(),
+				bare: true,
+			}
+			.with_span(Span { start: 1, end: 5 }),
+			Token::Colon.with_span(Span::single(6)),
+			Token::String {
+				content: ">1".into(),
+				bare: true,
+			}
+			.with_span(Span { start: 7, end: 8 }),
+		]
+	);
+}
@@ -8,6 +8,8 @@ use crate::lex::span::Span;
 pub enum Token {
 	/// `&`
 	And,
+	/// `@`
+	At,
 	/// `)`
 	CloseParen,
 	/// `:`
@@ -44,6 +46,7 @@ impl Token {
 	pub fn into_type(self) -> Type {
 		match self {
 			Self::And => Type::And,
+			Self::At => Type::At,
 			Self::CloseParen => Type::CloseParen,
 			Self::Colon => Type::Colon,
 			Self::Error(error) => Type::Error(error),
@@ -75,6 +78,7 @@ pub struct SpannedToken {
 #[allow(missing_docs)] // all the variants exactly match those of `Token`, which are documented
 pub enum Type {
 	And,
+	At,
 	CloseParen,
 	Colon,
 	Error(Box<Error>),
@@ -1,8 +1,9 @@
 //! # Viewspec
 //!
-//! Lexing and parsing of "viewspecs", which are configurations for filtering items based on tags.
+//! Lexing and parsing of "viewspecs", which are configurations for filtering items based on tags and properties.
 //!
-//! The language may be extended later with properties or other features. Assuming I don't do anything stupid, those added features should always be backwards-compatible.
+//! The language may be extended later with other features. Assuming I don't do anything stupid, those added features should always be backwards-compatible.
+//! (Adding properties made `@` a special character, so tags containing it must now be quoted.)
 
 #![warn(clippy::pedantic)]
 #![warn(
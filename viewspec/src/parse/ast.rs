@@ -4,6 +4,7 @@
 
 use std::fmt::{self, Debug, Formatter};
 
+pub use super::property::Property;
 pub use super::tag::Tag;
 
 /// The key used to refer to other nodes in the AST.
@@ -18,8 +19,10 @@ pub struct Key(u32);
 /// Note: Nodes do not own their children; they only contain references to them.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Node {
-	/// A tag, which is only leaf node in this AST
+	/// A tag, which is one of the two leaf nodes in this AST
 	Tag(Tag),
+	/// A property predicate, which is the other leaf node in this AST
+	Property(Property),
 	/// An "and" operation, such as `a & b`
 	And(Key, Key),
 	/// An "or" operation, such as `a | b`
@@ -88,6 +91,27 @@ impl Ast {
 		&'a self,
 		mut predicate: impl FnMut(&'a Tag) -> Option<U>,
 	) -> Option<U> {
+		self.find_map_leaf(|node| match node {
+			Node::Tag(tag) => predicate(tag),
+			_ => None,
+		})
+	}
+
+	/// Find a property within the AST by a predicate.
+	///
+	/// Prefers properties that occurred earlier in the input.
+	#[must_use]
+	pub fn find_map_property<'a, U>(
+		&'a self,
+		mut predicate: impl FnMut(&'a Property) -> Option<U>,
+	) -> Option<U> {
+		self.find_map_leaf(|node| match node {
+			Node::Property(property) => predicate(property),
+			_ => None,
+		})
+	}
+
+	fn find_map_leaf<'a, U>(&'a self, mut predicate: impl FnMut(&'a Node) -> Option<U>) -> Option<U> {
 		let mut stack = smallvec::SmallVec::<[_; 50]>::new();
 
 		macro_rules! step {
@@ -97,8 +121,8 @@ impl Ast {
 						stack.extend([*left, *right].into_iter().rev())
 					}
 					Node::Not(child) => stack.push(*child),
-					Node::Tag(tag) => {
-						if let Some(ret) = predicate(tag) {
+					leaf @ (Node::Tag(..) | Node::Property(..)) => {
+						if let Some(ret) = predicate(leaf) {
 							return Some(ret);
 						}
 					}
@@ -117,6 +141,7 @@ impl Ast {
 }
 
 impl Debug for Ast {
+	#[allow(clippy::too_many_lines)] // mostly boilerplate
 	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
 		const DEBUG_PRETTY_TABSTOP: usize = 4;
 
@@ -220,6 +245,10 @@ impl Debug for Ast {
 					indent!();
 					write!(formatter, "{tag:?}",)?; // don't print in pretty mode, to avoid complicating indentation
 				}
+				Node::Property(property) => {
+					indent!();
+					write!(formatter, "{property:?}")?;
+				}
 			}
 		}
 
@@ -13,6 +13,30 @@ pub enum Error {
 	/// While parsing a tag, `0` was found, or an EOF was found if `0` is `None`.
 	#[error("expected a tag but got {0:?}")]
 	ExpectedTagGot(Option<(Span, TokenType)>),
+	/// After the colon that starts a tag without a category, `got` was found instead of the tag's name, or an EOF was found if `got` is `None`.
+	#[error("expected a tag name but got {got:?}")]
+	ExpectedTagNameGot {
+		/// The span of the colon.
+		colon_span: Span,
+		/// What was found instead.
+		got: Option<(Span, TokenType)>,
+	},
+	/// After the `@` that starts a property, `got` was found instead of the property's name, or an EOF was found if `got` is `None`.
+	#[error("expected a property name but got {got:?}")]
+	ExpectedPropertyNameGot {
+		/// The span of the `@`.
+		at_span: Span,
+		/// What was found instead.
+		got: Option<(Span, TokenType)>,
+	},
+	/// After the colon in a property, `got` was found instead of the value, or an EOF was found if `got` is `None`.
+	#[error("expected a property value but got {got:?}")]
+	ExpectedPropertyValueGot {
+		/// The span of the colon.
+		colon_span: Span,
+		/// What was found instead.
+		got: Option<(Span, TokenType)>,
+	},
 	/// Expected a closing parenthesis for the opening parenthesis found at `location`.
 	#[error("unclosed parenthesis")]
 	UnclosedParenthesis {
@@ -15,17 +15,19 @@
 //! ```text
 //! expression0 = expression1 (binary_op expression1)*
 //! expression1 = unary_op* expression2
-//! expression2 = tag | OPEN_PAREN expression0 CLOSE_PAREN
-//! tag = STRING | STRING COLON | STRING COLON STRING
+//! expression2 = tag | property | OPEN_PAREN expression0 CLOSE_PAREN
+//! tag = STRING | STRING COLON | STRING COLON STRING | COLON STRING
+//! property = AT STRING | AT STRING COLON STRING
 //! binary_op = AND | OR
 //! unary_op = NOT
 //! ```
 
-use crate::lex::span::Location;
+use crate::lex::span::{Location, Span};
 use crate::lex::token::{SpannedToken, Token};
 
 pub mod ast;
 pub mod error;
+pub mod property;
 pub mod tag;
 #[cfg(test)]
 mod test;
@@ -88,8 +90,10 @@ pub fn parse(input: impl Iterator<Item = SpannedToken>) -> Result<Ast> {
 			}
 			StackEntry::Expression2 => {
 				// pseudo:
-				// otherwise, set root to tag and push nothing
-				if let Some(open_paren) = input.next_if(|token| token.token == Token::OpenParen) {
+				// otherwise, set root to tag or property and push nothing
+				if let Some(at) = input.next_if(|token| token.token == Token::At) {
+					root = Some(property(&mut input, at.span)?);
+				} else if let Some(open_paren) = input.next_if(|token| token.token == Token::OpenParen) {
 					stack.extend(
 						[
 							StackEntry::Expression1,
@@ -163,19 +167,39 @@ pub fn parse(input: impl Iterator<Item = SpannedToken>) -> Result<Ast> {
 }
 
 fn tag(input: &mut std::iter::Peekable<impl Iterator<Item = SpannedToken>>) -> Result<Node> {
+	// a leading colon stands for no category
+	let colon_span = input
+		.next_if(|token| token.token == Token::Colon)
+		.map(|token| token.span);
 	let first = input.next();
-	let (first, first_span) = match first {
-		Some(SpannedToken {
-			token: Token::String { content, .. },
-			span,
-		}) => (content, span),
-		other => {
+	let (first, first_span) = match (first, colon_span) {
+		(
+			Some(SpannedToken {
+				token: Token::String { content, .. },
+				span,
+			}),
+			_,
+		) => (content, span),
+		(other, None) => {
 			return Err(Error::ExpectedTagGot(
 				other.map(|token| (token.span, token.token.into_type())),
 			));
 		}
+		(other, Some(colon_span)) => {
+			return Err(Error::ExpectedTagNameGot {
+				colon_span,
+				got: other.map(|token| (token.span, token.token.into_type())),
+			});
+		}
 	};
+	if colon_span.is_some() {
+		return Ok(Node::Tag(ast::Tag::uncategorized(&first, first_span)));
+	}
 	let tag = if input.next_if(|token| token.token == Token::Colon).is_some() {
+		// a second colon includes the category's subcategories
+		if input.next_if(|token| token.token == Token::Colon).is_some() {
+			return Ok(Node::Tag(ast::Tag::category_tree(&first, first_span)));
+		}
 		match input.next_if(|token| {
 			matches!(
 				token,
@@ -203,6 +227,48 @@ fn tag(input: &mut std::iter::Peekable<impl Iterator<Item = SpannedToken>>) -> R
 	Ok(Node::Tag(tag))
 }
 
+/// It is assumed that the at sign, spanning `at_span`, was already read.
+fn property(
+	input: &mut std::iter::Peekable<impl Iterator<Item = SpannedToken>>,
+	at_span: Span,
+) -> Result<Node> {
+	let (name, name_span) = match input.next() {
+		Some(SpannedToken {
+			token: Token::String { content, .. },
+			span,
+		}) => (content, span),
+		other => {
+			return Err(Error::ExpectedPropertyNameGot {
+				at_span,
+				got: other.map(|token| (token.span, token.token.into_type())),
+			});
+		}
+	};
+	let value = if let Some(colon) = input.next_if(|token| token.token == Token::Colon) {
+		match input.next() {
+			Some(SpannedToken {
+				token: Token::String { content, bare },
+				span,
+			}) => Some((content, span, bare)),
+			other => {
+				return Err(Error::ExpectedPropertyValueGot {
+					colon_span: colon.span,
+					got: other.map(|token| (token.span, token.token.into_type())),
+				});
+			}
+		}
+	} else {
+		None
+	};
+	Ok(Node::Property(ast::Property::new(
+		&name,
+		name_span,
+		value
+			.as_ref()
+			.map(|(content, span, bare)| (&**content, *span, *bare)),
+	)))
+}
+
 /* OLD RECURSIVE IMPLEMENTATION
 
 /// Parse a sequence of `SpannedToken` into an `Ast`.
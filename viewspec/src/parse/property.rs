@@ -0,0 +1,123 @@
+//! Provides [`Property`] and its support types.
+
+use crate::lex::span::Span;
+
+/// A node of the AST that filters items based on one of their properties rather than their tags.
+///
+/// Which properties exist, and what their values mean, is up to the consumer of the AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+	/// The name of the property, such as `width` in `@width:>100`.
+	pub name: Box<str>,
+	/// The span of the name.
+	pub name_span: Span,
+	/// The condition on the property's value, or `None` if the item simply has to have the property, like `@width`.
+	pub predicate: Option<Predicate>,
+}
+
+/// The condition on the value of a [`Property`], such as `>100` in `@width:>100`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Predicate {
+	/// How the value of the property is compared to `value`.
+	pub comparison: Comparison,
+	/// The value to compare against, with the comparison operator removed.
+	pub value: Box<str>,
+	/// The span of the value, not including the comparison operator.
+	pub value_span: Span,
+}
+
+/// A comparison operator that prefixes the value of a [`Predicate`].
+///
+/// If the value has no operator prefix, or the value was quoted, the comparison is [`Comparison::Equal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+	/// No prefix, or `=`
+	Equal,
+	/// `<`
+	Less,
+	/// `<=`
+	LessOrEqual,
+	/// `>`
+	Greater,
+	/// `>=`
+	GreaterOrEqual,
+}
+
+impl Comparison {
+	/// The operators in the order they should be tried, so that `<=` is not mistaken for `<`.
+	const PREFIXES: [(&'static str, Self); 5] = [
+		("<=", Self::LessOrEqual),
+		(">=", Self::GreaterOrEqual),
+		("<", Self::Less),
+		(">", Self::Greater),
+		("=", Self::Equal),
+	];
+
+	/// Split the comparison operator, if any, off the front of a value.
+	///
+	/// Returns the comparison along with the number of bytes that were removed from the front of the value.
+	#[must_use]
+	pub fn split(value: &str) -> (Self, usize) {
+		let (comparison, rest) = Self::PREFIXES
+			.iter()
+			.find_map(|&(prefix, comparison)| Some((comparison, value.strip_prefix(prefix)?)))
+			.unwrap_or((Self::Equal, value));
+		let rest = rest.trim_start();
+		(comparison, value.len() - rest.len())
+	}
+
+	/// The SQL operator that corresponds to this comparison.
+	#[must_use]
+	pub fn as_sql(self) -> &'static str {
+		match self {
+			Self::Equal => "=",
+			Self::Less => "<",
+			Self::LessOrEqual => "<=",
+			Self::Greater => ">",
+			Self::GreaterOrEqual => ">=",
+		}
+	}
+}
+
+impl Property {
+	/// Create a property from the parts of `@name:value`.
+	///
+	/// If `value` was a bare string, any comparison operator is split off of it. Quoted values are always compared for equality, so that values starting with an operator can still be expressed.
+	#[must_use]
+	pub fn new(name: &str, name_span: Span, value: Option<(&str, Span, bool)>) -> Self {
+		let predicate = value.map(|(value, value_span, bare)| {
+			if bare {
+				let (comparison, prefix_len) = Comparison::split(value);
+				let value_span = if value_span == Span::null() {
+					value_span
+				} else {
+					Span {
+						start: std::cmp::min(
+							value_span
+								.start
+								.saturating_add(u32::try_from(prefix_len).unwrap_or(u32::MAX)),
+							value_span.end,
+						),
+						end: value_span.end,
+					}
+				};
+				Predicate {
+					comparison,
+					value: value[prefix_len..].into(),
+					value_span,
+				}
+			} else {
+				Predicate {
+					comparison: Comparison::Equal,
+					value: value.into(),
+					value_span,
+				}
+			}
+		});
+		Self {
+			name: name.into(),
+			name_span,
+			predicate,
+		}
+	}
+}
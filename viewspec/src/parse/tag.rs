@@ -20,6 +20,14 @@ impl Tag {
 		Self(TagInner::new(&[name], TagKind::Name(span)))
 	}
 
+	/// Create a [`Tag`] that contains a `name`, of a tag that has no category.
+	///
+	/// Analogous to `Ref::Uncategorized`.
+	#[must_use]
+	pub fn uncategorized(name: &str, span: Span) -> Self {
+		Self(TagInner::new(&[name], TagKind::Uncategorized(span)))
+	}
+
 	/// Create a [`Tag`] that contains a `category`.
 	///
 	/// Analogous to `Ref::Category`.
@@ -28,6 +36,14 @@ impl Tag {
 		Self(TagInner::new(&[category], TagKind::Category(span)))
 	}
 
+	/// Create a [`Tag`] that contains a `category`, including its subcategories.
+	///
+	/// Analogous to `Ref::CategoryTree`.
+	#[must_use]
+	pub fn category_tree(category: &str, span: Span) -> Self {
+		Self(TagInner::new(&[category], TagKind::CategoryTree(span)))
+	}
+
 	/// Create a [`Tag`] that contains both a `category` and a `name`.
 	///
 	/// Analogous to `Ref::Both`.
@@ -54,7 +70,9 @@ impl Tag {
 	pub fn as_ref(&self) -> Ref<'_> {
 		match self.0.kind {
 			TagKind::Name(span) => Ref::Name(&self.0.data, span),
+			TagKind::Uncategorized(span) => Ref::Uncategorized(&self.0.data, span),
 			TagKind::Category(span) => Ref::Category(&self.0.data, span),
+			TagKind::CategoryTree(span) => Ref::CategoryTree(&self.0.data, span),
 			TagKind::Both {
 				category_span,
 				name_span,
@@ -101,8 +119,12 @@ impl std::fmt::Debug for Tag {
 pub enum Ref<'a> {
 	#[allow(missing_docs)]
 	Name(&'a str, Span),
+	/// Like `Name`, but only the tag that has no category, like `Both` with no category.
+	Uncategorized(&'a str, Span),
 	#[allow(missing_docs)]
 	Category(&'a str, Span),
+	/// Like `Category`, but also any tag within the category's subcategories, at any depth.
+	CategoryTree(&'a str, Span),
 	#[allow(missing_docs)]
 	Both {
 		category: &'a str,
@@ -121,7 +143,9 @@ impl Ref<'_> {
 	fn to_owned(self) -> Tag {
 		match self {
 			Self::Name(name, span) => Tag::name(name, span),
+			Self::Uncategorized(name, span) => Tag::uncategorized(name, span),
 			Self::Category(category, span) => Tag::category(category, span),
+			Self::CategoryTree(category, span) => Tag::category_tree(category, span),
 			Self::Both {
 				category,
 				category_span,
@@ -135,7 +159,9 @@ impl Ref<'_> {
 #[derive(Debug, Clone, Copy)]
 enum TagKind {
 	Name(Span),
+	Uncategorized(Span),
 	Category(Span),
+	CategoryTree(Span),
 	Both {
 		name_span: Span,
 		category_span: Span,
@@ -2,6 +2,7 @@ use crate::lex::span::Span;
 use crate::lex::token::Token;
 use crate::parse::ast::{Ast, Node};
 use crate::parse::parse;
+use crate::parse::property::{Comparison, Predicate, Property};
 use crate::parse::tag::Tag;
 
 fn test_parse(tokens: impl IntoIterator<Item = Token>) -> Ast {
@@ -34,6 +35,22 @@ fn basic_category() {
 	assert_eq!(ast.root(), &Node::Tag(Tag::category("abc", Span::null())));
 }
 
+#[test]
+fn basic_category_tree() {
+	let ast = test_parse([
+		Token::String {
+			content: "abc".into(),
+			bare: true,
+		},
+		Token::Colon,
+		Token::Colon,
+	]);
+	assert_eq!(
+		ast.root(),
+		&Node::Tag(Tag::category_tree("abc", Span::null()))
+	);
+}
+
 #[test]
 fn basic_both() {
 	let ast = test_parse([
@@ -185,3 +202,83 @@ fn associativity() {
 		_ => panic!("expected Or node"),
 	}
 }
+
+#[test]
+fn property() {
+	let ast = test_parse([
+		Token::At,
+		Token::String {
+			content: "width".into(),
+			bare: true,
+		},
+		Token::Colon,
+		Token::String {
+			content: ">= 100".into(),
+			bare: true,
+		},
+		Token::And,
+		Token::At,
+		Token::String {
+			content: "camera".into(),
+			bare: true,
+		},
+		Token::Colon,
+		Token::String {
+			content: "<none>".into(),
+			bare: false,
+		},
+		Token::Or,
+		Token::At,
+		Token::String {
+			content: "duration".into(),
+			bare: true,
+		},
+	]);
+
+	let property = |name: &str, predicate: Option<(Comparison, &str)>| {
+		Node::Property(Property {
+			name: name.into(),
+			name_span: Span::null(),
+			predicate: predicate.map(|(comparison, value)| Predicate {
+				comparison,
+				value: value.into(),
+				value_span: Span::null(),
+			}),
+		})
+	};
+
+	match ast.root() {
+		Node::Or(left, right) => {
+			match ast.resolve_key(*left) {
+				Node::And(left, right) => {
+					assert_eq!(
+						ast.resolve_key(*left),
+						&property("width", Some((Comparison::GreaterOrEqual, "100")))
+					);
+					assert_eq!(
+						ast.resolve_key(*right),
+						&property("camera", Some((Comparison::Equal, "<none>")))
+					);
+				}
+				_ => panic!("expected And node"),
+			};
+			assert_eq!(ast.resolve_key(*right), &property("duration", None));
+		}
+		_ => panic!("expected Or node"),
+	}
+}
+
+#[test]
+fn basic_uncategorized() {
+	let ast = test_parse([
+		Token::Colon,
+		Token::String {
+			content: "abc".into(),
+			bare: true,
+		},
+	]);
+	assert_eq!(
+		ast.root(),
+		&Node::Tag(Tag::uncategorized("abc", Span::null()))
+	);
+}